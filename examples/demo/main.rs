@@ -5,6 +5,7 @@ use std::cell::RefCell;
 #[path = "unigui-classic/src/lib.rs"]
 mod unigui_classic;
 use unigui_classic::button::Button;
+use unigui_classic::text_input::TextInput;
 
 #[derive(Debug)]
 pub struct App {
@@ -22,6 +23,8 @@ impl App {
         div.add_inline(Box::new(Button {}));
         div.add_inline(Box::new(Button {}));
         div.add_inline(Box::new(Button {}));
+        div.add_block(Box::new(TextInput::new().with_placeholder("Type here")));
+        div.add_block(Box::new(TextInput::multiline()));
 
         App {
             image_id: RefCell::new(None),
@@ -61,6 +64,10 @@ impl unigui::Widget for App {
 
         self.div.render(surface);
     }
+
+    fn handle_event(&mut self, event: &unigui::winit::event::WindowEvent) -> bool {
+        self.div.handle_event(event)
+    }
 }
 
 fn main() {
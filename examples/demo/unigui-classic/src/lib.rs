@@ -1 +1,2 @@
 pub mod button;
+pub mod text_input;
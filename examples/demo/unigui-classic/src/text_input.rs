@@ -0,0 +1,96 @@
+use unigui::editing::TextEditor;
+use unigui::prelude::*;
+use unigui::winit::event::WindowEvent;
+
+/// A field where text can be typed, on one line or several.
+pub struct TextInput {
+    /// The text, its cursor and how it is drawn.
+    pub editor: TextEditor,
+    placeholder: String,
+}
+
+impl TextInput {
+    const PADDING: f32 = 4.0;
+
+    pub fn new() -> TextInput {
+        TextInput {
+            editor: TextEditor::new(),
+            placeholder: String::new(),
+        }
+    }
+
+    pub fn multiline() -> TextInput {
+        let mut input = TextInput::new();
+        input.editor.multiline = true;
+        input
+    }
+
+    /// Sets the text shown while the input is empty and not focused.
+    pub fn with_placeholder(mut self, placeholder: impl Into<String>) -> TextInput {
+        self.placeholder = placeholder.into();
+        self
+    }
+}
+
+impl Default for TextInput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Widget for TextInput {
+    fn allocate_area(
+        &mut self,
+        _screen_size: (usize, usize),
+        _container_size: (usize, usize),
+    ) -> WidgetSize {
+        let line_height = self.editor.options.scale * 1.25 + 2.0 * TextInput::PADDING;
+        let lines = if self.editor.multiline { 4.0 } else { 1.0 };
+        WidgetSize {
+            min_width: 50.0,
+            width: 200.0,
+            max_width: 400.0,
+            min_height: line_height,
+            height: line_height * lines,
+            max_height: line_height * lines,
+        }
+    }
+
+    fn render(&self, mut surface: Area) {
+        let rect = surface.rect;
+        let border = if self.editor.focused {
+            [0.2, 0.4, 0.9, 1.0]
+        } else {
+            [0.6, 0.6, 0.6, 1.0]
+        };
+        surface.backend.add_rect(rect, &Paint::Solid(border));
+        let inside = Rect {
+            min: (rect.min.0 + 1.0, rect.min.1 + 1.0),
+            max: (rect.max.0 - 1.0, rect.max.1 - 1.0),
+        };
+        surface
+            .backend
+            .add_rect(inside, &Paint::Solid([1.0, 1.0, 1.0, 1.0]));
+
+        let padding = TextInput::PADDING;
+        let mut text_area = surface.subarea(Rect {
+            min: (padding, padding),
+            max: (rect.width() - padding, rect.height() - padding),
+        });
+        if self.editor.text().is_empty() && !self.editor.focused {
+            text_area.add_text(
+                &self.placeholder,
+                &TextOptions {
+                    color: [0.5, 0.5, 0.5, 1.0],
+                    scale: self.editor.options.scale,
+                    ..TextOptions::default()
+                },
+            );
+        }
+        self.editor.render(&mut text_area);
+    }
+
+    fn handle_event(&mut self, event: &WindowEvent) -> bool {
+        self.editor.handle_event(event)
+    }
+}
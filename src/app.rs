@@ -2,7 +2,12 @@ use crate::*;
 
 pub trait App: Widget {}
 
-pub fn run<App: crate::app::App + 'static>(mut app: App) -> ! {
+pub fn run<App: crate::app::App + 'static>(app: App) -> ! {
+    run_with_settings(app, Settings::default())
+}
+
+/// Same as [run], but allows choosing startup [Settings] such as the [AntiAliasing](crate::graphics::AntiAliasing) method.
+pub fn run_with_settings<App: crate::app::App + 'static>(app: App, settings: Settings) -> ! {
     use winit::{
         event::*,
         event_loop::{ControlFlow, EventLoop},
@@ -21,12 +26,7 @@ pub fn run<App: crate::app::App + 'static>(mut app: App) -> ! {
         panic!("On a non-unix OS, please run the app on the main thread");
     };
     let window = WindowBuilder::new().build(&event_loop).unwrap();
-
-    let mut state = futures::executor::block_on(WgpuBackend::new(
-        &window,
-        include_bytes!("graphics/ressources/Inconsolata-Regular.ttf"),
-    ));
-    let mut window_size = window.inner_size();
+    let mut runtime = Runtime::with_settings(app, &window, settings);
 
     event_loop.run(move |event, _, control_flow| match event {
         Event::WindowEvent {
@@ -43,35 +43,17 @@ pub fn run<App: crate::app::App + 'static>(mut app: App) -> ! {
                     },
                 ..
             } => *control_flow = ControlFlow::Exit,
-            WindowEvent::Resized(physical_size) => {
-                window_size = physical_size.to_owned();
-                state.resize(*physical_size);
+            event => {
+                runtime.handle_window_event(event);
             }
-            WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
-                window_size = (*new_inner_size).to_owned();
-                state.resize(**new_inner_size);
-            }
-            _ => {}
         },
-        Event::RedrawRequested(_) => {
-            app.update();
-            app.allocate_area(
-                (window_size.width as usize, window_size.height as usize),
-                (window_size.width as usize, window_size.height as usize),
-            );
-            app.render(Area::new(Rect::sized(0.0, 0.0, 1920.0, 1080.0), &mut state));
-
-            state.update();
-            match state.render() {
-                Ok(_) => {}
-                // Recreate the swap_chain if lost
-                Err(wgpu::SwapChainError::Lost) => state.resize(state.size),
-                // The system is out of memory, we should probably quit
-                Err(wgpu::SwapChainError::OutOfMemory) => *control_flow = ControlFlow::Exit,
-                // All other errors (Outdated, Timeout) should be resolved by the next frame
-                Err(e) => eprintln!("{:?}", e),
-            }
-        }
+        Event::RedrawRequested(_) => match runtime.render() {
+            Ok(_) => {}
+            // The system is out of memory, we should probably quit
+            Err(wgpu::SwapChainError::OutOfMemory) => *control_flow = ControlFlow::Exit,
+            // All other errors (Outdated, Timeout) should be resolved by the next frame
+            Err(e) => eprintln!("{:?}", e),
+        },
         Event::MainEventsCleared => {
             // RedrawRequested will only trigger once, unless we manually request it.
             window.request_redraw();
@@ -79,3 +61,196 @@ pub fn run<App: crate::app::App + 'static>(mut app: App) -> ! {
         _ => {}
     });
 }
+
+/// Runs an [App] in a window whose event loop is owned by the caller, unlike [run].
+///
+/// Events of the loop are forwarded with [Runtime::handle_event], and frames are rendered with [Runtime::render], typically on `RedrawRequested`.
+pub struct Runtime<App: crate::app::App> {
+    app: App,
+    backend: WgpuBackend,
+    window_id: winit::window::WindowId,
+    window_size: winit::dpi::PhysicalSize<u32>,
+}
+
+impl<App: crate::app::App> Runtime<App> {
+    pub fn new(app: App, window: &winit::window::Window) -> Self {
+        Runtime::with_settings(app, window, Settings::default())
+    }
+
+    /// Same as [Runtime::new], but allows choosing startup [Settings].
+    pub fn with_settings(app: App, window: &winit::window::Window, settings: Settings) -> Self {
+        let backend = futures::executor::block_on(WgpuBackend::new(
+            window,
+            include_bytes!("graphics/ressources/Inconsolata-Regular.ttf"),
+            &settings,
+        ));
+
+        Runtime {
+            app,
+            backend,
+            window_id: window.id(),
+            window_size: window.inner_size(),
+        }
+    }
+
+    /// Processes an event of the event loop.
+    /// Returns `true` if the event was consumed, in which case the caller should not handle it too.
+    /// Events of other windows are ignored.
+    pub fn handle_event<T>(&mut self, event: &winit::event::Event<T>) -> bool {
+        match event {
+            winit::event::Event::WindowEvent { event, window_id }
+                if *window_id == self.window_id =>
+            {
+                self.handle_window_event(event)
+            }
+            _ => false,
+        }
+    }
+
+    /// Same as [Runtime::handle_event], for an event that is known to be about the window of the runtime.
+    /// Events that are not about the window itself are given to the app, see [Widget::handle_event].
+    /// Size changes resize the backend, and are not consumed since the caller may have to resize its own resources.
+    pub fn handle_window_event(&mut self, event: &winit::event::WindowEvent) -> bool {
+        let (backend, window_size) = (&mut self.backend, &mut self.window_size);
+        dispatch_window_event(&mut self.app, event, |size| {
+            *window_size = size;
+            backend.resize(size);
+        })
+    }
+
+    /// Updates the app, lays it out and renders it to the window.
+    /// A lost swap chain is recreated, other errors are returned.
+    pub fn render(&mut self) -> Result<(), wgpu::SwapChainError> {
+        self.app.update();
+        self.app.allocate_area(
+            (
+                self.window_size.width as usize,
+                self.window_size.height as usize,
+            ),
+            (
+                self.window_size.width as usize,
+                self.window_size.height as usize,
+            ),
+        );
+        self.app.render(Area::new(
+            Rect::sized(
+                0.0,
+                0.0,
+                self.window_size.width as f32,
+                self.window_size.height as f32,
+            ),
+            &mut self.backend,
+        ));
+
+        self.backend.update();
+        match self.backend.render() {
+            // Recreate the swap_chain if lost
+            Err(wgpu::SwapChainError::Lost) => {
+                self.backend.resize(self.backend.size);
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    pub fn app(&self) -> &App {
+        &self.app
+    }
+
+    /// Gives access to the app, to forward it events for example.
+    pub fn app_mut(&mut self) -> &mut App {
+        &mut self.app
+    }
+
+    pub fn backend(&self) -> &WgpuBackend {
+        &self.backend
+    }
+
+    /// Gives access to the backend, to create textures or read the frame back for example.
+    pub fn backend_mut(&mut self) -> &mut WgpuBackend {
+        &mut self.backend
+    }
+}
+
+/// Calls `resize` with the new size of the window if the event changes it, or gives the event to the app.
+/// Returns whether the app consumed the event.
+fn dispatch_window_event(
+    app: &mut impl Widget,
+    event: &winit::event::WindowEvent,
+    resize: impl FnOnce(winit::dpi::PhysicalSize<u32>),
+) -> bool {
+    use winit::event::WindowEvent;
+
+    match event {
+        WindowEvent::Resized(physical_size) => {
+            resize(*physical_size);
+            false
+        }
+        WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+            resize(**new_inner_size);
+            false
+        }
+        event => app.handle_event(event),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::dpi::PhysicalSize;
+    use winit::event::WindowEvent;
+
+    /// Consumes characters, and counts the events it is given.
+    #[derive(Default)]
+    struct Recorder {
+        events: usize,
+    }
+
+    impl Widget for Recorder {
+        fn allocate_area(&mut self, _: (usize, usize), _: (usize, usize)) -> WidgetSize {
+            WidgetSize {
+                min_width: 0.0,
+                width: 0.0,
+                max_width: 0.0,
+                min_height: 0.0,
+                height: 0.0,
+                max_height: 0.0,
+            }
+        }
+
+        fn render(&self, _: Area) {}
+
+        fn handle_event(&mut self, event: &WindowEvent) -> bool {
+            self.events += 1;
+            matches!(event, WindowEvent::ReceivedCharacter(_))
+        }
+    }
+
+    #[test]
+    fn test_dispatch_window_event() {
+        let mut app = Recorder::default();
+        let mut size = None;
+
+        // Size changes go to the caller, and are not consumed
+        let event = WindowEvent::Resized(PhysicalSize::new(800, 600));
+        assert!(!dispatch_window_event(&mut app, &event, |new_size| size = Some(new_size)));
+        assert_eq!(size, Some(PhysicalSize::new(800, 600)));
+        let mut new_inner_size = PhysicalSize::new(1600, 1200);
+        let event = WindowEvent::ScaleFactorChanged {
+            scale_factor: 2.0,
+            new_inner_size: &mut new_inner_size,
+        };
+        assert!(!dispatch_window_event(&mut app, &event, |new_size| size = Some(new_size)));
+        assert_eq!(size, Some(PhysicalSize::new(1600, 1200)));
+        assert_eq!(app.events, 0);
+
+        // Other events go to the app, which tells whether it consumed them
+        size = None;
+        let event = WindowEvent::ReceivedCharacter('a');
+        assert!(dispatch_window_event(&mut app, &event, |new_size| size = Some(new_size)));
+        let event = WindowEvent::Focused(true);
+        assert!(!dispatch_window_event(&mut app, &event, |new_size| size = Some(new_size)));
+        assert_eq!(app.events, 2);
+        assert_eq!(size, None);
+    }
+}
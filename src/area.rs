@@ -27,6 +27,103 @@ impl<'a> Area<'a> {
             backend: self.backend,
         }
     }
+
+    /// Renders to a new texture of the given size instead of the screen.
+    /// Everything drawn on the area passed to `render` goes to the texture, which is then returned.
+    /// The texture can be drawn like any image, during this frame or later ones.
+    pub fn render_to_texture(&mut self, size: (u32, u32), render: impl FnOnce(Area)) -> TextureId {
+        self.backend.begin_offscreen_layer(size);
+        render(Area::new(
+            Rect::sized(0.0, 0.0, size.0 as f32, size.1 as f32),
+            self.backend,
+        ));
+        self.backend.end_offscreen_layer()
+    }
+
+    /// Draws with an opacity applied to everything drawn on the area passed to `render`, as a whole.
+    /// Unlike fading each shape, overlapping parts don't show through each other.
+    /// The content is composited through an offscreen layer, unless it is fully opaque.
+    pub fn with_opacity(&mut self, opacity: f32, render: impl FnOnce(Area)) {
+        if opacity <= 0.0 {
+            return;
+        }
+        if opacity >= 1.0 {
+            render(Area::new(self.rect, self.backend));
+            return;
+        }
+
+        let size = (self.width().ceil() as u32, self.height().ceil() as u32);
+        let texture_id = self.render_to_texture(size, render);
+        self.backend.add_image_with_options(
+            Rect::sized(
+                self.rect.min.0,
+                self.rect.min.1,
+                size.0 as f32,
+                size.1 as f32,
+            ),
+            texture_id,
+            &ImageOptions {
+                opacity,
+                ..ImageOptions::default()
+            },
+        );
+    }
+
+    /// Draws everything drawn on the area passed to `render` with a [BlendMode].
+    pub fn with_blend_mode(&mut self, blend_mode: BlendMode, render: impl FnOnce(Area)) {
+        let previous = self.backend.blend_mode();
+        self.backend.set_blend_mode(blend_mode);
+        render(Area::new(self.rect, self.backend));
+        self.backend.set_blend_mode(previous);
+    }
+
+    /// Draws everything drawn on the area passed to `render` with a [Transform], in the coordinates of this area.
+    /// For example, `Transform::rotation(angle).around(area.rect.min)` rotates around the top left corner of the area.
+    pub fn with_transform(&mut self, transform: Transform, render: impl FnOnce(Area)) {
+        self.backend.push_transform(transform);
+        render(Area::new(self.rect, self.backend));
+        self.backend.pop_transform();
+    }
+
+    /// Hides everything drawn on the area passed to `render` that goes beyond the rectangle of this area.
+    pub fn with_clip(&mut self, render: impl FnOnce(Area)) {
+        self.backend.push_clip(self.rect);
+        render(Area::new(self.rect, self.backend));
+        self.backend.pop_clip();
+    }
+
+    /// Draws text in the area, see [WgpuBackend::add_text_in_rect].
+    pub fn add_text(&mut self, text: &str, options: &TextOptions) {
+        self.backend.add_text_in_rect(text, self.rect, options);
+    }
+
+    /// Draws rich text in the area, see [WgpuBackend::add_rich_text].
+    pub fn add_rich_text(&mut self, text: &RichText, options: &TextOptions) -> RichTextLayout {
+        self.backend.add_rich_text(text, self.rect, options)
+    }
+
+    /// Converts a point (like the position of the cursor) to the coordinates of the area, undoing the current transform.
+    /// Returns [None] if the transform flattens the area.
+    ///
+    /// Points are in pixels of the window, or of the texture when drawing in an offscreen layer.
+    pub fn to_local(&self, point: (f32, f32)) -> Option<(f32, f32)> {
+        let inverse = self.backend.transform().inverse()?;
+        Some(inverse.apply(point))
+    }
+
+    /// Whether a point (like the position of the cursor) is over the area, as it is drawn.
+    /// Transforms and clips are taken into account, see [Area::to_local].
+    pub fn contains(&self, point: (f32, f32)) -> bool {
+        let (x, y) = match self.to_local(point) {
+            Some(local) => local,
+            None => return false,
+        };
+        x >= self.rect.min.0
+            && x <= self.rect.max.0
+            && y >= self.rect.min.1
+            && y <= self.rect.max.1
+            && self.backend.clip_region().contains(point)
+    }
 }
 
 impl<'a> std::fmt::Debug for Area<'a> {
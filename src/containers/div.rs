@@ -126,6 +126,14 @@ impl Widget for Div {
             widget.render(area);
         }
     }
+
+    fn handle_event(&mut self, event: &winit::event::WindowEvent) -> bool {
+        let mut consumed = false;
+        for (widget, _) in &mut self.widgets {
+            consumed |= widget.handle_event(event);
+        }
+        consumed
+    }
 }
 
 impl Default for Div {
@@ -374,4 +382,34 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_div_events() {
+        struct Listener(std::rc::Rc<std::cell::Cell<usize>>, bool);
+        impl Widget for Listener {
+            fn allocate_area(
+                &mut self,
+                screen_size: (usize, usize),
+                container_size: (usize, usize),
+            ) -> WidgetSize {
+                Button {}.allocate_area(screen_size, container_size)
+            }
+
+            fn render(&self, _area: Area) {}
+
+            fn handle_event(&mut self, _event: &winit::event::WindowEvent) -> bool {
+                self.0.set(self.0.get() + 1);
+                self.1
+            }
+        }
+
+        // Every widget receives events, even after one consumed them
+        let count = std::rc::Rc::new(std::cell::Cell::new(0));
+        let mut div = Div::new();
+        div.add_block(Box::new(Listener(count.clone(), true)));
+        div.add_block(Box::new(Listener(count.clone(), false)));
+        assert!(div.handle_event(&winit::event::WindowEvent::ReceivedCharacter('a')));
+        assert_eq!(count.get(), 2);
+        assert!(!Div::new().handle_event(&winit::event::WindowEvent::Focused(true)));
+    }
 }
@@ -301,6 +301,14 @@ impl Widget for Flexbox {
             widget.render(area);
         }
     }
+
+    fn handle_event(&mut self, event: &winit::event::WindowEvent) -> bool {
+        let mut consumed = false;
+        for widget in &mut self.widgets {
+            consumed |= widget.handle_event(event);
+        }
+        consumed
+    }
 }
 
 impl std::fmt::Debug for Flexbox {
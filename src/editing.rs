@@ -0,0 +1,957 @@
+//! The building blocks of text fields: a [TextEditor] holds the text, the cursor and the selection, and turns window events into edits.
+
+use crate::prelude::*;
+use std::cell::Cell;
+use std::ops::Range;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use unicode_segmentation::GraphemeCursor;
+use winit::event::{
+    ElementState, KeyboardInput, ModifiersState, MouseButton, VirtualKeyCode, WindowEvent,
+};
+
+/// Where copied text goes, shared by all editors (see [set_clipboard]).
+///
+/// unigui doesn't access the clipboard of the system: until another clipboard is set, copied text can only be pasted in the application.
+/// Implement this trait with a crate like `arboard` or `copypasta` to share text with other applications.
+pub trait Clipboard {
+    fn get(&mut self) -> Option<String>;
+    fn set(&mut self, text: String);
+}
+
+/// A clipboard that only lives in the application, used until another one is [set](set_clipboard).
+#[derive(Debug, Default)]
+pub struct MemoryClipboard {
+    text: Option<String>,
+}
+
+impl Clipboard for MemoryClipboard {
+    fn get(&mut self) -> Option<String> {
+        self.text.clone()
+    }
+
+    fn set(&mut self, text: String) {
+        self.text = Some(text);
+    }
+}
+
+static CLIPBOARD: Mutex<Option<Box<dyn Clipboard + Send>>> = Mutex::new(None);
+
+/// Replaces the clipboard used by editors, to share text with other applications with the clipboard of the system.
+pub fn set_clipboard(clipboard: impl Clipboard + Send + 'static) {
+    *CLIPBOARD.lock().unwrap() = Some(Box::new(clipboard));
+}
+
+fn with_clipboard<T>(f: impl FnOnce(&mut dyn Clipboard) -> T) -> T {
+    let mut clipboard = CLIPBOARD.lock().unwrap();
+    f(clipboard
+        .get_or_insert_with(|| Box::new(MemoryClipboard::default()))
+        .as_mut())
+}
+
+/// The selected part of a text, as byte indices.
+/// The cursor is the end that moves, and the anchor is where the selection started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Selection {
+    pub anchor: usize,
+    pub cursor: usize,
+}
+
+impl Selection {
+    pub fn collapsed(index: usize) -> Selection {
+        Selection {
+            anchor: index,
+            cursor: index,
+        }
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.anchor.min(self.cursor)..self.anchor.max(self.cursor)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.anchor == self.cursor
+    }
+}
+
+/// A move of the cursor, see [TextEditor::move_cursor].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Motion {
+    Left,
+    Right,
+    /// To the start of the previous word.
+    WordLeft,
+    /// To the end of the next word.
+    WordRight,
+    /// To the line above, keeping the horizontal position.
+    Up,
+    Down,
+    /// To the start of the line as it is displayed.
+    LineStart,
+    LineEnd,
+    TextStart,
+    TextEnd,
+}
+
+/// The kind of the last edit, to undo consecutive characters at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EditKind {
+    Insert,
+    Delete,
+    Other,
+}
+
+/// The maximum number of edits that can be undone.
+const MAX_UNDO: usize = 100;
+
+/// How long the caret stays visible, and then hidden.
+const BLINK: Duration = Duration::from_millis(530);
+
+/// Two clicks closer than this select a word.
+const DOUBLE_CLICK: Duration = Duration::from_millis(500);
+
+/// The editable text of a single-line or multi-line text field.
+///
+/// Editors handle typed characters, keys, mouse selection, undo and redo, and clipboard shortcuts in [TextEditor::handle_event], and draw themselves with [TextEditor::render].
+/// Text typed with an input method arrives as characters once it is committed, the caret position being given by [TextEditor::ime_position].
+/// The text being composed (the preedit) is only shown by the window of the input method, since winit doesn't report it.
+/// Text is drawn with the font of [TextOptions], without shaping.
+#[derive(Debug)]
+pub struct TextEditor {
+    text: String,
+    selection: Selection,
+    /// Allows line breaks, and wraps lines to the width of the editor.
+    pub multiline: bool,
+    /// The font, scale and color of the text. Alignment, ellipsis and shaping are not supported.
+    pub options: TextOptions,
+    pub selection_color: [f32; 4],
+    pub caret_color: [f32; 4],
+    /// Whether keys are handled. Clicking the editor focuses it, and clicking elsewhere unfocuses it.
+    pub focused: bool,
+    undo_stack: Vec<(String, Selection)>,
+    redo_stack: Vec<(String, Selection)>,
+    last_edit: Option<EditKind>,
+    modifiers: ModifiersState,
+    /// The position of the mouse, in the coordinates of the editor.
+    mouse_position: Option<(f32, f32)>,
+    dragging: bool,
+    last_click: Option<(Instant, usize)>,
+    /// The horizontal position kept when moving up and down.
+    preferred_x: Option<f32>,
+    /// When the caret last moved, to restart blinking.
+    moved_at: Instant,
+    /// Where the editor was last drawn.
+    rect: Cell<Option<Rect>>,
+    /// The transform the editor was last drawn with, mapping its coordinates to the ones of the window.
+    transform: Cell<Transform>,
+    scroll: Cell<(f32, f32)>,
+}
+
+impl Default for TextEditor {
+    fn default() -> Self {
+        TextEditor {
+            text: String::new(),
+            selection: Selection::default(),
+            multiline: false,
+            options: TextOptions::default(),
+            selection_color: [0.6, 0.75, 1.0, 1.0],
+            caret_color: [0.0, 0.0, 0.0, 1.0],
+            focused: false,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            last_edit: None,
+            modifiers: ModifiersState::empty(),
+            mouse_position: None,
+            dragging: false,
+            last_click: None,
+            preferred_x: None,
+            moved_at: Instant::now(),
+            rect: Cell::new(None),
+            transform: Cell::new(Transform::IDENTITY),
+            scroll: Cell::new((0.0, 0.0)),
+        }
+    }
+}
+
+/// A line as displayed: a line of the text, or a part of it when it wraps.
+#[derive(Debug, Clone, PartialEq)]
+struct VisualLine {
+    range: Range<usize>,
+    /// The positions the caret can take in the line, with their horizontal offset.
+    stops: Vec<(usize, f32)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct EditorLayout {
+    lines: Vec<VisualLine>,
+    line_height: f32,
+}
+
+impl EditorLayout {
+    fn line_of(&self, index: usize) -> usize {
+        self.lines
+            .iter()
+            .rposition(|line| line.range.start <= index)
+            .unwrap_or(0)
+    }
+
+    /// The line and horizontal offset of the caret at a byte index.
+    fn position_of(&self, index: usize) -> (usize, f32) {
+        let line = self.line_of(index);
+        let x = self.lines[line]
+            .stops
+            .iter()
+            .rev()
+            .find(|(stop, _)| *stop <= index)
+            .map_or(0.0, |(_, x)| *x);
+        (line, x)
+    }
+
+    fn nearest_stop(&self, line: usize, x: f32) -> usize {
+        self.lines[line]
+            .stops
+            .iter()
+            .min_by(|(_, a), (_, b)| (a - x).abs().partial_cmp(&(b - x).abs()).unwrap())
+            .map_or(0, |(index, _)| *index)
+    }
+
+    /// The byte index of the caret stop closest to a point, relative to the first line.
+    fn index_at(&self, (x, y): (f32, f32)) -> usize {
+        let line = (y / self.line_height).floor().max(0.0) as usize;
+        self.nearest_stop(line.min(self.lines.len() - 1), x)
+    }
+}
+
+fn is_word_character(character: char) -> bool {
+    character.is_alphanumeric() || character == '_'
+}
+
+impl TextEditor {
+    pub fn new() -> TextEditor {
+        TextEditor::default()
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Replaces the text, moving the cursor to its end and forgetting the edits that could be undone.
+    pub fn set_text(&mut self, text: impl Into<String>) {
+        self.text = text.into();
+        if !self.multiline {
+            self.text = self.text.replace(['\r', '\n'], " ");
+        }
+        self.selection = Selection::collapsed(self.text.len());
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.last_edit = None;
+    }
+
+    pub fn selection(&self) -> Selection {
+        self.selection
+    }
+
+    /// Selects a part of the text, indices being moved back to character boundaries.
+    pub fn set_selection(&mut self, selection: Selection) {
+        let clamp = |mut index: usize| {
+            index = index.min(self.text.len());
+            while !self.text.is_char_boundary(index) {
+                index -= 1;
+            }
+            index
+        };
+        self.selection = Selection {
+            anchor: clamp(selection.anchor),
+            cursor: clamp(selection.cursor),
+        };
+        self.caret_moved();
+    }
+
+    pub fn selected_text(&self) -> &str {
+        &self.text[self.selection.range()]
+    }
+
+    pub fn select_all(&mut self) {
+        self.set_selection(Selection {
+            anchor: 0,
+            cursor: self.text.len(),
+        });
+    }
+
+    fn caret_moved(&mut self) {
+        self.moved_at = Instant::now();
+        self.last_edit = None;
+    }
+
+    /// Saves the text before an edit, so that it can be undone.
+    fn record(&mut self, kind: EditKind, inserted: &str) {
+        // Consecutive characters are undone at once, until a word ends
+        let starts_word = inserted.starts_with(char::is_whitespace);
+        if kind == EditKind::Other || self.last_edit != Some(kind) || starts_word {
+            self.undo_stack.push((self.text.clone(), self.selection));
+            if self.undo_stack.len() > MAX_UNDO {
+                self.undo_stack.remove(0);
+            }
+        }
+        self.redo_stack.clear();
+        self.last_edit = Some(kind);
+        self.moved_at = Instant::now();
+        self.preferred_x = None;
+    }
+
+    fn replace_selection(&mut self, text: &str) {
+        let range = self.selection.range();
+        self.text.replace_range(range.clone(), text);
+        self.selection = Selection::collapsed(range.start + text.len());
+    }
+
+    /// Replaces the selection with a text, like if it was typed.
+    /// Line breaks are replaced by spaces in single-line editors.
+    pub fn insert(&mut self, text: &str) {
+        let text = match self.multiline {
+            true => text.replace("\r\n", "\n").replace('\r', "\n"),
+            false => text.replace("\r\n", " ").replace(['\r', '\n'], " "),
+        };
+        if text.is_empty() && self.selection.is_empty() {
+            return;
+        }
+        let kind = match text.chars().count() {
+            1 => EditKind::Insert,
+            _ => EditKind::Other,
+        };
+        self.record(kind, &text);
+        self.replace_selection(&text);
+    }
+
+    /// Deletes the selection, or the character before the cursor, with the marks combined with it.
+    pub fn backspace(&mut self) {
+        if self.selection.is_empty() {
+            self.select_to(self.previous_boundary(self.selection.cursor));
+        }
+        self.delete_selection();
+    }
+
+    /// Deletes the selection, or the character after the cursor, with the marks combined with it.
+    pub fn delete(&mut self) {
+        if self.selection.is_empty() {
+            self.select_to(self.next_boundary(self.selection.cursor));
+        }
+        self.delete_selection();
+    }
+
+    fn select_to(&mut self, index: usize) {
+        self.selection.cursor = index;
+    }
+
+    fn delete_selection(&mut self) {
+        if self.selection.is_empty() {
+            return;
+        }
+        self.record(EditKind::Delete, "");
+        self.replace_selection("");
+    }
+
+    /// The start of the grapheme cluster before an index, so that accents and emoji sequences are edited as a single character.
+    fn previous_boundary(&self, index: usize) -> usize {
+        GraphemeCursor::new(index, self.text.len(), true)
+            .prev_boundary(&self.text, 0)
+            .ok()
+            .flatten()
+            .unwrap_or(0)
+    }
+
+    /// The end of the grapheme cluster after an index.
+    fn next_boundary(&self, index: usize) -> usize {
+        GraphemeCursor::new(index, self.text.len(), true)
+            .next_boundary(&self.text, 0)
+            .ok()
+            .flatten()
+            .unwrap_or(index)
+    }
+
+    fn previous_word(&self, index: usize) -> usize {
+        let mut characters = self.text[..index].char_indices().rev().peekable();
+        while let Some((_, character)) = characters.peek() {
+            if is_word_character(*character) {
+                break;
+            }
+            characters.next();
+        }
+        let mut start = characters.peek().map_or(0, |(i, _)| *i);
+        for (i, character) in characters {
+            if !is_word_character(character) {
+                break;
+            }
+            start = i;
+        }
+        start
+    }
+
+    fn next_word(&self, index: usize) -> usize {
+        let mut characters = self.text[index..].char_indices().peekable();
+        while let Some((_, character)) = characters.peek() {
+            if is_word_character(*character) {
+                break;
+            }
+            characters.next();
+        }
+        for (i, character) in characters {
+            if !is_word_character(character) {
+                return index + i;
+            }
+        }
+        self.text.len()
+    }
+
+    /// Moves the cursor, extending the selection if `select` is `true`.
+    /// Without `select`, moving left or right from a selection goes to its start or end.
+    pub fn move_cursor(&mut self, motion: Motion, select: bool) {
+        let cursor = self.selection.cursor;
+        let range = self.selection.range();
+        let keeps_x = matches!(motion, Motion::Up | Motion::Down);
+        let index = match motion {
+            Motion::Left if !select && !range.is_empty() => range.start,
+            Motion::Right if !select && !range.is_empty() => range.end,
+            Motion::Left => self.previous_boundary(cursor),
+            Motion::Right => self.next_boundary(cursor),
+            Motion::WordLeft => self.previous_word(cursor),
+            Motion::WordRight => self.next_word(cursor),
+            Motion::TextStart => 0,
+            Motion::TextEnd => self.text.len(),
+            Motion::Up | Motion::Down | Motion::LineStart | Motion::LineEnd => {
+                let layout = self.layout();
+                let (line, x) = layout.position_of(cursor);
+                let x = self.preferred_x.unwrap_or(x);
+                self.preferred_x = Some(x);
+                match motion {
+                    Motion::Up if line == 0 => 0,
+                    Motion::Up => layout.nearest_stop(line - 1, x),
+                    Motion::Down if line + 1 == layout.lines.len() => self.text.len(),
+                    Motion::Down => layout.nearest_stop(line + 1, x),
+                    Motion::LineStart => layout.lines[line].range.start,
+                    _ => layout.lines[line].stops.last().map_or(cursor, |(i, _)| *i),
+                }
+            }
+        };
+        let preferred_x = self.preferred_x;
+        match select {
+            true => self.selection.cursor = index,
+            false => self.selection = Selection::collapsed(index),
+        }
+        self.caret_moved();
+        if keeps_x {
+            self.preferred_x = preferred_x;
+        } else {
+            self.preferred_x = None;
+        }
+    }
+
+    /// Goes back to the text before the last edit. Returns `false` if there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        match self.undo_stack.pop() {
+            Some((text, selection)) => {
+                let current = std::mem::replace(&mut self.text, text);
+                self.redo_stack.push((current, self.selection));
+                self.selection = selection;
+                self.caret_moved();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Applies the last undone edit again. Returns `false` if there is nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        match self.redo_stack.pop() {
+            Some((text, selection)) => {
+                let current = std::mem::replace(&mut self.text, text);
+                self.undo_stack.push((current, self.selection));
+                self.selection = selection;
+                self.caret_moved();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Copies the selection to the clipboard.
+    pub fn copy(&self) {
+        if !self.selection.is_empty() {
+            let text = self.selected_text().to_string();
+            with_clipboard(|clipboard| clipboard.set(text));
+        }
+    }
+
+    pub fn cut(&mut self) {
+        self.copy();
+        self.delete_selection();
+    }
+
+    pub fn paste(&mut self) {
+        if let Some(text) = with_clipboard(|clipboard| clipboard.get()) {
+            self.insert(&text);
+        }
+    }
+
+    /// Lays out the text in the width of the editor, or in a single line.
+    fn layout(&self) -> EditorLayout {
+        let fonts = Fonts::global();
+        let (font, scale) = (self.options.font, self.options.scale);
+        let width = match (self.multiline && self.options.wrap, self.rect.get()) {
+            (true, Some(rect)) => Some(rect.width()),
+            _ => None,
+        };
+        let line_height = fonts
+            .measure("x", font, scale, None)
+            .lines
+            .first()
+            .map_or(scale, |line| line.ascent - line.descent + line.line_gap);
+
+        let mut lines = Vec::new();
+        let mut start = 0;
+        for paragraph in self.text.split('\n') {
+            let end = start + paragraph.len();
+            let metrics = fonts.measure(paragraph, font, scale, width);
+            if metrics.lines.is_empty() {
+                lines.push(VisualLine {
+                    range: start..end,
+                    stops: vec![(start, 0.0)],
+                });
+            }
+            for (i, line) in metrics.lines.iter().enumerate() {
+                let glyphs = &metrics.glyphs[line.glyphs.clone()];
+                let mut stops: Vec<(usize, f32)> = glyphs
+                    .iter()
+                    .map(|glyph| (start + glyph.byte_index, glyph.bounds.min.0))
+                    .collect();
+                // Wrapped lines end where the next one starts
+                let line_end = match metrics.lines.get(i + 1) {
+                    Some(next) => start + metrics.glyphs[next.glyphs.start].byte_index,
+                    None => {
+                        stops.push((end, line.bounds.max.0));
+                        end
+                    }
+                };
+                lines.push(VisualLine {
+                    range: stops[0].0..line_end,
+                    stops,
+                });
+            }
+            start = end + 1;
+        }
+        EditorLayout { lines, line_height }
+    }
+
+    /// The byte index of the text at a point in the coordinates of the editor, once drawn.
+    fn index_at(&self, (x, y): (f32, f32)) -> usize {
+        let rect = self.rect.get().unwrap_or(Rect::sized(0.0, 0.0, 0.0, 0.0));
+        let scroll = self.scroll.get();
+        self.layout()
+            .index_at((x - rect.min.0 + scroll.0, y - rect.min.1 + scroll.1))
+    }
+
+    /// The bottom left corner of the caret in the window, where input method windows should be placed (see [winit::window::Window::set_ime_position]).
+    /// Returns [None] if the editor has not been drawn yet.
+    pub fn ime_position(&self) -> Option<(f32, f32)> {
+        let rect = self.rect.get()?;
+        let layout = self.layout();
+        let (line, x) = layout.position_of(self.selection.cursor);
+        let scroll = self.scroll.get();
+        Some(self.transform.get().apply((
+            rect.min.0 + x - scroll.0,
+            rect.min.1 + (line + 1) as f32 * layout.line_height - scroll.1,
+        )))
+    }
+
+    /// Handles typed characters, keys and the mouse.
+    /// Returns `true` if the event changed the editor.
+    pub fn handle_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = *modifiers;
+                false
+            }
+            WindowEvent::ReceivedCharacter(character) if self.focused => {
+                // Control characters and shortcuts are handled as key presses, but AltGr is reported as Ctrl+Alt on Windows
+                let shortcut =
+                    (self.modifiers.ctrl() && !self.modifiers.alt()) || self.modifiers.logo();
+                if character.is_control() || shortcut {
+                    return false;
+                }
+                self.insert(character.encode_utf8(&mut [0; 4]));
+                true
+            }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                ..
+            } if self.focused => self.handle_key(*key),
+            WindowEvent::CursorMoved { position, .. } => {
+                // The mouse is over the editor as it is drawn, with its transform
+                let position = (position.x as f32, position.y as f32);
+                self.mouse_position = self
+                    .transform
+                    .get()
+                    .inverse()
+                    .map(|inverse| inverse.apply(position));
+                match (self.dragging, self.mouse_position) {
+                    (true, Some(position)) => {
+                        self.selection.cursor = self.index_at(position);
+                        self.caret_moved();
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => match state {
+                ElementState::Pressed => self.click(),
+                ElementState::Released => std::mem::replace(&mut self.dragging, false),
+            },
+            _ => false,
+        }
+    }
+
+    fn click(&mut self) -> bool {
+        let (position, rect) = match (self.mouse_position, self.rect.get()) {
+            (Some(position), Some(rect)) => (position, rect),
+            _ => return false,
+        };
+        let inside = position.0 >= rect.min.0
+            && position.0 <= rect.max.0
+            && position.1 >= rect.min.1
+            && position.1 <= rect.max.1;
+        self.focused = inside;
+        if !inside {
+            return false;
+        }
+
+        let index = self.index_at(position);
+        let double_click = matches!(
+            self.last_click,
+            Some((time, last)) if last == index && time.elapsed() < DOUBLE_CLICK
+        );
+        if double_click {
+            // Double clicks select a word
+            let end = self.next_word(index);
+            let start = self.previous_word(end);
+            self.selection = Selection {
+                anchor: start,
+                cursor: end,
+            };
+            self.last_click = None;
+        } else {
+            match self.modifiers.shift() {
+                true => self.selection.cursor = index,
+                false => self.selection = Selection::collapsed(index),
+            }
+            self.last_click = Some((Instant::now(), index));
+            self.dragging = true;
+        }
+        self.caret_moved();
+        self.preferred_x = None;
+        true
+    }
+
+    fn handle_key(&mut self, key: VirtualKeyCode) -> bool {
+        let select = self.modifiers.shift();
+        // Words are skipped with Ctrl, or Alt on macOS
+        let word = self.modifiers.ctrl() || self.modifiers.alt();
+        let command = self.modifiers.ctrl() || self.modifiers.logo();
+        match key {
+            VirtualKeyCode::Left if word => self.move_cursor(Motion::WordLeft, select),
+            VirtualKeyCode::Left => self.move_cursor(Motion::Left, select),
+            VirtualKeyCode::Right if word => self.move_cursor(Motion::WordRight, select),
+            VirtualKeyCode::Right => self.move_cursor(Motion::Right, select),
+            VirtualKeyCode::Up => self.move_cursor(Motion::Up, select),
+            VirtualKeyCode::Down => self.move_cursor(Motion::Down, select),
+            VirtualKeyCode::Home if command => self.move_cursor(Motion::TextStart, select),
+            VirtualKeyCode::Home => self.move_cursor(Motion::LineStart, select),
+            VirtualKeyCode::End if command => self.move_cursor(Motion::TextEnd, select),
+            VirtualKeyCode::End => self.move_cursor(Motion::LineEnd, select),
+            VirtualKeyCode::Back if word && self.selection.is_empty() => {
+                self.selection.cursor = self.previous_word(self.selection.cursor);
+                self.delete_selection();
+            }
+            VirtualKeyCode::Back => self.backspace(),
+            VirtualKeyCode::Delete if word && self.selection.is_empty() => {
+                self.selection.cursor = self.next_word(self.selection.cursor);
+                self.delete_selection();
+            }
+            VirtualKeyCode::Delete => self.delete(),
+            VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter if self.multiline => {
+                self.insert("\n")
+            }
+            VirtualKeyCode::A if command => self.select_all(),
+            VirtualKeyCode::C if command => self.copy(),
+            VirtualKeyCode::X if command => self.cut(),
+            VirtualKeyCode::V if command => self.paste(),
+            VirtualKeyCode::Z if command && select => {
+                self.redo();
+            }
+            VirtualKeyCode::Z if command => {
+                self.undo();
+            }
+            VirtualKeyCode::Y if command => {
+                self.redo();
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Draws the selection, the text, and the caret if the editor is focused.
+    /// The view scrolls to keep the caret visible.
+    pub fn render(&self, area: &mut Area) {
+        let rect = area.rect;
+        self.rect.set(Some(rect));
+        self.transform.set(area.backend.transform());
+        let layout = self.layout();
+        let line_height = layout.line_height;
+
+        let (caret_line, caret_x) = layout.position_of(self.selection.cursor);
+        let caret_y = caret_line as f32 * line_height;
+        let (mut scroll_x, mut scroll_y) = self.scroll.get();
+        scroll_x = scroll_x
+            .min(caret_x)
+            .max(caret_x - rect.width() + 1.0)
+            .max(0.0);
+        scroll_y = scroll_y
+            .min(caret_y)
+            .max(caret_y + line_height - rect.height())
+            .max(0.0);
+        self.scroll.set((scroll_x, scroll_y));
+        let (left, top) = (rect.min.0 - scroll_x, rect.min.1 - scroll_y);
+
+        area.backend.push_clip(rect);
+        let selection = self.selection.range();
+        if !selection.is_empty() {
+            // Selected line breaks are shown as a space
+            let space = Fonts::global()
+                .measure(" ", self.options.font, self.options.scale, None)
+                .width();
+            for (i, line) in layout.lines.iter().enumerate() {
+                let start = selection.start.max(line.range.start);
+                let end = selection.end.min(line.range.end);
+                let selects_break =
+                    selection.end > line.range.end && self.text[line.range.end..].starts_with('\n');
+                if start > end || (start == end && !selects_break) {
+                    continue;
+                }
+                let (_, x1) = layout.position_of(start);
+                let (_, x2) = layout.position_of(end);
+                let x2 = if selects_break { x2 + space } else { x2 };
+                let y = top + i as f32 * line_height;
+                area.backend.add_rect(
+                    Rect {
+                        min: (left + x1, y),
+                        max: (left + x2, y + line_height),
+                    },
+                    &Paint::Solid(self.selection_color),
+                );
+            }
+        }
+
+        for (i, line) in layout.lines.iter().enumerate() {
+            area.backend.add_text(
+                glyph_brush::Section::default()
+                    .with_screen_position((left, top + i as f32 * line_height))
+                    .add_text(
+                        glyph_brush::Text::new(&self.text[line.range.clone()])
+                            .with_font_id(self.options.font)
+                            .with_scale(self.options.scale)
+                            .with_color(self.options.color),
+                    ),
+            );
+        }
+
+        let blink_on = (self.moved_at.elapsed().as_millis() / BLINK.as_millis()) & 1 == 0;
+        if self.focused && blink_on {
+            let width = (self.options.scale / 16.0).max(1.0);
+            area.backend.add_rect(
+                Rect::sized(left + caret_x, top + caret_y, width, line_height),
+                &Paint::Solid(self.caret_color),
+            );
+        }
+        area.backend.pop_clip();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editor(text: &str) -> TextEditor {
+        Fonts::global()
+            .set_default_font(include_bytes!(
+                "graphics/ressources/Inconsolata-Regular.ttf"
+            ))
+            .unwrap();
+        let mut editor = TextEditor::new();
+        editor.multiline = true;
+        editor.focused = true;
+        editor.set_text(text);
+        editor
+    }
+
+    fn type_text(editor: &mut TextEditor, text: &str) {
+        for character in text.chars() {
+            editor.handle_event(&WindowEvent::ReceivedCharacter(character));
+        }
+    }
+
+    #[test]
+    fn test_editing() {
+        let mut editor = editor("héllo");
+        editor.move_cursor(Motion::Left, false);
+        editor.backspace();
+        assert_eq!(editor.text(), "hélo");
+        editor.move_cursor(Motion::TextStart, false);
+        editor.move_cursor(Motion::Right, false);
+        editor.delete();
+        assert_eq!(editor.text(), "hlo");
+
+        editor.set_selection(Selection {
+            anchor: 1,
+            cursor: 100,
+        });
+        assert_eq!(editor.selected_text(), "lo");
+        // Control characters are handled as key presses
+        type_text(&mut editor, "ey\u{8}!");
+        assert_eq!(editor.text(), "hey!");
+
+        editor.multiline = false;
+        editor.insert("\na\r\nb");
+        assert_eq!(editor.text(), "hey! a b");
+    }
+
+    #[test]
+    fn test_grapheme_clusters() {
+        // A letter with a combining accent, and a family emoji joining three emoji
+        let family = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}";
+        let mut editor = editor(&format!("e\u{301}{}!", family));
+        editor.move_cursor(Motion::TextStart, false);
+        editor.move_cursor(Motion::Right, false);
+        assert_eq!(editor.selection().cursor, 3);
+        editor.move_cursor(Motion::Right, false);
+        assert_eq!(editor.selection().cursor, 3 + family.len());
+        editor.move_cursor(Motion::Left, false);
+        assert_eq!(editor.selection().cursor, 3);
+
+        editor.delete();
+        assert_eq!(editor.text(), "e\u{301}!");
+        editor.backspace();
+        assert_eq!(editor.text(), "!");
+    }
+
+    #[test]
+    fn test_word_navigation() {
+        let mut editor = editor("one, two_2  three");
+        editor.move_cursor(Motion::WordLeft, false);
+        assert_eq!(editor.selection().cursor, 12);
+        editor.move_cursor(Motion::WordLeft, true);
+        assert_eq!(editor.selected_text(), "two_2  ");
+        editor.move_cursor(Motion::TextStart, false);
+        editor.move_cursor(Motion::WordRight, false);
+        assert_eq!(editor.selection().cursor, 3);
+        editor.move_cursor(Motion::WordRight, false);
+        assert_eq!(editor.selection().cursor, 10);
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut editor = editor("");
+        type_text(&mut editor, "one two");
+        editor.backspace();
+        editor.backspace();
+        assert_eq!(editor.text(), "one t");
+
+        // Deletions and words are undone at once
+        assert!(editor.undo());
+        assert_eq!(editor.text(), "one two");
+        assert!(editor.undo());
+        assert_eq!(editor.text(), "one");
+        assert!(editor.undo());
+        assert_eq!(editor.text(), "");
+        assert!(!editor.undo());
+        assert!(editor.redo());
+        assert_eq!(editor.text(), "one");
+        editor.insert("!");
+        assert!(!editor.redo());
+    }
+
+    #[test]
+    fn test_clipboard() {
+        let mut editor = editor("copy paste");
+        editor.set_selection(Selection {
+            anchor: 0,
+            cursor: 5,
+        });
+        editor.cut();
+        assert_eq!(editor.text(), "paste");
+        editor.move_cursor(Motion::TextEnd, false);
+        editor.paste();
+        assert_eq!(editor.text(), "pastecopy ");
+    }
+
+    #[test]
+    fn test_lines() {
+        let mut editor = editor("ab\n\ncd");
+        let layout = editor.layout();
+        assert_eq!(layout.lines.len(), 3);
+        assert_eq!(layout.lines[1].range, 3..3);
+        assert_eq!(layout.position_of(3).0, 1);
+        assert_eq!(layout.index_at((100.0, layout.line_height * 2.5)), 6);
+
+        editor.move_cursor(Motion::Up, false);
+        assert_eq!(editor.selection().cursor, 3);
+        editor.move_cursor(Motion::Up, false);
+        // The horizontal position is kept through the empty line
+        assert_eq!(editor.selection().cursor, 2);
+        editor.move_cursor(Motion::LineStart, false);
+        assert_eq!(editor.selection().cursor, 0);
+        editor.move_cursor(Motion::Down, true);
+        editor.move_cursor(Motion::LineEnd, true);
+        assert_eq!(editor.selected_text(), "ab\n");
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_transformed_mouse() {
+        let mut editor = editor("ab\ncd");
+        editor.focused = false;
+        editor.rect.set(Some(Rect::sized(0.0, 0.0, 200.0, 100.0)));
+        editor.transform.set(Transform::translation(300.0, 200.0));
+        let line_height = editor.layout().line_height;
+        let device_id = unsafe { winit::event::DeviceId::dummy() };
+        let moved = |x: f32, y: f32| WindowEvent::CursorMoved {
+            device_id,
+            position: winit::dpi::PhysicalPosition::new(x as f64, y as f64),
+            modifiers: ModifiersState::empty(),
+        };
+        let press = WindowEvent::MouseInput {
+            device_id,
+            state: ElementState::Pressed,
+            button: MouseButton::Left,
+            modifiers: ModifiersState::empty(),
+        };
+
+        // Points are mapped to the editor as it is drawn
+        editor.handle_event(&moved(100.0, line_height * 1.5));
+        editor.handle_event(&press);
+        assert!(!editor.focused);
+        editor.handle_event(&moved(400.0, 200.0 + line_height * 1.5));
+        editor.handle_event(&press);
+        assert!(editor.focused);
+        assert_eq!(editor.selection().cursor, 5);
+        let (x, y) = editor.ime_position().unwrap();
+        assert!(x > 300.0 && (y - (200.0 + 2.0 * line_height)).abs() < 0.01);
+    }
+}
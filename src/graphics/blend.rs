@@ -0,0 +1,74 @@
+/// Defines how what is drawn is combined with what has already been drawn.
+///
+/// Shaders output colors premultiplied by their alpha, so that every mode can be expressed with blend factors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    /// What is drawn is put over what has been drawn before, according to its opacity. (default)
+    #[default]
+    Normal,
+    /// Same as [BlendMode::Normal], but the colors drawn are considered to already be premultiplied by their alpha.
+    /// This applies to vertex colors, paints and images.
+    PremultipliedOver,
+    /// Colors are multiplied, which darkens the result.
+    Multiply,
+    /// Inverted colors are multiplied, which lightens the result.
+    Screen,
+    /// Colors are added, which is useful for glows and lights.
+    Additive,
+}
+
+impl BlendMode {
+    pub(super) const ALL: [BlendMode; 5] = [
+        BlendMode::Normal,
+        BlendMode::PremultipliedOver,
+        BlendMode::Multiply,
+        BlendMode::Screen,
+        BlendMode::Additive,
+    ];
+
+    /// The index of the pipelines using this mode.
+    pub(super) fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Whether shaders must not premultiply the colors they output.
+    pub(super) fn premultiplied_input(self) -> bool {
+        self == BlendMode::PremultipliedOver
+    }
+
+    pub(super) fn blend_state(self) -> wgpu::BlendState {
+        let color = |src_factor, dst_factor| wgpu::BlendComponent {
+            src_factor,
+            dst_factor,
+            operation: wgpu::BlendOperation::Add,
+        };
+        let color = match self {
+            BlendMode::Normal | BlendMode::PremultipliedOver => {
+                color(wgpu::BlendFactor::One, wgpu::BlendFactor::OneMinusSrcAlpha)
+            }
+            BlendMode::Multiply => {
+                color(wgpu::BlendFactor::Dst, wgpu::BlendFactor::OneMinusSrcAlpha)
+            }
+            BlendMode::Screen => color(wgpu::BlendFactor::One, wgpu::BlendFactor::OneMinusSrc),
+            BlendMode::Additive => color(wgpu::BlendFactor::One, wgpu::BlendFactor::One),
+        };
+        wgpu::BlendState {
+            color,
+            alpha: wgpu::BlendComponent::OVER,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_indices() {
+        for (i, mode) in BlendMode::ALL.iter().enumerate() {
+            assert_eq!(mode.index(), i);
+        }
+        assert!(BlendMode::PremultipliedOver.premultiplied_input());
+        assert!(!BlendMode::default().premultiplied_input());
+    }
+}
@@ -0,0 +1,136 @@
+/// A color with straight (not premultiplied) alpha.
+///
+/// Colors given to the backend as `[f32; 4]` are sRGB components, like the ones of design tools and CSS: `[0.5, 0.5, 0.5, 1.0]` is a mid gray.
+/// The shaders convert them to linear colors, so that blending and anti-aliasing happen in linear space, whatever the format of the frame.
+/// A [Color] makes the color space explicit, and converts to sRGB components with [Color::to_srgb] or [Into].
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Color {
+    /// The components, in linear space.
+    linear: [f32; 4],
+}
+
+impl Color {
+    pub const TRANSPARENT: Color = Color::linear(0.0, 0.0, 0.0, 0.0);
+    pub const BLACK: Color = Color::linear(0.0, 0.0, 0.0, 1.0);
+    pub const WHITE: Color = Color::linear(1.0, 1.0, 1.0, 1.0);
+
+    /// A color from components in linear space, like the ones of physically based lighting.
+    pub const fn linear(r: f32, g: f32, b: f32, a: f32) -> Color {
+        Color {
+            linear: [r, g, b, a],
+        }
+    }
+
+    /// A color from sRGB components between `0.0` and `1.0`.
+    /// Alpha is not affected by the color space.
+    pub fn srgb(r: f32, g: f32, b: f32, a: f32) -> Color {
+        Color::linear(srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a)
+    }
+
+    /// A color from sRGB components between `0` and `255`, like `rgba()` in CSS but with an alpha in the same range.
+    pub fn from_rgba8(r: u8, g: u8, b: u8, a: u8) -> Color {
+        let [r, g, b, a] = [r, g, b, a].map(|value| value as f32 / 255.0);
+        Color::srgb(r, g, b, a)
+    }
+
+    /// An opaque color from the `0xRRGGBB` notation of design tools.
+    pub fn from_hex(hex: u32) -> Color {
+        let [_, r, g, b] = hex.to_be_bytes();
+        Color::from_rgba8(r, g, b, 255)
+    }
+
+    pub fn with_alpha(self, alpha: f32) -> Color {
+        let [r, g, b, _] = self.linear;
+        Color::linear(r, g, b, alpha)
+    }
+
+    /// The sRGB components, as taken by the backend.
+    pub fn to_srgb(self) -> [f32; 4] {
+        let [r, g, b, a] = self.linear;
+        [linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b), a]
+    }
+
+    /// The components in linear space.
+    pub fn to_linear(self) -> [f32; 4] {
+        self.linear
+    }
+
+    /// The sRGB components between `0` and `255`.
+    pub fn to_rgba8(self) -> [u8; 4] {
+        self.to_srgb()
+            .map(|value| (value * 255.0).round().clamp(0.0, 255.0) as u8)
+    }
+}
+
+/// Interprets the components as sRGB ones, like the rest of the API.
+impl From<[f32; 4]> for Color {
+    fn from([r, g, b, a]: [f32; 4]) -> Self {
+        Color::srgb(r, g, b, a)
+    }
+}
+
+impl From<Color> for [f32; 4] {
+    fn from(color: Color) -> Self {
+        color.to_srgb()
+    }
+}
+
+/// Decodes an sRGB component.
+pub(super) fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encodes a linear component to sRGB.
+pub(super) fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: [f32; 4], b: [f32; 4]) {
+        for (a, b) in a.iter().zip(&b) {
+            assert!((a - b).abs() < 0.0001, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn test_color_spaces() {
+        // Mid gray in sRGB is about a fifth of the light of white
+        let gray = Color::srgb(0.5, 0.5, 0.5, 0.5);
+        assert_close(gray.to_linear(), [0.21404, 0.21404, 0.21404, 0.5]);
+        assert_close(gray.to_srgb(), [0.5, 0.5, 0.5, 0.5]);
+        assert_eq!(Color::from([0.5, 0.5, 0.5, 0.5]), gray);
+        assert_close(
+            Color::linear(0.21404, 0.0, 1.0, 1.0).into(),
+            [0.5, 0.0, 1.0, 1.0],
+        );
+
+        // Dark values are linear
+        assert_close(
+            Color::srgb(0.02, 0.0, 0.0, 1.0).to_linear(),
+            [0.02 / 12.92, 0.0, 0.0, 1.0],
+        );
+
+        // Bytes round-trip
+        for value in 0..=255 {
+            let color = Color::from_rgba8(value, 0, 255, value);
+            assert_eq!(color.to_rgba8(), [value, 0, 255, value]);
+        }
+        assert_eq!(
+            Color::from_hex(0x3366ff).to_rgba8(),
+            [0x33, 0x66, 0xff, 255]
+        );
+        assert_eq!(Color::from_hex(0xffffff), Color::WHITE);
+        assert_eq!(Color::BLACK.with_alpha(0.0), Color::TRANSPARENT);
+    }
+}
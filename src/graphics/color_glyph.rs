@@ -0,0 +1,1387 @@
+//! Color glyphs, like emoji: the layers of the `COLR` table, and the bitmaps of the `CBDT` and `sbix` tables.
+//!
+//! They are drawn from their own RGBA atlas, since the glyph cache of glyph_brush only stores coverage.
+//! Glyphs are drawn at a few sizes per octave and scaled to the size of the text, and the atlas is emptied when it is full.
+//! Gradients and composite modes of `COLR` version 1 glyphs are drawn per pixel, and transformed clip boxes clip to the rectangle containing them.
+
+use super::shaping::FontData;
+use super::texture::{linear_to_srgb, srgb_to_linear, GlyphAtlas};
+use crate::rect::Rect;
+use glyph_brush::ab_glyph::{
+    self, point, Font, FontArc, OutlineCurve, OutlinedGlyph, PxScale, ScaleFont,
+};
+use glyph_brush::{FontId, GlyphPositioner, SectionGeometry, SectionGlyph, ToSectionText};
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use ttf_parser::colr::{self, ClipBox, CompositeMode, GradientExtend, Paint};
+use ttf_parser::{Face, GlyphId, RasterGlyphImage, RasterImageFormat, RgbaColor, Transform};
+
+/// Tells whether a font has glyphs that are not drawn from their outline.
+fn has_color_glyphs(face: &Face) -> bool {
+    let tables = face.tables();
+    tables.colr.is_some()
+        || tables.cbdt.is_some()
+        || tables.sbix.is_some()
+        || tables.ebdt.is_some()
+        || tables.bdat.is_some()
+}
+
+/// Tells whether a glyph is drawn from the [ColorAtlas].
+/// Glyphs with layers are, and so are bitmaps of color fonts, or of glyphs without outline, if they can be decoded.
+fn is_color_glyph(face: &Face, id: GlyphId) -> bool {
+    let tables = face.tables();
+    face.is_color_glyph(id)
+        || ((tables.cbdt.is_some()
+            || tables.sbix.is_some()
+            || face.glyph_bounding_box(id).is_none())
+            && face
+                .glyph_raster_image(id, u16::MAX)
+                .is_some_and(|image| can_decode(&image)))
+}
+
+/// Tells whether a bitmap can be decoded, PNG bitmaps needing the `png` feature.
+fn can_decode(image: &RasterGlyphImage) -> bool {
+    !matches!(image.format, RasterImageFormat::PNG) || cfg!(feature = "png")
+}
+
+/// Tells whether the glyphs of a font can be drawn.
+/// They can't for fonts whose only glyphs are PNG bitmaps, without the `png` feature.
+pub(super) fn can_draw_glyphs(data: &[u8], index: u32) -> bool {
+    let face = match Face::parse(data, index) {
+        Ok(face) => face,
+        Err(_) => return false,
+    };
+    let tables = face.tables();
+    if tables.glyf.is_some()
+        || tables.cff.is_some()
+        || tables.cff2.is_some()
+        || tables.colr.is_some()
+    {
+        return true;
+    }
+    // Bitmap fonts store all their glyphs in the same format
+    match (0..face.number_of_glyphs()).find_map(|id| face.glyph_raster_image(GlyphId(id), u16::MAX))
+    {
+        Some(image) => can_decode(&image),
+        None => true,
+    }
+}
+
+/// A layout leaving out the color glyphs, which glyph_brush would draw as a silhouette if they have an outline.
+pub(super) struct ColorFilter<L> {
+    pub(super) layout: L,
+    pub(super) font_data: Vec<FontData>,
+}
+
+impl<L: Hash> Hash for ColorFilter<L> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        "color".hash(state);
+        self.layout.hash(state);
+    }
+}
+
+impl<L: GlyphPositioner> GlyphPositioner for ColorFilter<L> {
+    fn calculate_glyphs<F, S>(
+        &self,
+        fonts: &[F],
+        geometry: &SectionGeometry,
+        sections: &[S],
+    ) -> Vec<SectionGlyph>
+    where
+        F: Font,
+        S: ToSectionText,
+    {
+        let mut glyphs = self.layout.calculate_glyphs(fonts, geometry, sections);
+        let mut faces: HashMap<FontId, Option<Face>> = HashMap::new();
+        glyphs.retain(|glyph| {
+            let face = faces.entry(glyph.font_id).or_insert_with(|| {
+                let (data, index) = self.font_data.get(glyph.font_id.0).copied().flatten()?;
+                Face::parse(data, index).ok().filter(has_color_glyphs)
+            });
+            match face {
+                Some(face) => !is_color_glyph(face, GlyphId(glyph.glyph.id.0)),
+                None => true,
+            }
+        });
+        glyphs
+    }
+
+    fn bounds_rect(&self, geometry: &SectionGeometry) -> ab_glyph::Rect {
+        self.layout.bounds_rect(geometry)
+    }
+}
+
+/// The largest size color glyphs are drawn at, in pixels. Larger glyphs are scaled up.
+const MAX_STRIKE_SIZE: f32 = 512.0;
+
+/// The size in pixels a color glyph is drawn at for a size of text.
+/// There are four sizes per octave, so that zooming does not draw glyphs at every size.
+fn strike_size(size: f32) -> u32 {
+    let quarter_octaves = (size.max(1.0).log2() * 4.0).ceil();
+    2f32.powf(quarter_octaves / 4.0)
+        .round()
+        .min(MAX_STRIKE_SIZE) as u32
+}
+
+/// A glyph stored in a [ColorAtlas].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct ColorGlyph {
+    /// The rectangle of the glyph in the atlas, in pixels.
+    pub(super) tex_coords: Rect,
+    /// The size in pixels the glyph was drawn at, see [strike_size].
+    pub(super) size: f32,
+    /// The rectangle covered by the glyph relative to its origin on the baseline, in pixels at the size it was drawn at.
+    pub(super) bounds: Rect,
+    /// Whether the glyph is a grayscale bitmap, tinted by the color of the text like other glyphs.
+    pub(super) tinted: bool,
+}
+
+/// The glyphs drawn with their own colors, see the [module](self) documentation.
+/// Their pixels are stored premultiplied, in linear colors encoded as sRGB.
+pub(super) struct ColorAtlas {
+    pub(super) atlas: GlyphAtlas,
+    /// The data of each font, if it has color glyphs.
+    fonts: Vec<FontData>,
+    /// The glyphs drawn so far by font, glyph, pixel size and text color for glyphs using it, [None] for glyphs drawn by glyph_brush or too large for the atlas.
+    glyphs: HashMap<(FontId, GlyphId, u32, Option<[u8; 3]>), Option<ColorGlyph>>,
+    /// The glyphs with layers using the color of the text, which are drawn for each color.
+    foreground_glyphs: HashSet<(FontId, GlyphId)>,
+    /// Whether a glyph did not fit in the atlas because of the others, see [ColorAtlas::evict_if_full].
+    full: bool,
+}
+
+impl ColorAtlas {
+    pub(super) fn new() -> ColorAtlas {
+        ColorAtlas {
+            atlas: GlyphAtlas::new(4),
+            fonts: Vec::new(),
+            glyphs: HashMap::new(),
+            foreground_glyphs: HashSet::new(),
+            full: false,
+        }
+    }
+
+    /// Learns which fonts have color glyphs, given the data of all fonts.
+    pub(super) fn set_fonts(&mut self, font_data: &[FontData]) {
+        for data in &font_data[self.fonts.len().min(font_data.len())..] {
+            let has_color = data
+                .and_then(|(data, index)| Face::parse(data, index).ok())
+                .is_some_and(|face| has_color_glyphs(&face));
+            self.fonts.push(if has_color { *data } else { None });
+        }
+    }
+
+    pub(super) fn has_color_glyphs(&self, font_id: FontId) -> bool {
+        matches!(self.fonts.get(font_id.0), Some(Some(_)))
+    }
+
+    /// Returns a color glyph drawn for a scale and the straight sRGB color of the text, drawing it if it is not in the atlas yet.
+    /// Returns [None] for glyphs drawn by glyph_brush, or if the atlas can't grow anymore.
+    /// In the latter case, the glyph is drawn again once the atlas has been emptied.
+    pub(super) fn glyph(
+        &mut self,
+        font_id: FontId,
+        font: &FontArc,
+        id: ab_glyph::GlyphId,
+        scale: PxScale,
+        color: [u8; 3],
+    ) -> Option<ColorGlyph> {
+        let (data, index) = self.fonts.get(font_id.0).copied().flatten()?;
+        let size = strike_size(scale.y);
+        let id = GlyphId(id.0);
+        let mut key = (font_id, id, size, None);
+        if self.foreground_glyphs.contains(&(font_id, id)) {
+            key.3 = Some(color);
+        }
+        if let Some(glyph) = self.glyphs.get(&key) {
+            return *glyph;
+        }
+
+        let face = Face::parse(data, index).ok()?;
+        let drawn = if face.is_color_glyph(id) {
+            let foreground = RgbaColor::new(color[0], color[1], color[2], 255);
+            draw_layers(font, &face, id, size as f32, foreground).map(
+                |(bounds, canvas, uses_foreground)| {
+                    if uses_foreground {
+                        self.foreground_glyphs.insert((font_id, id));
+                        key.3 = Some(color);
+                    }
+                    (bounds, canvas, false)
+                },
+            )
+        } else if is_color_glyph(&face, id) {
+            face.glyph_raster_image(id, size.min(u16::MAX as u32) as u16)
+                .and_then(|image| draw_bitmap(&image, size as f32))
+        } else {
+            None
+        };
+        let glyph = match drawn {
+            Some((bounds, canvas, tinted)) => {
+                let canvas_size = (canvas.width as u32, canvas.height as u32);
+                match self.atlas.insert(canvas_size, &canvas.to_srgb()) {
+                    Some((x, y)) => Some(ColorGlyph {
+                        tex_coords: Rect {
+                            min: (x as f32, y as f32),
+                            max: ((x + canvas_size.0) as f32, (y + canvas_size.1) as f32),
+                        },
+                        size: size as f32,
+                        bounds,
+                        tinted,
+                    }),
+                    // Glyphs only too large for the atlas are not drawn again
+                    None if self.atlas.could_fit(canvas_size) => {
+                        self.full = true;
+                        return None;
+                    }
+                    None => None,
+                }
+            }
+            None => None,
+        };
+        self.glyphs.insert(key, glyph);
+        glyph
+    }
+
+    /// Empties the atlas if a glyph did not fit in it, so that the glyphs of the next frames are drawn again.
+    /// It must be called between frames, once the glyphs drawn from the atlas have been rendered.
+    pub(super) fn evict_if_full(&mut self) {
+        if self.full {
+            self.full = false;
+            self.atlas.clear();
+            self.glyphs.clear();
+        }
+    }
+}
+
+/// An image in premultiplied linear colors.
+#[derive(Debug, Clone, PartialEq)]
+struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<[f32; 4]>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Canvas {
+        Canvas {
+            width,
+            height,
+            pixels: vec![[0.0; 4]; width * height],
+        }
+    }
+
+    /// Draws a color over a pixel, with an opacity.
+    fn blend(&mut self, x: usize, y: usize, color: [f32; 4], coverage: f32) {
+        let coverage = coverage.clamp(0.0, 1.0);
+        let pixel = &mut self.pixels[y * self.width + x];
+        let alpha = color[3] * coverage;
+        for channel in 0..4 {
+            pixel[channel] = color[channel] * coverage + pixel[channel] * (1.0 - alpha);
+        }
+    }
+
+    /// Encodes the pixels as RGBA bytes, for a texture in the sRGB color space.
+    fn to_srgb(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|pixel| {
+                [
+                    linear_to_srgb(pixel[0]),
+                    linear_to_srgb(pixel[1]),
+                    linear_to_srgb(pixel[2]),
+                    (pixel[3] * 255.0).round().clamp(0.0, 255.0) as u8,
+                ]
+            })
+            .collect()
+    }
+}
+
+/// Converts a straight sRGB color to a premultiplied linear color.
+fn premultiplied_linear(color: RgbaColor) -> [f32; 4] {
+    let alpha = color.alpha as f32 / 255.0;
+    [
+        srgb_to_linear(color.red) * alpha,
+        srgb_to_linear(color.green) * alpha,
+        srgb_to_linear(color.blue) * alpha,
+        alpha,
+    ]
+}
+
+/// Draws the layers of a `COLR` glyph, with the color of the text for layers using it.
+/// Returns the rectangle it covers relative to its origin, its pixels, and whether a layer uses the color of the text.
+fn draw_layers(
+    font: &FontArc,
+    face: &Face,
+    id: GlyphId,
+    size: f32,
+    foreground: RgbaColor,
+) -> Option<(Rect, Canvas, bool)> {
+    // The first passes find the size of the glyph, and the last one draws it
+    // Layers use the color of the text if their colors change with it
+    let mut painter = LayerPainter::new(font, PxScale::from(size));
+    face.paint_color_glyph(id, 0, RgbaColor::new(0, 0, 0, 255), &mut painter)?;
+    let black = std::mem::take(&mut painter.colors);
+    face.paint_color_glyph(id, 0, RgbaColor::new(255, 255, 255, 255), &mut painter)?;
+    let uses_foreground = painter.colors != black;
+    let bounds = painter.bounds?;
+    painter.start_drawing(bounds);
+    face.paint_color_glyph(id, 0, foreground, &mut painter)?;
+    painter
+        .layers
+        .pop()
+        .map(|(_, canvas)| (bounds, canvas, uses_foreground))
+}
+
+/// A region paints are clipped to.
+struct Clip {
+    /// The rectangle containing it, in pixels.
+    bounds: Rect,
+    /// The coverage of each pixel of the canvas, once drawing.
+    mask: Option<Vec<f32>>,
+}
+
+/// Draws the paints of a `COLR` glyph, see [draw_layers].
+struct LayerPainter<'f> {
+    font: &'f FontArc,
+    scale: PxScale,
+    /// The glyph whose outline is filled by the next paint, when it is not clipped.
+    outline: Option<GlyphId>,
+    /// The regions clipping paints, each one inside the previous ones.
+    clips: Vec<Clip>,
+    /// The transforms of paints, in font units, each combined with the previous ones.
+    transforms: Vec<Transform>,
+    /// The rectangle covered by the paints so far, in pixels.
+    bounds: Option<Rect>,
+    /// The colors of the paints so far, before drawing.
+    colors: Vec<[f32; 4]>,
+    /// The position of the top left corner of the canvas, in pixels.
+    origin: (i32, i32),
+    /// Where paints are drawn once drawing, with the mode each layer is composited with over the previous one.
+    layers: Vec<(CompositeMode, Canvas)>,
+}
+
+impl<'f> LayerPainter<'f> {
+    fn new(font: &'f FontArc, scale: PxScale) -> LayerPainter<'f> {
+        LayerPainter {
+            font,
+            scale,
+            outline: None,
+            clips: Vec::new(),
+            transforms: Vec::new(),
+            bounds: None,
+            colors: Vec::new(),
+            origin: (0, 0),
+            layers: Vec::new(),
+        }
+    }
+
+    /// Draws the next paints on a canvas covering a rectangle.
+    fn start_drawing(&mut self, bounds: Rect) {
+        self.origin = (bounds.min.0 as i32, bounds.min.1 as i32);
+        let canvas = Canvas::new(bounds.width() as usize, bounds.height() as usize);
+        self.layers = vec![(CompositeMode::SourceOver, canvas)];
+    }
+
+    /// The size of the canvas, if drawing.
+    fn canvas_size(&self) -> Option<(usize, usize)> {
+        self.layers
+            .first()
+            .map(|(_, canvas)| (canvas.width, canvas.height))
+    }
+
+    /// The scale factors from font units to pixels.
+    fn scale_factors(&self) -> (f32, f32) {
+        let scaled = self.font.as_scaled(self.scale);
+        (scaled.h_scale_factor(), scaled.v_scale_factor())
+    }
+
+    /// Outlines a glyph at the scale of the painter, with the current transform applied.
+    fn shape(&self, id: GlyphId) -> Option<OutlinedGlyph> {
+        let mut outline = self.font.outline(ab_glyph::GlyphId(id.0))?;
+        if let Some(transform) = self.transforms.last() {
+            let apply = |p: &mut ab_glyph::Point| {
+                let (x, y) = (p.x, p.y);
+                p.x = transform.a * x + transform.c * y + transform.e;
+                p.y = transform.b * x + transform.d * y + transform.f;
+            };
+            let (mut min, mut max) = (
+                point(f32::INFINITY, f32::INFINITY),
+                point(f32::NEG_INFINITY, f32::NEG_INFINITY),
+            );
+            for curve in &mut outline.curves {
+                let mut update = |p: &mut ab_glyph::Point| {
+                    apply(p);
+                    min = point(min.x.min(p.x), min.y.min(p.y));
+                    max = point(max.x.max(p.x), max.y.max(p.y));
+                };
+                match curve {
+                    OutlineCurve::Line(p0, p1) => {
+                        update(p0);
+                        update(p1);
+                    }
+                    OutlineCurve::Quad(p0, p1, p2) => {
+                        update(p0);
+                        update(p1);
+                        update(p2);
+                    }
+                    OutlineCurve::Cubic(p0, p1, p2, p3) => {
+                        update(p0);
+                        update(p1);
+                        update(p2);
+                        update(p3);
+                    }
+                }
+            }
+            // Outline bounds have the Y axis pointing up, from the top left corner
+            outline.bounds = ab_glyph::Rect {
+                min: point(min.x, max.y),
+                max: point(max.x, min.y),
+            };
+        }
+        let glyph = ab_glyph::GlyphId(id.0).with_scale(self.scale);
+        let scale_factor = self.font.as_scaled(self.scale).scale_factor();
+        Some(OutlinedGlyph::new(glyph, outline, scale_factor))
+    }
+
+    /// The rectangle covered by the outline of a glyph in pixels, and its coverage of the pixels of the canvas if drawing.
+    fn glyph_region(&self, id: GlyphId) -> Option<(Rect, Option<Vec<f32>>)> {
+        let shape = self.shape(id)?;
+        let px_bounds = shape.px_bounds();
+        let bounds = Rect {
+            min: (px_bounds.min.x, px_bounds.min.y),
+            max: (px_bounds.max.x, px_bounds.max.y),
+        };
+        let mask = self.canvas_size().map(|(width, height)| {
+            let mut mask = vec![0.0; width * height];
+            let offset = (
+                px_bounds.min.x as i32 - self.origin.0,
+                px_bounds.min.y as i32 - self.origin.1,
+            );
+            shape.draw(|x, y, coverage| {
+                let (x, y) = (x as i32 + offset.0, y as i32 + offset.1);
+                if x >= 0 && y >= 0 && (x as usize) < width && (y as usize) < height {
+                    mask[y as usize * width + x as usize] = coverage.clamp(0.0, 1.0);
+                }
+            });
+            mask
+        });
+        Some((bounds, mask))
+    }
+
+    /// Clips the next paints to a region, inside of the current clip.
+    fn push_region(&mut self, bounds: Rect, mask: Option<Vec<f32>>) {
+        let (bounds, mask) = match self.clips.last() {
+            Some(clip) => {
+                let bounds = Rect {
+                    min: (
+                        bounds.min.0.max(clip.bounds.min.0),
+                        bounds.min.1.max(clip.bounds.min.1),
+                    ),
+                    max: (
+                        bounds.max.0.min(clip.bounds.max.0),
+                        bounds.max.1.min(clip.bounds.max.1),
+                    ),
+                };
+                let mask = match (mask, &clip.mask) {
+                    (Some(mask), Some(outer)) => {
+                        Some(mask.iter().zip(outer).map(|(a, b)| a * b).collect())
+                    }
+                    (mask, _) => mask,
+                };
+                (bounds, mask)
+            }
+            None => (bounds, mask),
+        };
+        self.clips.push(Clip { bounds, mask });
+    }
+
+    /// Maps a pixel of the canvas to a point in the space of paints, in font units.
+    fn paint_point(&self, x: usize, y: usize) -> Option<(f32, f32)> {
+        let (h_factor, v_factor) = self.scale_factors();
+        let x = (x as i32 + self.origin.0) as f32 + 0.5;
+        let y = (y as i32 + self.origin.1) as f32 + 0.5;
+        let (x, y) = (x / h_factor, -y / v_factor);
+        match self.transforms.last() {
+            Some(t) => {
+                let determinant = t.a * t.d - t.b * t.c;
+                if determinant == 0.0 {
+                    return None;
+                }
+                let (x, y) = (x - t.e, y - t.f);
+                Some((
+                    (t.d * x - t.c * y) / determinant,
+                    (t.a * y - t.b * x) / determinant,
+                ))
+            }
+            None => Some((x, y)),
+        }
+    }
+}
+
+impl<'a, 'f> colr::Painter<'a> for LayerPainter<'f> {
+    fn outline_glyph(&mut self, glyph_id: GlyphId) {
+        self.outline = Some(glyph_id);
+    }
+
+    fn paint(&mut self, paint: Paint<'a>) {
+        // Paints fill the clip, or the outline of layers of version 0 glyphs
+        let (bounds, own_mask) = match (self.clips.last(), self.outline) {
+            (Some(clip), _) => (clip.bounds, None),
+            (None, Some(glyph)) => match self.glyph_region(glyph) {
+                Some(region) => region,
+                None => return,
+            },
+            (None, None) => return,
+        };
+        if bounds.width() <= 0.0 || bounds.height() <= 0.0 {
+            return;
+        }
+        if self.layers.is_empty() {
+            self.bounds = Some(match self.bounds {
+                Some(previous) => Rect {
+                    min: (
+                        previous.min.0.min(bounds.min.0),
+                        previous.min.1.min(bounds.min.1),
+                    ),
+                    max: (
+                        previous.max.0.max(bounds.max.0),
+                        previous.max.1.max(bounds.max.1),
+                    ),
+                },
+                None => bounds,
+            });
+            match Fill::new(&paint) {
+                Fill::Solid(color) => self.colors.push(color),
+                Fill::Gradient { stops, .. } => {
+                    self.colors.extend(stops.iter().map(|(_, color)| *color))
+                }
+            }
+            return;
+        }
+
+        let fill = Fill::new(&paint);
+        let mask = match (&own_mask, self.clips.last()) {
+            (Some(mask), _) => mask,
+            (
+                None,
+                Some(Clip {
+                    mask: Some(mask), ..
+                }),
+            ) => mask,
+            _ => return,
+        };
+        let colors: Vec<Option<[f32; 4]>> = mask
+            .iter()
+            .enumerate()
+            .map(|(index, coverage)| {
+                let (width, _) = self.canvas_size()?;
+                if *coverage <= 0.0 {
+                    return None;
+                }
+                let point = self.paint_point(index % width, index / width)?;
+                Some(fill.color_at(point))
+            })
+            .collect();
+        let (_, canvas) = self.layers.last_mut().unwrap();
+        for (index, (color, coverage)) in colors.iter().zip(mask).enumerate() {
+            if let Some(color) = color {
+                let (x, y) = (index % canvas.width, index / canvas.width);
+                canvas.blend(x, y, *color, *coverage);
+            }
+        }
+    }
+
+    fn push_clip(&mut self) {
+        let region = self.outline.and_then(|glyph| self.glyph_region(glyph));
+        let (bounds, mask) = region.unwrap_or((Rect::sized(0.0, 0.0, 0.0, 0.0), None));
+        self.push_region(bounds, mask);
+    }
+
+    fn push_clip_box(&mut self, clipbox: ClipBox) {
+        // The box is transformed, and drawn as the rectangle containing it
+        let (h_factor, v_factor) = self.scale_factors();
+        let corners = [
+            (clipbox.x_min, clipbox.y_min),
+            (clipbox.x_max, clipbox.y_min),
+            (clipbox.x_min, clipbox.y_max),
+            (clipbox.x_max, clipbox.y_max),
+        ];
+        let (mut min, mut max) = (
+            (f32::INFINITY, f32::INFINITY),
+            (f32::NEG_INFINITY, f32::NEG_INFINITY),
+        );
+        for (x, y) in corners {
+            let (x, y) = match self.transforms.last() {
+                Some(t) => (t.a * x + t.c * y + t.e, t.b * x + t.d * y + t.f),
+                None => (x, y),
+            };
+            let (x, y) = (x * h_factor, -y * v_factor);
+            min = (min.0.min(x), min.1.min(y));
+            max = (max.0.max(x), max.1.max(y));
+        }
+        let bounds = Rect { min, max };
+        let mask = self.canvas_size().map(|(width, height)| {
+            let mut mask = vec![0.0; width * height];
+            for y in 0..height {
+                for x in 0..width {
+                    let center = (
+                        (x as i32 + self.origin.0) as f32 + 0.5,
+                        (y as i32 + self.origin.1) as f32 + 0.5,
+                    );
+                    if center.0 >= min.0
+                        && center.0 < max.0
+                        && center.1 >= min.1
+                        && center.1 < max.1
+                    {
+                        mask[y * width + x] = 1.0;
+                    }
+                }
+            }
+            mask
+        });
+        self.push_region(bounds, mask);
+    }
+
+    fn pop_clip(&mut self) {
+        self.clips.pop();
+    }
+
+    fn push_layer(&mut self, mode: CompositeMode) {
+        if let Some((width, height)) = self.canvas_size() {
+            self.layers.push((mode, Canvas::new(width, height)));
+        }
+    }
+
+    fn pop_layer(&mut self) {
+        if self.layers.len() < 2 {
+            return;
+        }
+        let (mode, source) = self.layers.pop().unwrap();
+        let (_, destination) = self.layers.last_mut().unwrap();
+        for (destination, source) in destination.pixels.iter_mut().zip(source.pixels) {
+            *destination = composite(mode, source, *destination);
+        }
+    }
+
+    fn push_transform(&mut self, t: Transform) {
+        let combined = match self.transforms.last() {
+            Some(p) => Transform::new(
+                p.a * t.a + p.c * t.b,
+                p.b * t.a + p.d * t.b,
+                p.a * t.c + p.c * t.d,
+                p.b * t.c + p.d * t.d,
+                p.a * t.e + p.c * t.f + p.e,
+                p.b * t.e + p.d * t.f + p.f,
+            ),
+            None => t,
+        };
+        self.transforms.push(combined);
+    }
+
+    fn pop_transform(&mut self) {
+        self.transforms.pop();
+    }
+}
+
+/// The color of a paint at each point.
+enum Fill {
+    Solid([f32; 4]),
+    Gradient {
+        kind: GradientKind,
+        /// The offsets and premultiplied linear colors of the stops, by offset.
+        stops: Vec<(f32, [f32; 4])>,
+        extend: GradientExtend,
+    },
+}
+
+/// The shape of a gradient of a paint, in font units.
+enum GradientKind {
+    /// Colors vary along a vector, from a point.
+    Linear {
+        start: (f32, f32),
+        vector: (f32, f32),
+    },
+    /// Colors vary from a circle to another, given by their center and radius.
+    Radial {
+        start: (f32, f32, f32),
+        end: (f32, f32, f32),
+    },
+    /// Colors vary with the counter-clockwise angle around a center, between two angles in degrees.
+    Sweep {
+        center: (f32, f32),
+        angles: (f32, f32),
+    },
+}
+
+impl Fill {
+    fn new(paint: &Paint) -> Fill {
+        let gradient = |kind, stops: &mut dyn Iterator<Item = colr::ColorStop>, extend| {
+            let mut stops: Vec<(f32, [f32; 4])> = stops
+                .map(|stop| (stop.stop_offset, premultiplied_linear(stop.color)))
+                .collect();
+            stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+            Fill::Gradient {
+                kind,
+                stops,
+                extend,
+            }
+        };
+        match paint {
+            Paint::Solid(color) => Fill::Solid(premultiplied_linear(*color)),
+            Paint::LinearGradient(g) => {
+                // The gradient goes from the first point to the second one, projected on the normal of the line to the third one
+                let vector = (g.x1 - g.x0, g.y1 - g.y0);
+                let normal = (g.y0 - g.y2, g.x2 - g.x0);
+                let length = normal.0 * normal.0 + normal.1 * normal.1;
+                let vector = match length > 0.0 {
+                    true => {
+                        let factor = (vector.0 * normal.0 + vector.1 * normal.1) / length;
+                        (normal.0 * factor, normal.1 * factor)
+                    }
+                    false => vector,
+                };
+                let kind = GradientKind::Linear {
+                    start: (g.x0, g.y0),
+                    vector,
+                };
+                gradient(kind, &mut g.stops(0, &[]), g.extend)
+            }
+            Paint::RadialGradient(g) => {
+                let kind = GradientKind::Radial {
+                    start: (g.x0, g.y0, g.r0),
+                    end: (g.x1, g.y1, g.r1),
+                };
+                gradient(kind, &mut g.stops(0, &[]), g.extend)
+            }
+            Paint::SweepGradient(g) => {
+                // Angles are stored biased by half a turn
+                let kind = GradientKind::Sweep {
+                    center: (g.center_x, g.center_y),
+                    angles: ((g.start_angle + 1.0) * 180.0, (g.end_angle + 1.0) * 180.0),
+                };
+                gradient(kind, &mut g.stops(0, &[]), g.extend)
+            }
+        }
+    }
+
+    /// The premultiplied linear color at a point, in font units.
+    fn color_at(&self, point: (f32, f32)) -> [f32; 4] {
+        let (kind, stops, extend) = match self {
+            Fill::Solid(color) => return *color,
+            Fill::Gradient {
+                kind,
+                stops,
+                extend,
+            } => (kind, stops, extend),
+        };
+        match kind.offset(point) {
+            Some(offset) => color_at_offset(stops, *extend, offset),
+            None => [0.0; 4],
+        }
+    }
+}
+
+impl GradientKind {
+    /// The offset of a point in the gradient, [None] for points outside of radial gradients.
+    fn offset(&self, (x, y): (f32, f32)) -> Option<f32> {
+        match *self {
+            GradientKind::Linear { start, vector } => {
+                let length = vector.0 * vector.0 + vector.1 * vector.1;
+                match length > 0.0 {
+                    true => Some(((x - start.0) * vector.0 + (y - start.1) * vector.1) / length),
+                    false => None,
+                }
+            }
+            GradientKind::Radial { start, end } => {
+                // The largest offset whose circle goes through the point, with a positive radius
+                let center = (end.0 - start.0, end.1 - start.1);
+                let radius = end.2 - start.2;
+                let point = (x - start.0, y - start.1);
+                let a = center.0 * center.0 + center.1 * center.1 - radius * radius;
+                let b = point.0 * center.0 + point.1 * center.1 + start.2 * radius;
+                let c = point.0 * point.0 + point.1 * point.1 - start.2 * start.2;
+                let valid = |t: f32| start.2 + t * radius >= 0.0;
+                if a.abs() < 1e-6 {
+                    return Some(c / (2.0 * b)).filter(|t| t.is_finite() && valid(*t));
+                }
+                let discriminant = b * b - a * c;
+                if discriminant < 0.0 {
+                    return None;
+                }
+                let roots = ((b + discriminant.sqrt()) / a, (b - discriminant.sqrt()) / a);
+                let (high, low) = (roots.0.max(roots.1), roots.0.min(roots.1));
+                [high, low].iter().copied().find(|t| valid(*t))
+            }
+            GradientKind::Sweep { center, angles } => {
+                let angle = (y - center.1)
+                    .atan2(x - center.0)
+                    .to_degrees()
+                    .rem_euclid(360.0);
+                match angles.1 != angles.0 {
+                    true => Some((angle - angles.0) / (angles.1 - angles.0)),
+                    false => None,
+                }
+            }
+        }
+    }
+}
+
+/// The color of a gradient at an offset, extended beyond its stops.
+fn color_at_offset(stops: &[(f32, [f32; 4])], extend: GradientExtend, offset: f32) -> [f32; 4] {
+    let (first, last) = match (stops.first(), stops.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return [0.0; 4],
+    };
+    let length = last.0 - first.0;
+    let offset = match extend {
+        _ if length <= 0.0 => offset,
+        GradientExtend::Pad => offset,
+        GradientExtend::Repeat => first.0 + (offset - first.0).rem_euclid(length),
+        GradientExtend::Reflect => {
+            let position = (offset - first.0).rem_euclid(2.0 * length);
+            first.0 + length - (position - length).abs()
+        }
+    };
+    if offset <= first.0 {
+        return first.1;
+    }
+    for pair in stops.windows(2) {
+        let ((start, from), (end, to)) = (pair[0], pair[1]);
+        if offset <= end {
+            let t = match end > start {
+                true => (offset - start) / (end - start),
+                false => 1.0,
+            };
+            let mut color = [0.0; 4];
+            for channel in 0..4 {
+                color[channel] = from[channel] + (to[channel] - from[channel]) * t;
+            }
+            return color;
+        }
+    }
+    last.1
+}
+
+/// Composites a premultiplied color over another, see the compositing and blending specification of the W3C.
+fn composite(mode: CompositeMode, source: [f32; 4], destination: [f32; 4]) -> [f32; 4] {
+    let (sa, da) = (source[3], destination[3]);
+    let porter_duff = |fa: f32, fb: f32| {
+        let mut result = [0.0; 4];
+        for channel in 0..4 {
+            result[channel] = (source[channel] * fa + destination[channel] * fb).min(1.0);
+        }
+        result
+    };
+    match mode {
+        CompositeMode::Clear => return [0.0; 4],
+        CompositeMode::Source => return source,
+        CompositeMode::Destination => return destination,
+        CompositeMode::SourceOver => return porter_duff(1.0, 1.0 - sa),
+        CompositeMode::DestinationOver => return porter_duff(1.0 - da, 1.0),
+        CompositeMode::SourceIn => return porter_duff(da, 0.0),
+        CompositeMode::DestinationIn => return porter_duff(0.0, sa),
+        CompositeMode::SourceOut => return porter_duff(1.0 - da, 0.0),
+        CompositeMode::DestinationOut => return porter_duff(0.0, 1.0 - sa),
+        CompositeMode::SourceAtop => return porter_duff(da, 1.0 - sa),
+        CompositeMode::DestinationAtop => return porter_duff(1.0 - da, sa),
+        CompositeMode::Xor => return porter_duff(1.0 - da, 1.0 - sa),
+        CompositeMode::Plus => return porter_duff(1.0, 1.0),
+        _ => {}
+    }
+
+    // Blend modes mix the straight colors where both layers are, and draw the source over the destination
+    let straight = |color: [f32; 4]| match color[3] > 0.0 {
+        true => [
+            color[0] / color[3],
+            color[1] / color[3],
+            color[2] / color[3],
+        ],
+        false => [0.0; 3],
+    };
+    let (cs, cb) = (straight(source), straight(destination));
+    let separable = |blend: fn(f32, f32) -> f32| {
+        [
+            blend(cs[0], cb[0]),
+            blend(cs[1], cb[1]),
+            blend(cs[2], cb[2]),
+        ]
+    };
+    let blended = match mode {
+        CompositeMode::Multiply => separable(|s, b| s * b),
+        CompositeMode::Screen => separable(screen),
+        CompositeMode::Overlay => separable(|s, b| hard_light(b, s)),
+        CompositeMode::Darken => separable(f32::min),
+        CompositeMode::Lighten => separable(f32::max),
+        CompositeMode::ColorDodge => separable(|s, b| match (b, s) {
+            (b, _) if b <= 0.0 => 0.0,
+            (_, s) if s >= 1.0 => 1.0,
+            (b, s) => (b / (1.0 - s)).min(1.0),
+        }),
+        CompositeMode::ColorBurn => separable(|s, b| match (b, s) {
+            (b, _) if b >= 1.0 => 1.0,
+            (_, s) if s <= 0.0 => 0.0,
+            (b, s) => 1.0 - ((1.0 - b) / s).min(1.0),
+        }),
+        CompositeMode::HardLight => separable(hard_light),
+        CompositeMode::SoftLight => separable(|s, b| {
+            if s <= 0.5 {
+                b - (1.0 - 2.0 * s) * b * (1.0 - b)
+            } else {
+                let d = match b <= 0.25 {
+                    true => ((16.0 * b - 12.0) * b + 4.0) * b,
+                    false => b.sqrt(),
+                };
+                b + (2.0 * s - 1.0) * (d - b)
+            }
+        }),
+        CompositeMode::Difference => separable(|s, b| (s - b).abs()),
+        CompositeMode::Exclusion => separable(|s, b| s + b - 2.0 * s * b),
+        CompositeMode::Hue => set_luminosity(set_saturation(cs, saturation(cb)), luminosity(cb)),
+        CompositeMode::Saturation => {
+            set_luminosity(set_saturation(cb, saturation(cs)), luminosity(cb))
+        }
+        CompositeMode::Color => set_luminosity(cs, luminosity(cb)),
+        _ => set_luminosity(cb, luminosity(cs)),
+    };
+    let mut result = [0.0; 4];
+    for channel in 0..3 {
+        result[channel] = (1.0 - da) * source[channel]
+            + (1.0 - sa) * destination[channel]
+            + sa * da * blended[channel];
+    }
+    result[3] = sa + da - sa * da;
+    result
+}
+
+fn screen(s: f32, b: f32) -> f32 {
+    s + b - s * b
+}
+
+fn hard_light(s: f32, b: f32) -> f32 {
+    match s <= 0.5 {
+        true => b * 2.0 * s,
+        false => screen(2.0 * s - 1.0, b),
+    }
+}
+
+fn luminosity([r, g, b]: [f32; 3]) -> f32 {
+    0.3 * r + 0.59 * g + 0.11 * b
+}
+
+fn set_luminosity(color: [f32; 3], target: f32) -> [f32; 3] {
+    let d = target - luminosity(color);
+    let color = color.map(|channel| channel + d);
+    let l = luminosity(color);
+    let (min, max) = (
+        color[0].min(color[1]).min(color[2]),
+        color[0].max(color[1]).max(color[2]),
+    );
+    color.map(|channel| {
+        let mut channel = channel;
+        if min < 0.0 {
+            channel = l + (channel - l) * l / (l - min);
+        }
+        if max > 1.0 {
+            channel = l + (channel - l) * (1.0 - l) / (max - l);
+        }
+        channel
+    })
+}
+
+fn saturation(color: [f32; 3]) -> f32 {
+    color[0].max(color[1]).max(color[2]) - color[0].min(color[1]).min(color[2])
+}
+
+fn set_saturation(color: [f32; 3], target: f32) -> [f32; 3] {
+    let (min, max) = (
+        color[0].min(color[1]).min(color[2]),
+        color[0].max(color[1]).max(color[2]),
+    );
+    color.map(|channel| match max > min {
+        true => (channel - min) * target / (max - min),
+        false => 0.0,
+    })
+}
+
+/// Draws the bitmap of a glyph at a size, scaling it down if its strike is larger.
+/// Returns the rectangle it covers relative to its origin, its pixels, and whether it is a grayscale bitmap.
+fn draw_bitmap(image: &RasterGlyphImage, size: f32) -> Option<(Rect, Canvas, bool)> {
+    let (bitmap, tinted) = decode_bitmap(image)?;
+
+    // Bitmaps are placed from their bottom left corner, with the Y axis pointing up
+    let scale = size / image.pixels_per_em as f32;
+    let bounds = Rect {
+        min: (
+            image.x as f32 * scale,
+            -(image.y as f32 + bitmap.height as f32) * scale,
+        ),
+        max: (
+            (image.x as f32 + bitmap.width as f32) * scale,
+            -image.y as f32 * scale,
+        ),
+    };
+    let canvas = if scale < 1.0 {
+        downscale(&bitmap, scale)
+    } else {
+        bitmap
+    };
+    Some((bounds, canvas, tinted))
+}
+
+/// Scales an image down, averaging the pixels covered by each pixel of the result.
+fn downscale(image: &Canvas, scale: f32) -> Canvas {
+    let width = ((image.width as f32 * scale).ceil() as usize).max(1);
+    let height = ((image.height as f32 * scale).ceil() as usize).max(1);
+    let mut result = Canvas::new(width, height);
+    let mut counts = vec![0; width * height];
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let target = ((y as f32 * scale) as usize).min(height - 1) * width
+                + ((x as f32 * scale) as usize).min(width - 1);
+            let pixel = image.pixels[y * image.width + x];
+            for (sum, value) in result.pixels[target].iter_mut().zip(pixel) {
+                *sum += value;
+            }
+            counts[target] += 1;
+        }
+    }
+    for (pixel, count) in result.pixels.iter_mut().zip(counts) {
+        for value in pixel.iter_mut() {
+            *value /= count.max(1) as f32;
+        }
+    }
+    result
+}
+
+/// Decodes the bitmap of a glyph, and tells whether it is a grayscale bitmap.
+fn decode_bitmap(image: &RasterGlyphImage) -> Option<(Canvas, bool)> {
+    let (width, height) = (image.width as usize, image.height as usize);
+    let (bits, packed) = match image.format {
+        RasterImageFormat::PNG => return decode_png(image.data).map(|canvas| (canvas, false)),
+        RasterImageFormat::BitmapPremulBgra32 => {
+            let mut canvas = Canvas::new(width, height);
+            for (pixel, bgra) in canvas.pixels.iter_mut().zip(image.data.chunks_exact(4)) {
+                // The colors are premultiplied in the sRGB color space
+                let alpha = bgra[3] as f32 / 255.0;
+                let channel = |value: u8| match bgra[3] {
+                    0 => 0.0,
+                    a => {
+                        srgb_to_linear(
+                            ((value as u32 * 255 + a as u32 / 2) / a as u32).min(255) as u8
+                        ) * alpha
+                    }
+                };
+                *pixel = [channel(bgra[2]), channel(bgra[1]), channel(bgra[0]), alpha];
+            }
+            return Some((canvas, false));
+        }
+        RasterImageFormat::BitmapMono => (1, false),
+        RasterImageFormat::BitmapMonoPacked => (1, true),
+        RasterImageFormat::BitmapGray2 => (2, false),
+        RasterImageFormat::BitmapGray2Packed => (2, true),
+        RasterImageFormat::BitmapGray4 => (4, false),
+        RasterImageFormat::BitmapGray4Packed => (4, true),
+        RasterImageFormat::BitmapGray8 => (8, false),
+    };
+
+    // Grayscale bitmaps are drawn white, with the gray level as opacity
+    // Pixels are stored from the most significant bit, and rows start on a new byte unless they are packed
+    let row_bits = match packed {
+        true => width * bits,
+        false => (width * bits).div_ceil(8) * 8,
+    };
+    let max = ((1 << bits) - 1) as f32;
+    let mut canvas = Canvas::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            let bit = y * row_bits + x * bits;
+            let byte = *image.data.get(bit / 8)?;
+            let value = (byte >> (8 - bits - bit % 8)) & ((1 << bits) - 1) as u8;
+            let value = value as f32 / max;
+            canvas.pixels[y * width + x] = [value; 4];
+        }
+    }
+    Some((canvas, true))
+}
+
+#[cfg(feature = "png")]
+fn decode_png(data: &[u8]) -> Option<Canvas> {
+    let mut decoder = png::Decoder::new(data);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let (info, mut reader) = decoder.read_info().ok()?;
+    let mut buffer = vec![0; info.buffer_size()];
+    reader.next_frame(&mut buffer).ok()?;
+
+    let channels = match info.color_type {
+        png::ColorType::RGBA => 4,
+        png::ColorType::RGB => 3,
+        png::ColorType::GrayscaleAlpha => 2,
+        png::ColorType::Grayscale => 1,
+        png::ColorType::Indexed => return None,
+    };
+    let (width, height) = (info.width as usize, info.height as usize);
+    let mut canvas = Canvas::new(width, height);
+    for (pixel, values) in canvas.pixels.iter_mut().zip(buffer.chunks_exact(channels)) {
+        let (rgb, alpha) = match channels {
+            4 => ([values[0], values[1], values[2]], values[3]),
+            3 => ([values[0], values[1], values[2]], 255),
+            2 => ([values[0]; 3], values[1]),
+            _ => ([values[0]; 3], 255),
+        };
+        *pixel = premultiplied_linear(RgbaColor::new(rgb[0], rgb[1], rgb[2], alpha));
+    }
+    Some(canvas)
+}
+
+/// Bitmaps compressed as PNG need the `png` feature to be drawn.
+#[cfg(not(feature = "png"))]
+fn decode_png(_data: &[u8]) -> Option<Canvas> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use colr::Painter;
+    use glyph_brush::{BuiltInLineBreaker, Layout, SectionText};
+
+    const FONT: &[u8] = include_bytes!("ressources/Inconsolata-Regular.ttf");
+
+    #[test]
+    fn test_decode_bitmap() {
+        let image = |format, width, height, data| RasterGlyphImage {
+            x: 1,
+            y: -2,
+            width,
+            height,
+            pixels_per_em: 20,
+            format,
+            data,
+        };
+
+        // Rows of 2 bits pixels start on a new byte, unless they are packed
+        let gray = image(
+            RasterImageFormat::BitmapGray2,
+            3,
+            2,
+            &[0b11_01_00_00, 0b00_10_11_00],
+        );
+        let (canvas, tinted) = decode_bitmap(&gray).unwrap();
+        assert!(tinted);
+        let alphas: Vec<f32> = canvas.pixels.iter().map(|pixel| pixel[3]).collect();
+        assert_eq!(alphas, [1.0, 1.0 / 3.0, 0.0, 0.0, 2.0 / 3.0, 1.0]);
+        assert_eq!(canvas.pixels[0], [1.0; 4]);
+        let packed = image(
+            RasterImageFormat::BitmapMonoPacked,
+            3,
+            3,
+            &[0b1010_1011, 0b0],
+        );
+        let (canvas, _) = decode_bitmap(&packed).unwrap();
+        let alphas: Vec<f32> = canvas.pixels.iter().map(|pixel| pixel[3]).collect();
+        assert_eq!(alphas, [1.0, 0.0, 1.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0]);
+        // Missing data is not read
+        let short = image(RasterImageFormat::BitmapGray8, 2, 2, &[0, 0, 0]);
+        assert_eq!(decode_bitmap(&short), None);
+        // PNG bitmaps are left to glyph_brush without the png feature
+        let png = image(RasterImageFormat::PNG, 1, 1, &[]);
+        assert_eq!(can_decode(&png), cfg!(feature = "png"));
+        assert!(can_decode(&gray));
+
+        // Colors are unpremultiplied to be linearized
+        let bgra = image(
+            RasterImageFormat::BitmapPremulBgra32,
+            1,
+            1,
+            &[0, 0, 128, 128],
+        );
+        let (canvas, tinted) = decode_bitmap(&bgra).unwrap();
+        assert!(!tinted);
+        let alpha = 128.0 / 255.0;
+        assert_eq!(canvas.pixels[0], [alpha, 0.0, 0.0, alpha]);
+
+        // Bitmaps are placed from their bottom left corner, and scaled down to the size of the text
+        let (bounds, canvas, _) = draw_bitmap(&gray, 10.0).unwrap();
+        assert_eq!(bounds.min, (0.5, 0.0));
+        assert_eq!(bounds.max, (2.0, 1.0));
+        assert_eq!((canvas.width, canvas.height), (2, 1));
+        let (bounds, canvas, _) = draw_bitmap(&gray, 40.0).unwrap();
+        assert_eq!(bounds.min, (2.0, 0.0));
+        assert_eq!((canvas.width, canvas.height), (3, 2));
+    }
+
+    #[test]
+    fn test_layer_painter() {
+        let font = FontArc::try_from_slice(FONT).unwrap();
+        let face = Face::parse(FONT, 0).unwrap();
+        let id = face.glyph_index('A').unwrap();
+        let red = RgbaColor::new(255, 0, 0, 255);
+        let paint = |painter: &mut LayerPainter| {
+            painter.outline_glyph(id);
+            painter.paint(Paint::Solid(red));
+        };
+
+        let mut painter = LayerPainter::new(&font, PxScale::from(20.0));
+        paint(&mut painter);
+        let bounds = painter.bounds.unwrap();
+        // Glyphs are above the baseline
+        assert!(bounds.min.1 < -10.0 && bounds.max.1 <= 1.0);
+        // The colors of paints tell whether they use the color of the text
+        assert_eq!(painter.colors, vec![[1.0, 0.0, 0.0, 1.0]]);
+        painter.start_drawing(bounds);
+        paint(&mut painter);
+        let (_, canvas) = painter.layers.pop().unwrap();
+        assert!(canvas.pixels.contains(&[1.0, 0.0, 0.0, 1.0]));
+        assert!(canvas.pixels.iter().all(|pixel| pixel[1] == 0.0));
+
+        // Transforms are in font units, and combined with the previous ones
+        let mut painter = LayerPainter::new(&font, PxScale::from(20.0));
+        painter.push_transform(Transform::new(1.0, 0.0, 0.0, 1.0, 0.0, 500.0));
+        painter.push_transform(Transform::new(2.0, 0.0, 0.0, 2.0, 0.0, 0.0));
+        paint(&mut painter);
+        let scaled = painter.bounds.unwrap();
+        assert!((scaled.width() - 2.0 * bounds.width()).abs() <= 2.0);
+        assert!(scaled.max.1 < -4.0);
+        painter.pop_transform();
+        painter.pop_transform();
+
+        // Clipped paints fill the outline of the clip
+        let mut painter = LayerPainter::new(&font, PxScale::from(20.0));
+        painter.outline_glyph(face.glyph_index('.').unwrap());
+        painter.push_clip();
+        painter.outline_glyph(id);
+        painter.paint(Paint::Solid(red));
+        assert!(painter.bounds.unwrap().height() < bounds.height() / 2.0);
+
+        // Clip boxes clip paints too
+        let mut painter = LayerPainter::new(&font, PxScale::from(20.0));
+        painter.start_drawing(bounds);
+        painter.push_clip_box(ClipBox {
+            x_min: -1000.0,
+            y_min: -1000.0,
+            x_max: 1000.0,
+            y_max: 0.0,
+        });
+        paint(&mut painter);
+        assert!(painter.layers[0]
+            .1
+            .pixels
+            .iter()
+            .all(|pixel| pixel[3] == 0.0));
+    }
+
+    #[test]
+    fn test_gradients() {
+        let linear = GradientKind::Linear {
+            start: (10.0, 0.0),
+            vector: (100.0, 0.0),
+        };
+        assert_eq!(linear.offset((60.0, 30.0)), Some(0.5));
+        // Radial gradients grow from the first circle to the second one
+        let radial = GradientKind::Radial {
+            start: (0.0, 0.0, 10.0),
+            end: (0.0, 0.0, 110.0),
+        };
+        assert_eq!(radial.offset((0.0, 60.0)), Some(0.5));
+        let sweep = GradientKind::Sweep {
+            center: (0.0, 0.0),
+            angles: (0.0, 360.0),
+        };
+        assert_eq!(sweep.offset((0.0, 10.0)), Some(0.25));
+
+        let (black, white) = ([0.0, 0.0, 0.0, 1.0], [1.0; 4]);
+        let stops = [(0.0, black), (1.0, white)];
+        let at = |extend, offset| color_at_offset(&stops, extend, offset)[0];
+        assert_eq!(at(GradientExtend::Pad, 0.25), 0.25);
+        assert_eq!(at(GradientExtend::Pad, 1.5), 1.0);
+        assert_eq!(at(GradientExtend::Repeat, 1.25), 0.25);
+        assert_eq!(at(GradientExtend::Reflect, 1.25), 0.75);
+        assert_eq!(color_at_offset(&[], GradientExtend::Pad, 0.5), [0.0; 4]);
+    }
+
+    #[test]
+    fn test_composite() {
+        let red = [1.0, 0.0, 0.0, 1.0];
+        let half_blue = [0.0, 0.0, 0.5, 0.5];
+        assert_eq!(
+            composite(CompositeMode::SourceOver, half_blue, red),
+            [0.5, 0.0, 0.5, 1.0]
+        );
+        assert_eq!(
+            composite(CompositeMode::DestinationIn, red, half_blue),
+            half_blue
+        );
+        assert_eq!(composite(CompositeMode::Clear, red, red), [0.0; 4]);
+        // Blend modes mix colors where both layers are
+        let gray = [0.5, 0.5, 0.5, 1.0];
+        assert_eq!(
+            composite(CompositeMode::Multiply, gray, red),
+            [0.5, 0.0, 0.0, 1.0]
+        );
+        assert_eq!(
+            composite(CompositeMode::Screen, gray, red),
+            [1.0, 0.5, 0.5, 1.0]
+        );
+        assert_eq!(composite(CompositeMode::Multiply, gray, [0.0; 4]), gray);
+        let luminosity = composite(CompositeMode::Luminosity, gray, red);
+        assert!(
+            (super::luminosity([luminosity[0], luminosity[1], luminosity[2]]) - 0.5).abs() < 0.001
+        );
+    }
+
+    #[test]
+    fn test_strike_size() {
+        assert_eq!(strike_size(16.0), 16);
+        assert_eq!(strike_size(20.0), 23);
+        assert_eq!(strike_size(22.0), 23);
+        assert_eq!(strike_size(0.2), 1);
+        assert_eq!(strike_size(5000.0), 512);
+        // Sizes of a zoom share a few strikes
+        let mut sizes: Vec<u32> = (160..320)
+            .map(|size| strike_size(size as f32 / 10.0))
+            .collect();
+        sizes.dedup();
+        assert_eq!(sizes.len(), 5);
+    }
+
+    #[test]
+    fn test_fonts_without_color_glyphs() {
+        let font = FontArc::try_from_slice(FONT).unwrap();
+        let mut atlas = ColorAtlas::new();
+        atlas.set_fonts(&[Some((FONT, 0)), None]);
+        assert!(!atlas.has_color_glyphs(FontId(0)));
+        assert!(!atlas.has_color_glyphs(FontId(2)));
+        assert!(can_draw_glyphs(FONT, 0));
+        assert!(!can_draw_glyphs(b"not a font", 0));
+        let glyph = atlas.glyph(
+            FontId(0),
+            &font,
+            font.glyph_id('A'),
+            PxScale::from(20.0),
+            [0, 0, 0],
+        );
+        assert_eq!(glyph, None);
+        assert_eq!(atlas.atlas.take_changes(), None);
+
+        // Their glyphs are all kept by the filter
+        let layout = Layout::SingleLine {
+            line_breaker: BuiltInLineBreaker::default(),
+            h_align: glyph_brush::HorizontalAlign::Left,
+            v_align: glyph_brush::VerticalAlign::Top,
+        };
+        let filter = ColorFilter {
+            layout,
+            font_data: vec![Some((FONT, 0))],
+        };
+        let geometry = SectionGeometry::default();
+        let section = [SectionText {
+            text: "A b",
+            scale: PxScale::from(20.0),
+            font_id: FontId(0),
+        }];
+        assert_eq!(
+            filter.calculate_glyphs(&[&font], &geometry, &section),
+            layout.calculate_glyphs(&[&font], &geometry, &section)
+        );
+    }
+}
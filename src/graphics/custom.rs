@@ -0,0 +1,320 @@
+use super::transform::Transform;
+use std::ops::Range;
+
+/// Declarations to paste in GLSL shaders of custom pipelines, after the `#version` line.
+///
+/// It declares the uniforms shared with the backend (set 0), and two functions:
+/// - `vec4 to_clip_space(vec2 position)` transforms a position in pixels, and converts it to clip space (for the vertex shader)
+/// - `float clip_coverage(vec2 frag_coord)` is the part of the fragment at `gl_FragCoord.xy` inside of the current clip, by which the output color must be multiplied (for the fragment shader)
+pub const UNIFORMS_GLSL: &str = r#"
+layout(set=0, binding=0)
+uniform Uniforms {
+    float screen_width;
+    float screen_height;
+    uint premultiplied_input;
+    uint clip_plane_count;
+    vec4 clip_planes[12];
+    vec4 transform[2];
+    uint encode_srgb;
+    float text_gamma;
+    float text_contrast;
+};
+
+vec4 to_clip_space(vec2 position) {
+    vec2 p = vec2(dot(transform[0].xyz, vec3(position, 1.0)), dot(transform[1].xyz, vec3(position, 1.0)));
+    p = ((2.0 / vec2(screen_width, screen_height)) * p - 1.0) * vec2(1.0, -1.0);
+    return vec4(p, 0.0, 1.0);
+}
+
+float clip_coverage(vec2 frag_coord) {
+    float coverage = 1.0;
+    for (uint i = 0u; i < clip_plane_count; i++) {
+        float plane_distance = dot(clip_planes[i].xyz, vec3(frag_coord, 1.0));
+        coverage *= clamp(plane_distance + 0.5, 0.0, 1.0);
+    }
+    return coverage;
+}
+"#;
+
+/// Same as [UNIFORMS_GLSL], for WGSL shaders.
+///
+/// The uniforms are bound as `uniforms`, and `clip_coverage` takes the `[[builtin(position)]]` of the fragment.
+pub const UNIFORMS_WGSL: &str = r#"
+[[block]]
+struct Uniforms {
+    screen_width: f32;
+    screen_height: f32;
+    premultiplied_input: u32;
+    clip_plane_count: u32;
+    clip_planes: array<vec4<f32>, 12>;
+    transform: array<vec4<f32>, 2>;
+    encode_srgb: u32;
+    text_gamma: f32;
+    text_contrast: f32;
+};
+
+[[group(0), binding(0)]]
+var<uniform> uniforms: Uniforms;
+
+fn to_clip_space(position: vec2<f32>) -> vec4<f32> {
+    let p = vec3<f32>(position, 1.0);
+    let transformed = vec2<f32>(dot(uniforms.transform[0].xyz, p), dot(uniforms.transform[1].xyz, p));
+    let size = vec2<f32>(uniforms.screen_width, uniforms.screen_height);
+    let normalized = (transformed * 2.0 / size - vec2<f32>(1.0, 1.0)) * vec2<f32>(1.0, -1.0);
+    return vec4<f32>(normalized, 0.0, 1.0);
+}
+
+fn clip_coverage(frag_coord: vec2<f32>) -> f32 {
+    var coverage: f32 = 1.0;
+    var i: u32 = 0u;
+    loop {
+        if (i >= uniforms.clip_plane_count) {
+            break;
+        }
+        let plane_distance = dot(uniforms.clip_planes[i].xyz, vec3<f32>(frag_coord, 1.0));
+        coverage = coverage * clamp(plane_distance + 0.5, 0.0, 1.0);
+        i = i + 1u;
+    }
+    return coverage;
+}
+"#;
+
+/// A shader of a custom pipeline, see [CustomPipelineDescriptor].
+pub struct CustomShader<'a> {
+    pub source: wgpu::ShaderSource<'a>,
+    /// The name of the function to run.
+    /// GLSL shaders compiled to SPIR-V use `main`.
+    pub entry_point: &'a str,
+}
+
+/// Describes a render pipeline provided by the user, see [WgpuBackend::create_custom_pipeline](super::WgpuBackend::create_custom_pipeline).
+///
+/// Shaders are given the uniforms of the backend in bind group 0, which must be declared as [UNIFORMS_GLSL] or [UNIFORMS_WGSL] do.
+/// To behave like built-in draws, the vertex shader should transform positions with `to_clip_space`, and the fragment shader should:
+/// - multiply its output by `clip_coverage`, so that clips are respected
+/// - output colors premultiplied by their alpha, unless `premultiplied_input` is set (see [BlendMode](super::BlendMode))
+/// - output linear colors, converting the sRGB colors it is given (see [Color](super::Color)), and encode them to sRGB itself when `encode_srgb` is set because the target is not an sRGB format
+pub struct CustomPipelineDescriptor<'a> {
+    pub label: &'a str,
+    pub vertex_shader: CustomShader<'a>,
+    pub fragment_shader: CustomShader<'a>,
+    /// The layout of the vertices given to [WgpuBackend::add_custom](super::WgpuBackend::add_custom).
+    pub vertex_layout: wgpu::VertexBufferLayout<'a>,
+    /// The size in bytes of the uniforms specific to this pipeline, bound in bind group 1.
+    /// `0` means there is no such bind group.
+    /// Their value is set with [WgpuBackend::write_custom_uniforms](super::WgpuBackend::write_custom_uniforms), and starts zeroed.
+    pub uniforms_size: u64,
+}
+
+/// Identifies a pipeline created with [WgpuBackend::create_custom_pipeline](super::WgpuBackend::create_custom_pipeline).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CustomPipelineId(pub(super) usize);
+
+/// A pipeline provided by the user.
+pub(super) struct CustomPipeline {
+    /// One pipeline per [BlendMode](super::BlendMode).
+    pub(super) pipelines: Vec<wgpu::RenderPipeline>,
+    pub(super) vertex_stride: u64,
+    pub(super) uniforms: Option<CustomUniforms>,
+}
+
+/// A draw call made with a [CustomPipeline].
+#[derive(Debug, Clone)]
+pub(super) struct CustomDraw {
+    pub(super) pipeline: CustomPipelineId,
+    /// The range of the vertices in the custom vertex buffer, in bytes.
+    pub(super) vertices: Range<u64>,
+    pub(super) transform: Transform,
+    /// The offset of the uniforms of the draw in the uniform buffer of the pipeline, if it has uniforms.
+    pub(super) uniforms_offset: Option<u32>,
+}
+
+/// Appends vertices to the bytes of the custom vertex buffer, and returns their range.
+/// The bytes are padded to a multiple of [wgpu::COPY_BUFFER_ALIGNMENT], so that they can be uploaded and the next vertices can be bound.
+pub(super) fn push_vertices(buffer: &mut Vec<u8>, vertices: &[u8]) -> Range<u64> {
+    let start = buffer.len() as u64;
+    buffer.extend_from_slice(vertices);
+    let end = buffer.len() as u64;
+    buffer.resize(align(end, wgpu::COPY_BUFFER_ALIGNMENT) as usize, 0);
+    start..end
+}
+
+/// Rounds a size up to a multiple of `alignment`.
+fn align(size: u64, alignment: u64) -> u64 {
+    size.div_ceil(alignment) * alignment
+}
+
+/// The values of the uniforms of a [CustomPipeline] for each draw of a frame.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct StagedUniforms {
+    /// The value given to the next draws.
+    value: Vec<u8>,
+    /// The values of the draws of the frame, every [StagedUniforms::stride] bytes.
+    staged: Vec<u8>,
+}
+
+impl StagedUniforms {
+    pub(super) fn new(size: u64) -> StagedUniforms {
+        StagedUniforms {
+            value: vec![0; size as usize],
+            staged: Vec::new(),
+        }
+    }
+
+    /// The distance between the values of two draws, since dynamic offsets must be multiples of [wgpu::BIND_BUFFER_ALIGNMENT].
+    pub(super) fn stride(&self) -> u64 {
+        align(self.value.len() as u64, wgpu::BIND_BUFFER_ALIGNMENT)
+    }
+
+    /// Panics if the data does not have the size of the uniforms.
+    pub(super) fn set(&mut self, data: &[u8]) {
+        assert_eq!(
+            self.value.len(),
+            data.len(),
+            "The data does not have the size of the uniforms"
+        );
+        self.value.copy_from_slice(data);
+    }
+
+    /// Copies the current value for a draw, and returns the offset of the copy.
+    pub(super) fn stage(&mut self) -> u32 {
+        let offset = self.staged.len();
+        self.staged.extend_from_slice(&self.value);
+        self.staged.resize(offset + self.stride() as usize, 0);
+        offset as u32
+    }
+
+    pub(super) fn staged(&self) -> &[u8] {
+        &self.staged
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.staged.clear();
+    }
+}
+
+/// The uniforms specific to a [CustomPipeline], bound with the offset of the value of each draw.
+pub(super) struct CustomUniforms {
+    pub(super) staging: StagedUniforms,
+    pub(super) layout: wgpu::BindGroupLayout,
+    buffer: wgpu::Buffer,
+    buffer_size: u64,
+    pub(super) bind_group: wgpu::BindGroup,
+}
+
+impl CustomUniforms {
+    pub(super) fn new(device: &wgpu::Device, size: u64) -> CustomUniforms {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::VERTEX | wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(size),
+                },
+                count: None,
+            }],
+            label: Some("custom_uniform_bind_group_layout"),
+        });
+        let staging = StagedUniforms::new(size);
+        let buffer_size = staging.stride() * 16;
+        let (buffer, bind_group) = create_custom_uniform_buffer(device, &layout, size, buffer_size);
+        CustomUniforms {
+            staging,
+            layout,
+            buffer,
+            buffer_size,
+            bind_group,
+        }
+    }
+
+    /// Uploads the values of the draws of the frame, growing the buffer if needed.
+    pub(super) fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let staged = self.staging.staged();
+        if staged.is_empty() {
+            return;
+        }
+        if staged.len() as u64 > self.buffer_size {
+            self.buffer_size = (staged.len() as u64).next_power_of_two();
+            let (buffer, bind_group) = create_custom_uniform_buffer(
+                device,
+                &self.layout,
+                self.value_size(),
+                self.buffer_size,
+            );
+            self.buffer = buffer;
+            self.bind_group = bind_group;
+        }
+        queue.write_buffer(&self.buffer, 0, staged);
+    }
+
+    fn value_size(&self) -> u64 {
+        self.staging.value.len() as u64
+    }
+}
+
+/// Creates the uniform buffer of a [CustomUniforms], with the bind group binding one value of it.
+fn create_custom_uniform_buffer(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    value_size: u64,
+    buffer_size: u64,
+) -> (wgpu::Buffer, wgpu::BindGroup) {
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Custom Uniform Buffer"),
+        size: buffer_size,
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: &buffer,
+                offset: 0,
+                size: wgpu::BufferSize::new(value_size),
+            }),
+        }],
+        label: Some("custom_uniform_bind_group"),
+    });
+    (buffer, bind_group)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_vertices() {
+        let mut buffer = Vec::new();
+        assert_eq!(push_vertices(&mut buffer, &[1, 2, 3]), 0..3);
+        assert_eq!(buffer, vec![1, 2, 3, 0]);
+        assert_eq!(push_vertices(&mut buffer, &[4; 8]), 4..12);
+        assert_eq!(buffer.len(), 12);
+    }
+
+    #[test]
+    fn test_staged_uniforms() {
+        let mut uniforms = StagedUniforms::new(8);
+        assert_eq!(uniforms.stride(), wgpu::BIND_BUFFER_ALIGNMENT);
+
+        // Each draw keeps the value set before it
+        assert_eq!(uniforms.stage(), 0);
+        uniforms.set(&[1; 8]);
+        assert_eq!(uniforms.stage(), 256);
+        uniforms.set(&[2; 8]);
+        assert_eq!(uniforms.stage(), 512);
+        let staged = uniforms.staged();
+        assert_eq!(staged.len(), 768);
+        assert_eq!(&staged[0..8], &[0; 8]);
+        assert_eq!(&staged[256..264], &[1; 8]);
+        assert_eq!(&staged[512..520], &[2; 8]);
+
+        // The value is kept across frames
+        uniforms.clear();
+        assert_eq!(uniforms.stage(), 0);
+        assert_eq!(&uniforms.staged()[0..8], &[2; 8]);
+    }
+}
@@ -0,0 +1,181 @@
+use super::screen_coords_to_wgpu;
+use crate::rect::Rect;
+use std::mem::size_of;
+
+/// A soft shadow cast by a rectangle, similar to the CSS `box-shadow` property.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Shadow {
+    /// Offset of the shadow relative to the rectangle, in pixels.
+    pub offset: (f32, f32),
+    /// The larger this value, the bigger and lighter the shadow becomes.
+    /// Zero makes the shadow sharp.
+    pub blur_radius: f32,
+    /// Grows (positive values) or shrinks (negative values) the shadow before it is blurred.
+    pub spread: f32,
+    pub color: [f32; 4],
+}
+
+impl Shadow {
+    /// The standard deviation of the gaussian blur, following the CSS convention.
+    pub(super) fn sigma(&self) -> f32 {
+        (self.blur_radius / 2.0).max(0.01)
+    }
+
+    /// The rectangle whose shadow is computed, once offset and spread.
+    pub(super) fn shadowed_rect(&self, rect: Rect) -> Rect {
+        Rect {
+            min: (
+                rect.min.0 + self.offset.0 - self.spread,
+                rect.min.1 + self.offset.1 - self.spread,
+            ),
+            max: (
+                rect.max.0 + self.offset.0 + self.spread,
+                rect.max.1 + self.offset.1 + self.spread,
+            ),
+        }
+    }
+
+    /// The area that has to be painted so that the shadow is not cut.
+    pub(super) fn painted_rect(&self, rect: Rect) -> Rect {
+        let rect = self.shadowed_rect(rect);
+        let margin = 3.0 * self.sigma();
+        Rect {
+            min: (rect.min.0 - margin, rect.min.1 - margin),
+            max: (rect.max.0 + margin, rect.max.1 + margin),
+        }
+    }
+}
+
+/// Vertex of the blur pipeline.
+/// The direction is the size of a texel along the blurred axis.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(super) struct BlurVertex {
+    position: [f32; 2],
+    tex_coords: [f32; 2],
+    direction: [f32; 2],
+    radius: f32,
+}
+
+impl BlurVertex {
+    pub(super) fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: size_of::<BlurVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: size_of::<[f32; 6]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32,
+                },
+            ],
+        }
+    }
+
+    /// Generates the two quads of a backdrop blur: the horizontal pass followed by the vertical pass.
+    /// The horizontal pass covers more than `rect` so that the vertical pass has everything it needs.
+    pub(super) fn backdrop_blur(
+        rect: Rect,
+        radius: f32,
+        screen_size: (u32, u32),
+        vertices: &mut Vec<BlurVertex>,
+    ) {
+        let texel = (1.0 / screen_size.0 as f32, 1.0 / screen_size.1 as f32);
+        let extended = Rect {
+            min: (rect.min.0, rect.min.1 - radius.ceil()),
+            max: (rect.max.0, rect.max.1 + radius.ceil()),
+        };
+
+        for (quad, direction) in [(extended, [texel.0, 0.0]), (rect, [0.0, texel.1])].iter() {
+            let min = screen_coords_to_wgpu(quad.min, screen_size);
+            let max = screen_coords_to_wgpu(quad.max, screen_size);
+            let tex_min = (quad.min.0 * texel.0, quad.min.1 * texel.1);
+            let tex_max = (quad.max.0 * texel.0, quad.max.1 * texel.1);
+
+            for (x, y) in [(1, 0), (0, 1), (0, 0), (1, 0), (0, 1), (1, 1)].iter() {
+                vertices.push(BlurVertex {
+                    position: [
+                        if *x == 0 { min.0 } else { max.0 },
+                        if *y == 0 { min.1 } else { max.1 },
+                    ],
+                    tex_coords: [
+                        if *x == 0 { tex_min.0 } else { tex_max.0 },
+                        if *y == 0 { tex_min.1 } else { tex_max.1 },
+                    ],
+                    direction: *direction,
+                    radius,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shadow_rects() {
+        let shadow = Shadow {
+            offset: (5.0, 10.0),
+            blur_radius: 4.0,
+            spread: 1.0,
+            color: [0.0, 0.0, 0.0, 0.5],
+        };
+        let rect = Rect::sized(100.0, 100.0, 50.0, 20.0);
+        assert_eq!(shadow.sigma(), 2.0);
+        assert_eq!(
+            shadow.shadowed_rect(rect),
+            Rect {
+                min: (104.0, 109.0),
+                max: (156.0, 131.0)
+            }
+        );
+        assert_eq!(
+            shadow.painted_rect(rect),
+            Rect {
+                min: (98.0, 103.0),
+                max: (162.0, 137.0)
+            }
+        );
+    }
+
+    #[test]
+    fn test_backdrop_blur_quads() {
+        let mut vertices = Vec::new();
+        BlurVertex::backdrop_blur(
+            Rect::sized(0.0, 32.0, 64.0, 64.0),
+            15.5,
+            (128, 128),
+            &mut vertices,
+        );
+        assert_eq!(vertices.len(), 12);
+
+        // Horizontal pass, extended vertically
+        assert_eq!(vertices[2].position, [-1.0, 0.75]);
+        assert_eq!(vertices[2].tex_coords, [0.0, 0.125]);
+        assert_eq!(vertices[2].direction, [1.0 / 128.0, 0.0]);
+        assert_eq!(vertices[5].tex_coords, [0.5, 0.875]);
+
+        // Vertical pass
+        assert_eq!(vertices[8].position, [-1.0, 0.5]);
+        assert_eq!(vertices[11].tex_coords, [0.5, 0.75]);
+        assert_eq!(vertices[11].direction, [0.0, 1.0 / 128.0]);
+        assert_eq!(vertices[11].radius, 15.5);
+    }
+}
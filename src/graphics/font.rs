@@ -0,0 +1,470 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// The slant of a font.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FontStyle {
+    #[default]
+    Normal,
+    Italic,
+    Oblique,
+}
+
+/// The family, weight and style of a font, used to find it with [Fonts::query](super::Fonts::query).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FontDescription {
+    pub family: String,
+    /// From `100` (thin) to `900` (black), `400` being regular and `700` bold.
+    pub weight: u16,
+    pub style: FontStyle,
+}
+
+impl Default for FontDescription {
+    fn default() -> Self {
+        FontDescription {
+            family: String::new(),
+            weight: 400,
+            style: FontStyle::Normal,
+        }
+    }
+}
+
+impl FontDescription {
+    /// Reads the description of a font from its `name` and `OS/2` tables.
+    pub(super) fn parse(data: &[u8], index: u32) -> Result<FontDescription, FontError> {
+        let face = ttf_parser::Face::parse(data, index).map_err(|_| FontError::Invalid)?;
+        Ok(FontDescription::from_tables(
+            face.tables().name,
+            face.tables().os2,
+        ))
+    }
+
+    /// Reads the descriptions of the fonts of a file with their index, reading only the table directories and the `name` and `OS/2` tables.
+    /// Returns nothing if the file is not a font.
+    fn read_all(file: &mut File) -> Vec<(u32, FontDescription)> {
+        let length = match file.metadata() {
+            Ok(metadata) => metadata.len(),
+            Err(_) => return Vec::new(),
+        };
+        let mut read_at = |offset: u64, size: u64| {
+            if offset.checked_add(size)? > length {
+                return None;
+            }
+            let mut data = vec![0; size as usize];
+            file.seek(SeekFrom::Start(offset)).ok()?;
+            file.read_exact(&mut data).ok()?;
+            Some(data)
+        };
+
+        // Collections start with the offsets of their fonts
+        let header = match read_at(0, 12) {
+            Some(header) => header,
+            None => return Vec::new(),
+        };
+        let offsets = match &header[..4] {
+            b"ttcf" => {
+                let count = u32_at(&header, 8) as u64;
+                match read_at(12, 4 * count) {
+                    Some(offsets) => offsets.chunks(4).map(|offset| u32_at(offset, 0)).collect(),
+                    None => return Vec::new(),
+                }
+            }
+            _ => vec![0],
+        };
+
+        let mut descriptions = Vec::new();
+        for (index, offset) in offsets.into_iter().enumerate() {
+            let offset = offset as u64;
+            let header = match read_at(offset, 12) {
+                Some(header) => header,
+                None => continue,
+            };
+            if !matches!(&header[..4], [0, 1, 0, 0] | b"OTTO" | b"true") {
+                continue;
+            }
+            let table_count = u16::from_be_bytes([header[4], header[5]]) as u64;
+            let records = match read_at(offset + 12, 16 * table_count) {
+                Some(records) => records,
+                None => continue,
+            };
+            let mut read_table = |tag: &[u8]| {
+                let record = records.chunks(16).find(|record| &record[..4] == tag)?;
+                read_at(u32_at(record, 8) as u64, u32_at(record, 12) as u64)
+            };
+            let name = read_table(b"name");
+            let os2 = read_table(b"OS/2");
+            let description = FontDescription::from_tables(
+                name.as_deref().and_then(ttf_parser::name::Table::parse),
+                os2.as_deref().and_then(ttf_parser::os2::Table::parse),
+            );
+            descriptions.push((index as u32, description));
+        }
+        descriptions
+    }
+
+    /// The description given by the `name` and `OS/2` tables of a font, with default values for missing tables.
+    fn from_tables(
+        name: Option<ttf_parser::name::Table>,
+        os2: Option<ttf_parser::os2::Table>,
+    ) -> FontDescription {
+        let names: Vec<_> = name
+            .map(|name| name.names.into_iter().collect())
+            .unwrap_or_default();
+        // The typographic family groups all weights, while the legacy one only goes up to four styles
+        let family = [
+            ttf_parser::name_id::TYPOGRAPHIC_FAMILY,
+            ttf_parser::name_id::FAMILY,
+        ]
+        .iter()
+        .find_map(|id| {
+            names
+                .iter()
+                .filter(|name| name.name_id == *id && name.is_unicode())
+                .find_map(|name| name.to_string())
+        })
+        .unwrap_or_default();
+
+        FontDescription {
+            family,
+            weight: os2.map(|os2| os2.weight()).unwrap_or_default().to_number(),
+            style: match os2.map(|os2| os2.style()).unwrap_or_default() {
+                ttf_parser::Style::Normal => FontStyle::Normal,
+                ttf_parser::Style::Italic => FontStyle::Italic,
+                ttf_parser::Style::Oblique => FontStyle::Oblique,
+            },
+        }
+    }
+
+    /// How far a font is from the requested weight and style, lower being better.
+    /// A different style matters more than any weight difference, but italic and oblique can replace each other.
+    pub(super) fn distance(&self, weight: u16, style: FontStyle) -> u32 {
+        let style_distance = match (self.style, style) {
+            (a, b) if a == b => 0,
+            (FontStyle::Normal, _) | (_, FontStyle::Normal) => 2,
+            _ => 1,
+        };
+        style_distance * 1000 + (self.weight as i32 - weight as i32).unsigned_abs()
+    }
+}
+
+/// Reads a big endian number.
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+/// A font installed on the system, see [Fonts::system_fonts](super::Fonts::system_fonts).
+#[derive(Debug, Clone, PartialEq)]
+pub struct SystemFont {
+    pub path: PathBuf,
+    /// The index of the font in its file, which can be a collection of fonts.
+    pub index: u32,
+    pub description: FontDescription,
+}
+
+/// The directories where fonts are installed, depending on the platform.
+fn system_font_directories() -> Vec<PathBuf> {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let mut directories = Vec::new();
+    if cfg!(target_os = "windows") {
+        let windows = std::env::var_os("WINDIR").unwrap_or_else(|| "C:\\Windows".into());
+        directories.push(PathBuf::from(windows).join("Fonts"));
+    } else if cfg!(target_os = "macos") {
+        directories.push(PathBuf::from("/System/Library/Fonts"));
+        directories.push(PathBuf::from("/Library/Fonts"));
+        directories.extend(home.map(|home| home.join("Library/Fonts")));
+    } else {
+        // Directories of the XDG Base Directory Specification, also used by fontconfig
+        let data_home = std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home.as_ref().map(|home| home.join(".local/share")));
+        directories.extend(data_home.map(|data_home| data_home.join("fonts")));
+        directories.extend(home.map(|home| home.join(".fonts")));
+        let data_dirs = std::env::var("XDG_DATA_DIRS")
+            .unwrap_or_else(|_| String::from("/usr/local/share:/usr/share"));
+        for data_dir in data_dirs.split(':').filter(|dir| !dir.is_empty()) {
+            directories.push(PathBuf::from(data_dir).join("fonts"));
+        }
+    }
+    directories
+}
+
+/// Where the fonts found by the last scan are stored, so that the next run doesn't read unchanged files again.
+fn cache_path() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let directory = if cfg!(target_os = "windows") {
+        std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home.map(|home| home.join("Library/Caches"))
+    } else {
+        std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home.map(|home| home.join(".cache")))
+    };
+    directory.map(|directory| directory.join("unigui").join("fonts.tsv"))
+}
+
+/// Finds the fonts installed on the system.
+pub(super) fn scan_system_fonts() -> Vec<SystemFont> {
+    scan_directories(system_font_directories(), cache_path().as_deref())
+}
+
+/// The fonts of scanned files by path, with the modification time in nanoseconds and the size of the file they were read from.
+type ScanCache = HashMap<PathBuf, ((u128, u64), Vec<(u32, FontDescription)>)>;
+
+/// Finds the fonts in directories and their subdirectories.
+/// Files that can't be read or parsed are ignored.
+/// Files that have not changed since the scan stored in the cache file are not read again, and the cache is updated.
+fn scan_directories(directories: Vec<PathBuf>, cache_path: Option<&Path>) -> Vec<SystemFont> {
+    let cache = cache_path.map(read_cache).unwrap_or_default();
+    let mut scanned = ScanCache::new();
+    let mut fonts = Vec::new();
+    let mut visited = HashSet::new();
+    let mut directories = directories;
+    while let Some(directory) = directories.pop() {
+        // Directories can be listed several times or linked to each other
+        let directory = match directory.canonicalize() {
+            Ok(directory) => directory,
+            Err(_) => continue,
+        };
+        if !visited.insert(directory.clone()) {
+            continue;
+        }
+        let entries = match std::fs::read_dir(&directory) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.is_dir() {
+                directories.push(path);
+                continue;
+            }
+            let extension = path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .map(|extension| extension.to_ascii_lowercase());
+            if !matches!(extension.as_deref(), Some("ttf" | "otf" | "ttc" | "otc")) {
+                continue;
+            }
+            let stamp = match path.metadata() {
+                Ok(metadata) => match metadata
+                    .modified()
+                    .map(|time| time.duration_since(UNIX_EPOCH))
+                {
+                    Ok(Ok(modified)) => (modified.as_nanos(), metadata.len()),
+                    _ => continue,
+                },
+                Err(_) => continue,
+            };
+            let descriptions = match cache.get(&path) {
+                Some((cached_stamp, descriptions)) if *cached_stamp == stamp => {
+                    descriptions.clone()
+                }
+                _ => match File::open(&path) {
+                    Ok(mut file) => FontDescription::read_all(&mut file),
+                    Err(_) => continue,
+                },
+            };
+            for (index, description) in &descriptions {
+                fonts.push(SystemFont {
+                    path: path.clone(),
+                    index: *index,
+                    description: description.clone(),
+                });
+            }
+            scanned.insert(path, (stamp, descriptions));
+        }
+    }
+    if let Some(cache_path) = cache_path {
+        if scanned != cache {
+            write_cache(cache_path, &scanned);
+        }
+    }
+    fonts.sort_by(|a, b| a.path.cmp(&b.path).then(a.index.cmp(&b.index)));
+    fonts
+}
+
+/// Reads the cache written by [write_cache], which has a line per font, or per file without fonts:
+/// the path, the modification time, the size, and the index, weight, style and family of the font.
+/// Invalid lines are ignored.
+fn read_cache(path: &Path) -> ScanCache {
+    let mut cache = ScanCache::new();
+    let text = std::fs::read_to_string(path).unwrap_or_default();
+    for line in text.lines() {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let (file, modified, size) = match fields[..] {
+            [file, modified, size, ..] => match (modified.parse(), size.parse()) {
+                (Ok(modified), Ok(size)) => (PathBuf::from(file), modified, size),
+                _ => continue,
+            },
+            _ => continue,
+        };
+        let entry = cache
+            .entry(file)
+            .or_insert_with(|| ((modified, size), Vec::new()));
+        if let [_, _, _, index, weight, style, family] = fields[..] {
+            let style = match style {
+                "normal" => FontStyle::Normal,
+                "italic" => FontStyle::Italic,
+                "oblique" => FontStyle::Oblique,
+                _ => continue,
+            };
+            if let (Ok(index), Ok(weight)) = (index.parse(), weight.parse()) {
+                let description = FontDescription {
+                    family: family.to_string(),
+                    weight,
+                    style,
+                };
+                entry.1.push((index, description));
+            }
+        }
+    }
+    cache
+}
+
+/// Stores the fonts found by a scan, see [read_cache].
+/// Files whose path or family can't be stored on a line are left out, and errors are ignored since the cache is only an optimization.
+fn write_cache(path: &Path, cache: &ScanCache) {
+    let mut text = String::new();
+    for (file, ((modified, size), descriptions)) in cache {
+        let file = match file.to_str() {
+            Some(file) if !file.contains(['\t', '\n', '\r']) => file,
+            _ => continue,
+        };
+        if descriptions
+            .iter()
+            .any(|(_, description)| description.family.contains(['\t', '\n', '\r']))
+        {
+            continue;
+        }
+        let stamp = format!("{}\t{}\t{}", file, modified, size);
+        if descriptions.is_empty() {
+            text.push_str(&format!("{}\n", stamp));
+        }
+        for (index, description) in descriptions {
+            let style = match description.style {
+                FontStyle::Normal => "normal",
+                FontStyle::Italic => "italic",
+                FontStyle::Oblique => "oblique",
+            };
+            text.push_str(&format!(
+                "{}\t{}\t{}\t{}\t{}\n",
+                stamp, index, description.weight, style, description.family
+            ));
+        }
+    }
+    if let Some(directory) = path.parent() {
+        let _ = std::fs::create_dir_all(directory);
+    }
+    let _ = std::fs::write(path, text);
+}
+
+/// An error that occured while loading a font.
+#[derive(Debug)]
+pub enum FontError {
+    Io(std::io::Error),
+    /// The data is not a supported font (TTF or OTF).
+    Invalid,
+    /// No installed font has the requested family.
+    NotFound,
+}
+
+impl std::fmt::Display for FontError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FontError::Io(error) => write!(f, "Failed to read font: {}", error),
+            FontError::Invalid => write!(f, "Invalid font"),
+            FontError::NotFound => write!(f, "Font not found"),
+        }
+    }
+}
+
+impl std::error::Error for FontError {}
+
+impl From<std::io::Error> for FontError {
+    fn from(error: std::io::Error) -> Self {
+        FontError::Io(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_description() {
+        let data = include_bytes!("ressources/Inconsolata-Regular.ttf");
+        let description = FontDescription::parse(data, 0).unwrap();
+        assert_eq!(description.family, "Inconsolata");
+        assert_eq!(description.weight, 400);
+        assert_eq!(description.style, FontStyle::Normal);
+
+        assert!(FontDescription::parse(b"not a font", 0).is_err());
+    }
+
+    #[test]
+    fn test_distance() {
+        let bold = FontDescription {
+            family: String::from("Test"),
+            weight: 700,
+            style: FontStyle::Normal,
+        };
+        assert_eq!(bold.distance(700, FontStyle::Normal), 0);
+        assert!(bold.distance(600, FontStyle::Normal) < bold.distance(400, FontStyle::Normal));
+        assert!(bold.distance(700, FontStyle::Oblique) > bold.distance(100, FontStyle::Normal));
+
+        let italic = FontDescription {
+            style: FontStyle::Italic,
+            ..bold
+        };
+        assert!(italic.distance(700, FontStyle::Oblique) < italic.distance(700, FontStyle::Normal));
+    }
+
+    #[test]
+    fn test_scan_directories() {
+        // Tests of other processes may run at the same time
+        let directory = std::env::temp_dir().join(format!(
+            "unigui-test-scan-directories-{}-{}",
+            std::process::id(),
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        let subdirectory = directory.join("truetype");
+        std::fs::create_dir_all(&subdirectory).unwrap();
+        let data = include_bytes!("ressources/Inconsolata-Regular.ttf");
+        std::fs::write(subdirectory.join("Inconsolata.TTF"), data).unwrap();
+        std::fs::write(directory.join("not-a-font.ttf"), b"not a font").unwrap();
+        std::fs::write(directory.join("readme.txt"), b"readme").unwrap();
+
+        let cache = directory.join("cache").join("fonts.tsv");
+        let directories = vec![directory.clone(), subdirectory.clone()];
+        let fonts = scan_directories(directories.clone(), Some(&cache));
+        let path = subdirectory.canonicalize().unwrap().join("Inconsolata.TTF");
+        assert_eq!(fonts.len(), 1);
+        assert_eq!(fonts[0].path, path);
+        assert_eq!(fonts[0].index, 0);
+        assert_eq!(
+            fonts[0].description,
+            FontDescription::parse(data, 0).unwrap()
+        );
+
+        // Unchanged files are described by the cache
+        let text = std::fs::read_to_string(&cache).unwrap();
+        assert_eq!(text.lines().count(), 2);
+        std::fs::write(&cache, text.replace("Inconsolata\n", "Cached\n")).unwrap();
+        let fonts = scan_directories(directories, Some(&cache));
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(fonts.len(), 1);
+        assert_eq!(fonts[0].description.family, "Cached");
+    }
+}
@@ -0,0 +1,477 @@
+use super::screen_coords_to_wgpu;
+use super::texture::{TextureFormat, TextureId};
+use super::transform::Transform;
+use crate::rect::Rect;
+use std::mem::size_of;
+
+/// Options of [WgpuBackend::add_image_with_options](super::WgpuBackend::add_image_with_options).
+/// The default options draw the whole image, unchanged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageOptions {
+    /// The part of the image to draw, in pixels of the image (useful for sprite sheets).
+    /// [None] draws the whole image.
+    pub source: Option<Rect>,
+    /// A color multiplied with the color of each pixel, in sRGB (see [Color](super::Color)).
+    pub tint: [f32; 4],
+    /// From `0.0` (invisible) to `1.0` (opaque).
+    pub opacity: f32,
+    /// Clockwise rotation, in radians.
+    pub rotation: f32,
+    /// The point the image rotates around, relative to the destination rectangle.
+    /// `(0.0, 0.0)` is the top left corner and `(1.0, 1.0)` the bottom right one.
+    pub pivot: (f32, f32),
+    /// Mirrors the image horizontally.
+    pub flip_x: bool,
+    /// Mirrors the image vertically.
+    pub flip_y: bool,
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        ImageOptions {
+            source: None,
+            tint: [1.0, 1.0, 1.0, 1.0],
+            opacity: 1.0,
+            rotation: 0.0,
+            pivot: (0.5, 0.5),
+            flip_x: false,
+            flip_y: false,
+        }
+    }
+}
+
+/// Defines how the edges and the center of a [NineSlice] image fill their space.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum EdgeMode {
+    /// The edges are stretched. (default)
+    #[default]
+    Stretch,
+    /// The edges are repeated, keeping the size they have in the image.
+    /// The last repetition is cut if needed.
+    /// Repetitions are stretched when more than [MAX_TILES] would be needed.
+    Tile,
+}
+
+/// The maximum number of repetitions of an edge or of the center of a [NineSlice] along an axis, see [EdgeMode::Tile].
+pub const MAX_TILES: usize = 64;
+
+/// Describes how an image is cut to be drawn with [WgpuBackend::add_nine_slice](super::WgpuBackend::add_nine_slice).
+///
+/// The insets (in pixels of the image) cut the image in nine parts.
+/// Corners keep their size, horizontal edges only grow horizontally, vertical edges only grow vertically, and the center grows in both directions.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct NineSlice {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+    /// Applies to the edges and to the center.
+    pub edges: EdgeMode,
+}
+
+impl NineSlice {
+    /// Creates a nine slice with the same inset on every side.
+    pub fn uniform(inset: f32) -> NineSlice {
+        NineSlice {
+            left: inset,
+            top: inset,
+            right: inset,
+            bottom: inset,
+            edges: EdgeMode::default(),
+        }
+    }
+
+    pub fn with_edges(mut self, edges: EdgeMode) -> NineSlice {
+        self.edges = edges;
+        self
+    }
+
+    /// Splits one axis in the parts to draw, as `(destination start, destination length, source start, source length)`.
+    /// `start` and `end` are the insets of this axis.
+    fn segments(
+        &self,
+        (destination, destination_length): (f32, f32),
+        source_length: f32,
+        (start, end): (f32, f32),
+    ) -> Vec<(f32, f32, f32, f32)> {
+        // Corners are shrunk if the destination is too small for them
+        let scale = (destination_length / (start + end)).min(1.0);
+        let middle_length = destination_length - (start + end) * scale;
+        let middle_source_length = source_length - start - end;
+
+        let mut segments = vec![(destination, start * scale, 0.0, start)];
+        match self.edges {
+            EdgeMode::Stretch => segments.push((
+                destination + start * scale,
+                middle_length,
+                start,
+                middle_source_length,
+            )),
+            EdgeMode::Tile if middle_source_length > 0.0 => {
+                // Each tile is a quad, so small tiles are stretched to bound their count
+                let tile_length = middle_source_length.max(middle_length / MAX_TILES as f32);
+                let source_scale = middle_source_length / tile_length;
+                let mut offset = 0.0;
+                while offset < middle_length {
+                    let length = tile_length.min(middle_length - offset);
+                    segments.push((
+                        destination + start * scale + offset,
+                        length,
+                        start,
+                        length * source_scale,
+                    ));
+                    offset += length;
+                }
+            }
+            EdgeMode::Tile => (),
+        }
+        segments.push((
+            destination + destination_length - end * scale,
+            end * scale,
+            source_length - end,
+            end,
+        ));
+
+        segments.retain(|(_, destination_length, _, source_length)| {
+            *destination_length > 0.0 && *source_length > 0.0
+        });
+        segments
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub(super) struct TextureVertex {
+    position: [f32; 2],
+    tex_coords: [f32; 2],
+    color: [f32; 4],
+    /// How the texture must be read.
+    /// 0 for regular textures, 1 for single channel textures used as opacity, 2 for textures with premultiplied colors.
+    kind: u32,
+}
+
+impl TextureVertex {
+    /// Pushes the two triangles of a textured rectangle, already in wgpu coordinates.
+    pub(super) fn quad(position: Rect, tex_coords: Rect, vertices: &mut Vec<TextureVertex>) {
+        let corners = [
+            (position.min.0, position.min.1),
+            (position.max.0, position.min.1),
+            (position.min.0, position.max.1),
+            (position.max.0, position.max.1),
+        ];
+        vertices.extend_from_slice(&TextureVertex::triangles(
+            corners,
+            tex_coords,
+            [1.0, 1.0, 1.0, 1.0],
+            0,
+        ));
+    }
+
+    /// Computes the two triangles of an image drawn with [ImageOptions], and then transformed.
+    pub(super) fn image(
+        position: Rect,
+        texture_id: &TextureId,
+        options: &ImageOptions,
+        transform: &Transform,
+        screen_size: (u32, u32),
+    ) -> [TextureVertex; 6] {
+        // Find the region of the GPU texture to sample
+        let region = texture_id.tex_coords;
+        let mut tex_coords = match options.source {
+            Some(source) => {
+                let size = texture_id.size();
+                let scale = (
+                    region.width() / size.0.max(1) as f32,
+                    region.height() / size.1.max(1) as f32,
+                );
+                Rect {
+                    min: (
+                        region.min.0 + source.min.0 * scale.0,
+                        region.min.1 + source.min.1 * scale.1,
+                    ),
+                    max: (
+                        region.min.0 + source.max.0 * scale.0,
+                        region.min.1 + source.max.1 * scale.1,
+                    ),
+                }
+            }
+            None => region,
+        };
+        if options.flip_x {
+            std::mem::swap(&mut tex_coords.min.0, &mut tex_coords.max.0);
+        }
+        if options.flip_y {
+            std::mem::swap(&mut tex_coords.min.1, &mut tex_coords.max.1);
+        }
+
+        // Rotate the corners in pixels, so that the aspect ratio of the screen does not distort the image
+        let pivot = (
+            position.min.0 + options.pivot.0 * position.width(),
+            position.min.1 + options.pivot.1 * position.height(),
+        );
+        let transform = Transform::rotation(options.rotation)
+            .around(pivot)
+            .then(*transform);
+        let mut corners = transform.apply_rect(position);
+        for corner in &mut corners {
+            *corner = screen_coords_to_wgpu(*corner, screen_size);
+        }
+
+        let mut color = options.tint;
+        color[3] *= options.opacity.clamp(0.0, 1.0);
+
+        let kind = if texture_id.format() == TextureFormat::R8 {
+            1
+        } else if texture_id.premultiplied {
+            2
+        } else {
+            0
+        };
+        TextureVertex::triangles(corners, tex_coords, color, kind)
+    }
+
+    /// Computes the quads of an image drawn with [NineSlice].
+    pub(super) fn nine_slice(
+        position: Rect,
+        texture_id: &TextureId,
+        nine_slice: &NineSlice,
+        transform: &Transform,
+        screen_size: (u32, u32),
+    ) -> Vec<[TextureVertex; 6]> {
+        let size = texture_id.size();
+        let columns = nine_slice.segments(
+            (position.min.0, position.width()),
+            size.0 as f32,
+            (nine_slice.left, nine_slice.right),
+        );
+        let rows = nine_slice.segments(
+            (position.min.1, position.height()),
+            size.1 as f32,
+            (nine_slice.top, nine_slice.bottom),
+        );
+
+        let mut quads = Vec::with_capacity(columns.len() * rows.len());
+        for (y, height, source_y, source_height) in &rows {
+            for (x, width, source_x, source_width) in &columns {
+                let options = ImageOptions {
+                    source: Some(Rect::sized(
+                        *source_x,
+                        *source_y,
+                        *source_width,
+                        *source_height,
+                    )),
+                    ..ImageOptions::default()
+                };
+                quads.push(TextureVertex::image(
+                    Rect::sized(*x, *y, *width, *height),
+                    texture_id,
+                    &options,
+                    transform,
+                    screen_size,
+                ));
+            }
+        }
+        quads
+    }
+
+    /// Builds two triangles from corners ordered as top left, top right, bottom left, bottom right.
+    fn triangles(
+        corners: [(f32, f32); 4],
+        tex_coords: Rect,
+        color: [f32; 4],
+        kind: u32,
+    ) -> [TextureVertex; 6] {
+        let uvs = [
+            [tex_coords.min.0, tex_coords.min.1],
+            [tex_coords.max.0, tex_coords.min.1],
+            [tex_coords.min.0, tex_coords.max.1],
+            [tex_coords.max.0, tex_coords.max.1],
+        ];
+        let vertex = |corner: usize| TextureVertex {
+            position: [corners[corner].0, corners[corner].1],
+            tex_coords: uvs[corner],
+            color,
+            kind,
+        };
+        [
+            vertex(1),
+            vertex(2),
+            vertex(0),
+            vertex(1),
+            vertex(2),
+            vertex(3),
+        ]
+    }
+
+    pub(super) fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: size_of::<TextureVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: size_of::<[f32; 8]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_image() {
+        let texture_id = TextureId::new(0, (64, 32), TextureFormat::Rgba8Srgb);
+        let vertices = TextureVertex::image(
+            Rect::sized(0.0, 0.0, 64.0, 64.0),
+            &texture_id,
+            &ImageOptions::default(),
+            &Transform::IDENTITY,
+            (128, 128),
+        );
+        // Top left corner
+        assert_eq!(vertices[2].position, [-1.0, 1.0]);
+        assert_eq!(vertices[2].tex_coords, [0.0, 0.0]);
+        // Bottom right corner
+        assert_eq!(vertices[5].position, [0.0, 0.0]);
+        assert_eq!(vertices[5].tex_coords, [1.0, 1.0]);
+        assert_eq!(vertices[5].color, [1.0; 4]);
+        assert_eq!(vertices[5].kind, 0);
+    }
+
+    #[test]
+    fn test_image_options() {
+        let texture_id = TextureId::new(0, (64, 32), TextureFormat::Rgba8Srgb);
+        let options = ImageOptions {
+            source: Some(Rect::sized(16.0, 0.0, 16.0, 16.0)),
+            tint: [1.0, 0.0, 0.0, 1.0],
+            opacity: 0.5,
+            flip_x: true,
+            ..ImageOptions::default()
+        };
+        let vertices = TextureVertex::image(
+            Rect::sized(0.0, 0.0, 64.0, 64.0),
+            &texture_id,
+            &options,
+            &Transform::IDENTITY,
+            (128, 128),
+        );
+        assert_eq!(vertices[2].tex_coords, [0.5, 0.0]);
+        assert_eq!(vertices[5].tex_coords, [0.25, 0.5]);
+        assert_eq!(vertices[5].color, [1.0, 0.0, 0.0, 0.5]);
+    }
+
+    #[test]
+    fn test_premultiplied_image() {
+        let mut texture_id = TextureId::new(0, (8, 8), TextureFormat::Bgra8Srgb);
+        texture_id.premultiplied = true;
+        let vertices = TextureVertex::image(
+            Rect::sized(0.0, 0.0, 8.0, 8.0),
+            &texture_id,
+            &ImageOptions::default(),
+            &Transform::IDENTITY,
+            (128, 128),
+        );
+        assert_eq!(vertices[0].kind, 2);
+    }
+
+    #[test]
+    fn test_nine_slice_stretch() {
+        let texture_id = TextureId::new(0, (32, 32), TextureFormat::Rgba8Srgb);
+        let quads = TextureVertex::nine_slice(
+            Rect::sized(0.0, 0.0, 128.0, 64.0),
+            &texture_id,
+            &NineSlice::uniform(8.0),
+            &Transform::IDENTITY,
+            (128, 128),
+        );
+        assert_eq!(quads.len(), 9);
+
+        // The top left corner keeps its size
+        assert_eq!(quads[0][5].position, [-0.875, 0.875]);
+        assert_eq!(quads[0][5].tex_coords, [0.25, 0.25]);
+        // The center is stretched
+        assert_eq!(quads[4][2].position, [-0.875, 0.875]);
+        assert_eq!(quads[4][5].position, [0.875, 0.125]);
+        assert_eq!(quads[4][5].tex_coords, [0.75, 0.75]);
+    }
+
+    #[test]
+    fn test_nine_slice_segments() {
+        let nine_slice = NineSlice::uniform(8.0).with_edges(EdgeMode::Tile);
+        assert_eq!(
+            nine_slice.segments((10.0, 56.0), 32.0, (8.0, 8.0)),
+            vec![
+                (10.0, 8.0, 0.0, 8.0),
+                (18.0, 16.0, 8.0, 16.0),
+                (34.0, 16.0, 8.0, 16.0),
+                (50.0, 8.0, 8.0, 8.0),
+                (58.0, 8.0, 24.0, 8.0),
+            ]
+        );
+
+        // Tiny tiles are stretched
+        let segments = nine_slice.segments((0.0, 10016.0), 17.0, (8.0, 8.0));
+        assert_eq!(segments.len(), MAX_TILES + 2);
+        assert_eq!(segments[1], (8.0, 156.25, 8.0, 1.0));
+
+        // Corners shrink when there is not enough space
+        let nine_slice = NineSlice::uniform(8.0);
+        assert_eq!(
+            nine_slice.segments((0.0, 8.0), 32.0, (8.0, 8.0)),
+            vec![(0.0, 4.0, 0.0, 8.0), (4.0, 4.0, 24.0, 8.0)]
+        );
+    }
+
+    #[test]
+    fn test_image_rotation() {
+        let texture_id = TextureId::new(0, (1, 1), TextureFormat::R8);
+        let options = ImageOptions {
+            rotation: std::f32::consts::FRAC_PI_2,
+            pivot: (0.0, 0.0),
+            ..ImageOptions::default()
+        };
+        let vertices = TextureVertex::image(
+            Rect::sized(64.0, 64.0, 32.0, 32.0),
+            &texture_id,
+            &options,
+            &Transform::IDENTITY,
+            (128, 128),
+        );
+        // The top right corner turns clockwise around the top left one, and ends below it
+        let top_right = vertices[0].position;
+        assert_eq!(vertices[0].kind, 1);
+        assert!((top_right[0] - 0.0).abs() < 0.0001);
+        assert!((top_right[1] - -0.5).abs() < 0.0001);
+
+        // The transform applies after the rotation
+        let vertices = TextureVertex::image(
+            Rect::sized(64.0, 64.0, 32.0, 32.0),
+            &texture_id,
+            &options,
+            &Transform::translation(-64.0, 0.0),
+            (128, 128),
+        );
+        let top_right = vertices[0].position;
+        assert!((top_right[0] - -1.0).abs() < 0.0001);
+        assert!((top_right[1] - -0.5).abs() < 0.0001);
+    }
+}
@@ -1,9 +1,49 @@
-use wgpu::util::DeviceExt;
+use glyph_brush::GlyphCruncher;
 use winit::window::Window;
+pub mod blend;
+pub mod color;
+mod color_glyph;
+pub mod custom;
+pub mod effects;
+pub mod font;
+pub mod image;
+pub mod paint;
+pub mod readback;
+pub mod rich_text;
+pub mod sdf;
+pub mod settings;
+mod shaping;
+mod targets;
+pub mod text;
 pub mod texture;
+pub mod transform;
 use crate::prelude::*;
+pub use blend::BlendMode;
+pub use color::Color;
+use color_glyph::{ColorAtlas, ColorFilter};
+use custom::{CustomDraw, CustomPipeline, CustomUniforms};
+pub use custom::{CustomPipelineDescriptor, CustomPipelineId, CustomShader};
+use effects::BlurVertex;
+pub use effects::Shadow;
+pub use font::{FontDescription, FontError, FontStyle, SystemFont};
+use image::TextureVertex;
+pub use image::{EdgeMode, ImageOptions, NineSlice, MAX_TILES};
+pub use paint::{ColorStop, Gradient, Paint, SpreadMode};
+use paint::{GpuColorStop, GpuPaint};
+pub use readback::Screenshot;
+pub use rich_text::{RichText, RichTextLayout, Span};
+use sdf::SdfAtlas;
+pub use sdf::TextEffects;
+pub use settings::{AntiAliasing, Settings, TextGamma};
 use std::mem::size_of;
-pub use texture::TextureId;
+use std::sync::Arc;
+use targets::{OffscreenTarget, RenderTargets};
+pub use text::{Fonts, GlyphMetrics, LineMetrics, TextMetrics, TextOptions};
+use texture::{Atlas, GlyphAtlas};
+pub use texture::{FilterMode, TextureFormat, TextureId, TextureOptions, WrapMode};
+pub(crate) use transform::ClipRegion;
+pub use transform::Transform;
+use transform::MAX_CLIP_PLANES;
 
 #[inline]
 fn screen_coords_to_wgpu((x, y): (f32, f32), screen_size: (u32, u32)) -> (f32, f32) {
@@ -12,45 +52,226 @@ fn screen_coords_to_wgpu((x, y): (f32, f32), screen_size: (u32, u32)) -> (f32, f
     (x, -y)
 }
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct Vertex {
-    pub position: [f32; 2],
-    pub color: [f32; 4],
+fn create_storage_buffer(device: &wgpu::Device, label: &str, size: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size: size as wgpu::BufferAddress,
+        usage: wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::COPY_DST,
+        mapped_at_creation: false,
+    })
 }
 
-impl Vertex {
-    const fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
-        wgpu::VertexBufferLayout {
-            array_stride: size_of::<Vertex>() as wgpu::BufferAddress,
-            step_mode: wgpu::InputStepMode::Vertex,
-            attributes: &[
-                wgpu::VertexAttribute {
+fn create_paint_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    paint_buffer: &wgpu::Buffer,
+    color_stop_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: paint_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: color_stop_buffer.as_entire_binding(),
+            },
+        ],
+        label: Some("paint_bind_group"),
+    })
+}
+
+fn create_vertex_buffer(device: &wgpu::Device, label: &str, size: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size: size as wgpu::BufferAddress,
+        usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_uniform_buffer(device: &wgpu::Device, size: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Uniform Buffer"),
+        size: size as wgpu::BufferAddress,
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_uniform_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    uniform_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: uniform_buffer,
+                offset: 0,
+                size: wgpu::BufferSize::new(size_of::<Uniforms>() as u64),
+            }),
+        }],
+        label: Some("uniform_bind_group"),
+    })
+}
+
+/// Creates a texture for glyphs: the glyph cache, the distance fields of glyphs, or color glyphs.
+fn create_glyph_texture(
+    device: &wgpu::Device,
+    label: &str,
+    format: wgpu::TextureFormat,
+    (width, height): (u32, u32),
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+        label: Some(label),
+    })
+}
+
+/// The texture a [GlyphAtlas] is uploaded to.
+struct GlyphTexture {
+    texture: wgpu::Texture,
+    /// Smaller than the size of the atlas when it has grown since the last upload.
+    size: (u32, u32),
+    label: &'static str,
+    format: wgpu::TextureFormat,
+}
+
+impl GlyphTexture {
+    fn new(
+        device: &wgpu::Device,
+        label: &'static str,
+        format: wgpu::TextureFormat,
+        atlas: &GlyphAtlas,
+    ) -> GlyphTexture {
+        GlyphTexture {
+            texture: create_glyph_texture(device, label, format, atlas.size()),
+            size: atlas.size(),
+            label,
+            format,
+        }
+    }
+
+    /// Uploads the glyphs added to the atlas, to a new texture if the atlas has grown.
+    /// Returns whether the texture was recreated, in which case the bind group using it must be too.
+    fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        atlas: &mut GlyphAtlas,
+    ) -> bool {
+        let recreated = atlas.size() != self.size;
+        if recreated {
+            *self = GlyphTexture::new(device, self.label, self.format, atlas);
+        }
+        let bytes_per_row = atlas.bytes_per_row();
+        if let Some((first_row, pixels)) = atlas.take_changes() {
+            let width = self.size.0;
+            let height = pixels.len() as u32 / bytes_per_row;
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &self.texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: first_row,
+                        z: 0,
+                    },
+                },
+                pixels,
+                wgpu::ImageDataLayout {
                     offset: 0,
-                    shader_location: 0,
-                    format: wgpu::VertexFormat::Float32x2,
+                    bytes_per_row: std::num::NonZeroU32::new(bytes_per_row),
+                    rows_per_image: std::num::NonZeroU32::new(height),
                 },
-                wgpu::VertexAttribute {
-                    offset: size_of::<[f32; 2]>() as wgpu::BufferAddress,
-                    shader_location: 1,
-                    format: wgpu::VertexFormat::Float32x4,
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
                 },
-            ],
+            );
         }
+        recreated
     }
 }
 
+/// Creates the bind group of the text pipelines, which sample the glyph cache, the distance field atlas and the color glyph atlas.
+fn create_text_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    (text_texture, text_sampler): (&wgpu::Texture, &wgpu::Sampler),
+    (sdf_texture, sdf_sampler): (&wgpu::Texture, &wgpu::Sampler),
+    color_texture: &wgpu::Texture,
+) -> wgpu::BindGroup {
+    let text_view = text_texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let sdf_view = sdf_texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let color_view = color_texture.create_view(&wgpu::TextureViewDescriptor::default());
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&text_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(text_sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(&sdf_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::Sampler(sdf_sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: wgpu::BindingResource::TextureView(&color_view),
+            },
+        ],
+        label: Some("text_bind_group"),
+    })
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Vertex {
+    pub position: [f32; 2],
+    /// In sRGB, see [Color].
+    pub color: [f32; 4],
+}
+
+/// Vertex as sent to the shape pipeline.
+/// The paint is an index in the paint buffer, `0` meaning the vertex color is used as is.
+/// Paints are evaluated at the position the vertex had before being transformed, so that they follow the transform.
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct TextureVertex {
+struct ShapeVertex {
     position: [f32; 2],
-    tex_coords: [f32; 2],
+    color: [f32; 4],
+    paint: u32,
+    paint_position: [f32; 2],
 }
 
-impl TextureVertex {
-    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+impl ShapeVertex {
+    const fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
         wgpu::VertexBufferLayout {
-            array_stride: size_of::<TextureVertex>() as wgpu::BufferAddress,
+            array_stride: size_of::<ShapeVertex>() as wgpu::BufferAddress,
             step_mode: wgpu::InputStepMode::Vertex,
             attributes: &[
                 wgpu::VertexAttribute {
@@ -61,6 +282,16 @@ impl TextureVertex {
                 wgpu::VertexAttribute {
                     offset: size_of::<[f32; 2]>() as wgpu::BufferAddress,
                     shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: size_of::<[f32; 6]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+                wgpu::VertexAttribute {
+                    offset: size_of::<[f32; 7]>() as wgpu::BufferAddress,
+                    shader_location: 3,
                     format: wgpu::VertexFormat::Float32x2,
                 },
             ],
@@ -68,12 +299,27 @@ impl TextureVertex {
     }
 }
 
+impl From<Vertex> for ShapeVertex {
+    fn from(vertex: Vertex) -> Self {
+        ShapeVertex {
+            position: vertex.position,
+            color: vertex.color,
+            paint: 0,
+            paint_position: vertex.position,
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 struct TextTextureVertex {
     position: [f32; 2],
     tex_coords: [f32; 2],
     color: [f32; 4],
+    /// The range of distances of the distance field in pixels (zero for glyphs of the glyph cache), the outline width and the glow radius.
+    effects: [f32; 4],
+    outline_color: [f32; 4],
+    glow_color: [f32; 4],
 }
 
 impl TextTextureVertex {
@@ -97,16 +343,46 @@ impl TextTextureVertex {
                     shader_location: 2,
                     format: wgpu::VertexFormat::Float32x4,
                 },
+                wgpu::VertexAttribute {
+                    offset: size_of::<[f32; 8]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: size_of::<[f32; 12]>() as wgpu::BufferAddress,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: size_of::<[f32; 16]>() as wgpu::BufferAddress,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
             ],
         }
     }
 }
 
+/// Where the pixels of a glyph are sampled from.
+#[derive(Debug, Clone, Copy)]
+enum GlyphSource {
+    /// The glyph cache of glyph_brush.
+    Cache,
+    /// The [SdfAtlas], with the range of distances of the field in pixels, and the effects.
+    DistanceField(f32, TextEffects),
+    /// The [ColorAtlas].
+    Color,
+}
+
 #[derive(Debug, Clone)]
 struct TextVertex {
     position: Rect,
     tex_coords: Rect,
     color: [f32; 4],
+    /// The index of the section of the glyph in [WgpuBackend::text_sections].
+    section: usize,
+    /// Texture coordinates are in pixels for glyphs of the [SdfAtlas] and the [ColorAtlas].
+    source: GlyphSource,
 }
 
 impl TextVertex {
@@ -128,86 +404,284 @@ impl TextVertex {
                 max: (tex_coords.max.x, tex_coords.max.y),
             },
             color: extra.color,
+            section: extra.z as usize,
+            source: GlyphSource::Cache,
         }
     }
 
-    fn into_vertices(self, vertices: &mut Vec<TextTextureVertex>) {
-        vertices.push(TextTextureVertex {
-            position: [self.position.max.0, self.position.min.1],
-            tex_coords: [self.tex_coords.max.0, self.tex_coords.min.1],
-            color: self.color,
-        });
-        vertices.push(TextTextureVertex {
-            position: [self.position.min.0, self.position.max.1],
-            tex_coords: [self.tex_coords.min.0, self.tex_coords.max.1],
-            color: self.color,
-        });
-        vertices.push(TextTextureVertex {
-            position: [self.position.min.0, self.position.min.1],
-            tex_coords: [self.tex_coords.min.0, self.tex_coords.min.1],
-            color: self.color,
-        });
-
-        vertices.push(TextTextureVertex {
-            position: [self.position.max.0, self.position.min.1],
-            tex_coords: [self.tex_coords.max.0, self.tex_coords.min.1],
-            color: self.color,
-        });
-        vertices.push(TextTextureVertex {
-            position: [self.position.min.0, self.position.max.1],
-            tex_coords: [self.tex_coords.min.0, self.tex_coords.max.1],
-            color: self.color,
-        });
-        vertices.push(TextTextureVertex {
-            position: [self.position.max.0, self.position.max.1],
-            tex_coords: [self.tex_coords.max.0, self.tex_coords.max.1],
-            color: self.color,
-        });
+    fn push_vertices(
+        &self,
+        transform: &Transform,
+        (sdf_atlas_size, color_atlas_size): ((u32, u32), (u32, u32)),
+        vertices: &mut Vec<TextTextureVertex>,
+    ) {
+        let corners = transform.apply_rect(self.position);
+        let (scale, effects, outline_color, glow_color) = match self.source {
+            GlyphSource::Cache => ((1.0, 1.0), [0.0; 4], [0.0; 4], [0.0; 4]),
+            GlyphSource::DistanceField(range, effects) => (
+                (1.0 / sdf_atlas_size.0 as f32, 1.0 / sdf_atlas_size.1 as f32),
+                [range, effects.outline_width, effects.glow_radius, 0.0],
+                effects.outline_color,
+                effects.glow_color,
+            ),
+            GlyphSource::Color => (
+                (
+                    1.0 / color_atlas_size.0 as f32,
+                    1.0 / color_atlas_size.1 as f32,
+                ),
+                [0.0, 0.0, 0.0, 1.0],
+                [0.0; 4],
+                [0.0; 4],
+            ),
+        };
+        let (min, max) = (self.tex_coords.min, self.tex_coords.max);
+        let tex_coords = [
+            [min.0 * scale.0, min.1 * scale.1],
+            [max.0 * scale.0, min.1 * scale.1],
+            [min.0 * scale.0, max.1 * scale.1],
+            [max.0 * scale.0, max.1 * scale.1],
+        ];
+        for corner in [1, 2, 0, 1, 2, 3].iter() {
+            vertices.push(TextTextureVertex {
+                position: [corners[*corner].0, corners[*corner].1],
+                tex_coords: tex_coords[*corner],
+                color: self.color,
+                effects,
+                outline_color,
+                glow_color,
+            });
+        }
     }
 }
 
+/// A group of draw calls.
+/// Layers are drawn in order, so that effects can apply to everything that has been drawn before them.
+/// Within a layer, shapes are drawn first, then images, then text.
+#[derive(Debug, Clone)]
+struct Layer {
+    first_vertex: u32,
+    first_image: usize,
+    /// Draws of custom pipelines are drawn after everything else of the layer.
+    first_custom_draw: usize,
+    /// A region of what has been drawn by the previous layers that is blurred before this layer is drawn.
+    /// The blurred rectangle is drawn clipped to the region, which is not axis-aligned when it is transformed.
+    backdrop_blur: Option<(Rect, f32, ClipRegion)>,
+    /// The offscreen target this layer is drawn to, or [None] for the screen.
+    target: Option<usize>,
+    blend_mode: BlendMode,
+    clip: ClipRegion,
+}
+
+/// An offscreen layer being drawn to, see [WgpuBackend::begin_offscreen_layer].
+/// The state of the backend before it began is saved, to be restored when it ends.
+#[derive(Debug, Clone)]
+struct OffscreenLayer {
+    target: usize,
+    blend_mode: BlendMode,
+    transforms: Vec<Transform>,
+    clips: Vec<ClipRegion>,
+}
+
+/// Uniforms of a layer.
+/// The uniforms of all layers are stored in the same buffer, and selected with a dynamic offset.
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 struct Uniforms {
     screen_width: f32,
     screen_height: f32,
+    /// Whether the shaders must not premultiply the colors they output, see [BlendMode::PremultipliedOver].
+    premultiplied_input: u32,
+    clip_plane_count: u32,
+    /// Fragments outside of any of these planes are discarded, see [ClipRegion].
+    clip_planes: [[f32; 4]; MAX_CLIP_PLANES],
+    /// Only used by custom pipelines, built-in ones transform vertices before uploading them.
+    transform: [[f32; 4]; 2],
+    /// Whether the target stores colors as they are output, so that shaders must encode them to sRGB themselves.
+    encode_srgb: u32,
+    text_gamma: f32,
+    text_contrast: f32,
+    /// Dynamic offsets must be multiples of [wgpu::BIND_BUFFER_ALIGNMENT].
+    _padding: u32,
+}
+
+impl Uniforms {
+    fn new(
+        size: (u32, u32),
+        blend_mode: BlendMode,
+        clip: &ClipRegion,
+        format: wgpu::TextureFormat,
+        text_gamma: TextGamma,
+    ) -> Uniforms {
+        let (clip_planes, clip_plane_count) = clip.planes();
+        Uniforms {
+            screen_width: size.0 as f32,
+            screen_height: size.1 as f32,
+            premultiplied_input: blend_mode.premultiplied_input() as u32,
+            clip_plane_count,
+            clip_planes,
+            transform: Transform::IDENTITY.rows(),
+            encode_srgb: !format.describe().srgb as u32,
+            text_gamma: text_gamma.gamma.max(0.01),
+            text_contrast: text_gamma.contrast.clamp(0.0, 1.0),
+            _padding: 0,
+        }
+    }
+
+    /// The dynamic offset of the uniforms at `index` in the uniform buffer.
+    fn offset(index: usize) -> u32 {
+        (index * size_of::<Uniforms>()) as u32
+    }
+}
+
+/// Creates a render pipeline for each [BlendMode], indexed by [BlendMode::index].
+#[allow(clippy::too_many_arguments)]
+fn create_blended_pipelines(
+    device: &wgpu::Device,
+    label: &str,
+    layout: &wgpu::PipelineLayout,
+    (vs_module, vs_entry_point): (&wgpu::ShaderModule, &str),
+    (fs_module, fs_entry_point): (&wgpu::ShaderModule, &str),
+    buffer: wgpu::VertexBufferLayout,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> Vec<wgpu::RenderPipeline> {
+    BlendMode::ALL
+        .iter()
+        .map(|blend_mode| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(layout),
+                vertex: wgpu::VertexState {
+                    module: vs_module,
+                    entry_point: vs_entry_point,
+                    buffers: std::slice::from_ref(&buffer),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: fs_module,
+                    entry_point: fs_entry_point,
+                    targets: &[wgpu::ColorTargetState {
+                        format,
+                        blend: Some(blend_mode.blend_state()),
+                        write_mask: wgpu::ColorWrite::ALL,
+                    }],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    clamp_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState {
+                    count: sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+            })
+        })
+        .collect()
 }
 
 pub struct WgpuBackend {
-    surface: wgpu::Surface,
-    device: wgpu::Device,
-    queue: wgpu::Queue,
+    /// [None] when the backend is embedded in an application that renders the frame itself, see [WgpuBackend::with_device].
+    surface: Option<(wgpu::Surface, wgpu::SwapChain)>,
+    device: Arc<wgpu::Device>,
+    queue: Arc<wgpu::Queue>,
     sc_desc: wgpu::SwapChainDescriptor,
-    swap_chain: wgpu::SwapChain,
+    /// The format layers are drawn in, see [targets::render_format].
+    render_format: wgpu::TextureFormat,
     pub(crate) size: winit::dpi::PhysicalSize<u32>,
-
-    render_pipeline: wgpu::RenderPipeline,
+    sample_count: u32,
+    targets: RenderTargets,
+    blit_render_pipeline: wgpu::RenderPipeline,
+    layers: Vec<Layer>,
+    offscreen_targets: Vec<OffscreenTarget>,
+    /// The offscreen targets of the previous frame, reused by offscreen layers of the same size once their texture is dropped.
+    offscreen_pool: Vec<OffscreenTarget>,
+    /// The offscreen layers currently drawn to, the last one being the current one.
+    offscreen_stack: Vec<OffscreenLayer>,
+    blend_mode: BlendMode,
+    text_gamma: TextGamma,
+    /// The current transform is the last one, and already combines all the others.
+    transforms: Vec<Transform>,
+    /// The current clip is the last one, and already is the intersection of all the others.
+    clips: Vec<ClipRegion>,
+
+    /// One pipeline per [BlendMode].
+    render_pipelines: Vec<wgpu::RenderPipeline>,
     vertex_buffer: wgpu::Buffer,
-    vertices: Vec<Vertex>,
-
-    texture_render_pipeline: wgpu::RenderPipeline,
+    vertex_buffer_size: usize,
+    vertices: Vec<ShapeVertex>,
+    paint_bind_group_layout: wgpu::BindGroupLayout,
+    paint_bind_group: wgpu::BindGroup,
+    paint_buffer: wgpu::Buffer,
+    paint_buffer_size: usize,
+    color_stop_buffer: wgpu::Buffer,
+    color_stop_buffer_size: usize,
+    paints: Vec<GpuPaint>,
+    color_stops: Vec<GpuColorStop>,
+
+    texture_render_pipelines: Vec<wgpu::RenderPipeline>,
     texture_vertex_buffer: wgpu::Buffer,
+    texture_vertex_buffer_size: usize,
     texture_sampler: wgpu::Sampler,
+    /// Samplers of the textures, created when first needed.
+    samplers: Vec<((FilterMode, WrapMode), wgpu::Sampler)>,
     texture_bind_group_layout: wgpu::BindGroupLayout,
     texture_bind_groups: Vec<(usize, TextureId, wgpu::Texture, wgpu::BindGroup)>,
     texture_id_counter: usize,
-    images: Vec<(TextureId, Rect)>,
+    atlases: Vec<Atlas>,
+    images: Vec<(TextureId, [TextureVertex; 6])>,
 
-    text_render_pipeline: wgpu::RenderPipeline,
+    blur_render_pipeline: wgpu::RenderPipeline,
+    blur_vertex_buffer: wgpu::Buffer,
+    blur_vertex_buffer_size: usize,
+
+    text_render_pipelines: Vec<wgpu::RenderPipeline>,
     text_vertex_buffer: wgpu::Buffer,
+    text_vertex_buffer_size: usize,
     text_texture: wgpu::Texture,
+    text_bind_group_layout: wgpu::BindGroupLayout,
     text_bind_group: wgpu::BindGroup,
     glyph_brush: glyph_brush::GlyphBrush<TextVertex>,
+    /// The layer and transform of each text section queued this frame.
+    text_sections: Vec<(usize, Transform)>,
+    /// The glyphs of the last time text was processed, kept so that they can be transformed again when glyph_brush asks to redraw them.
+    text_quads: Vec<TextVertex>,
     last_text_vertices_count: u32,
+    /// The index of the first text vertex after each layer, as of the last time glyphs were processed.
+    text_layer_ends: Vec<u32>,
     has_text: bool,
-
-    uniforms: Uniforms,
+    sdf_atlas: SdfAtlas,
+    sdf_texture: GlyphTexture,
+    sdf_sampler: wgpu::Sampler,
+    color_atlas: ColorAtlas,
+    color_texture: GlyphTexture,
+    /// The glyphs drawn from the [SdfAtlas] or the [ColorAtlas] this frame, in the order of their sections.
+    glyph_quads: Vec<TextVertex>,
+
+    custom_pipelines: Vec<CustomPipeline>,
+    custom_draws: Vec<CustomDraw>,
+    custom_vertices: Vec<u8>,
+    custom_vertex_buffer: wgpu::Buffer,
+    custom_vertex_buffer_size: usize,
+
+    /// The uniforms of each layer, followed by the uniforms of blurred backdrops and those of custom draws.
     uniform_buffer: wgpu::Buffer,
+    uniform_buffer_size: usize,
+    uniform_bind_group_layout: wgpu::BindGroupLayout,
     uniform_bind_group: wgpu::BindGroup,
 }
 
 impl WgpuBackend {
-    pub(crate) async fn new(window: &Window, default_font: &'static [u8]) -> Self {
+    pub(crate) async fn new(
+        window: &Window,
+        default_font: &'static [u8],
+        settings: &Settings,
+    ) -> Self {
         let size = window.inner_size();
 
         let instance = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
@@ -241,6 +715,56 @@ impl WgpuBackend {
         };
         let swap_chain = device.create_swap_chain(&surface, &sc_desc);
 
+        Self::from_parts(
+            Arc::new(device),
+            Arc::new(queue),
+            sc_desc,
+            Some((surface, swap_chain)),
+            default_font,
+            settings,
+        )
+    }
+
+    /**
+    Creates a backend using the device of an existing wgpu application, to draw an overlay for example.
+    Frames are rendered with [WgpuBackend::render_to_view] or [WgpuBackend::render_into_pass] instead of being presented to a window.
+    What is drawn is blended over the content of the texture it is rendered to.
+
+    `format` is the format of that texture, and `size` its size in pixels.
+    **/
+    pub fn with_device(
+        device: Arc<wgpu::Device>,
+        queue: Arc<wgpu::Queue>,
+        format: wgpu::TextureFormat,
+        size: (u32, u32),
+        default_font: &'static [u8],
+        settings: &Settings,
+    ) -> Self {
+        let sc_desc = wgpu::SwapChainDescriptor {
+            usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
+            format,
+            width: size.0,
+            height: size.1,
+            present_mode: wgpu::PresentMode::Fifo,
+        };
+        Self::from_parts(device, queue, sc_desc, None, default_font, settings)
+    }
+
+    /// Sets the backend up, once the device is known.
+    fn from_parts(
+        device: Arc<wgpu::Device>,
+        queue: Arc<wgpu::Queue>,
+        sc_desc: wgpu::SwapChainDescriptor,
+        surface: Option<(wgpu::Surface, wgpu::SwapChain)>,
+        default_font: &'static [u8],
+        settings: &Settings,
+    ) -> Self {
+        let size = winit::dpi::PhysicalSize::new(sc_desc.width, sc_desc.height);
+        let render_format = targets::render_format(sc_desc.format);
+
+        // Setup anti-aliasing
+        let sample_count = settings.anti_aliasing.sample_count();
+
         // Setup textures
         let texture_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             label: Some("Texture Sampler"),
@@ -279,47 +803,127 @@ impl WgpuBackend {
                 label: Some("texture_bind_group_layout"),
             });
 
-        // Setup uniforms
-        let uniforms = Uniforms {
-            screen_width: window.inner_size().width as f32,
-            screen_height: window.inner_size().height as f32,
+        // Distance fields are interpolated when they are minified too, since they are not drawn pixel for pixel
+        let sdf_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Distance Field Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let text_texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStage::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        };
+        let text_sampler_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStage::FRAGMENT,
+            ty: wgpu::BindingType::Sampler {
+                comparison: false,
+                filtering: true,
+            },
+            count: None,
         };
+        let text_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    text_texture_entry(0),
+                    text_sampler_entry(1),
+                    text_texture_entry(2),
+                    text_sampler_entry(3),
+                    text_texture_entry(4),
+                ],
+                label: Some("text_bind_group_layout"),
+            });
 
-        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Uniform Buffer"),
-            contents: bytemuck::cast_slice(&[uniforms]),
-            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
-        });
+        // Setup offscreen rendering
+        let targets = RenderTargets::new(
+            &device,
+            (sc_desc.width, sc_desc.height),
+            render_format,
+            sample_count,
+            &texture_bind_group_layout,
+            &texture_sampler,
+        );
 
+        // Setup uniforms
         let uniform_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 entries: &[wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: wgpu::ShaderStage::VERTEX,
+                    visibility: wgpu::ShaderStage::VERTEX | wgpu::ShaderStage::FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(size_of::<Uniforms>() as u64),
                     },
                     count: None,
                 }],
                 label: Some("uniform_bind_group_layout"),
             });
 
-        let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &uniform_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: uniform_buffer.as_entire_binding(),
-            }],
-            label: Some("uniform_bind_group"),
-        });
+        let uniform_buffer_size = 64 * size_of::<Uniforms>();
+        let uniform_buffer = create_uniform_buffer(&device, uniform_buffer_size);
+        let uniform_bind_group =
+            create_uniform_bind_group(&device, &uniform_bind_group_layout, &uniform_buffer);
+
+        // Setup paints
+        let paint_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStage::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStage::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some("paint_bind_group_layout"),
+            });
+
+        let paints = vec![GpuPaint::VERTEX_COLOR];
+        let color_stops = vec![GpuColorStop::PLACEHOLDER];
+        let paint_buffer_size = 64 * size_of::<GpuPaint>();
+        let paint_buffer = create_storage_buffer(&device, "Paint Buffer", paint_buffer_size);
+        let color_stop_buffer_size = 256 * size_of::<GpuColorStop>();
+        let color_stop_buffer =
+            create_storage_buffer(&device, "Color Stop Buffer", color_stop_buffer_size);
+        let paint_bind_group = create_paint_bind_group(
+            &device,
+            &paint_bind_group_layout,
+            &paint_buffer,
+            &color_stop_buffer,
+        );
 
         // Setup render pipeline
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[&uniform_bind_group_layout],
+                bind_group_layouts: &[&uniform_bind_group_layout, &paint_bind_group_layout],
                 push_constant_ranges: &[],
             });
 
@@ -328,39 +932,16 @@ impl WgpuBackend {
         let fs_module =
             device.create_shader_module(&wgpu::include_spirv!("ressources/shader.frag.spv"));
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &vs_module,
-                entry_point: "main",
-                buffers: &[Vertex::desc()],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &fs_module,
-                entry_point: "main",
-                targets: &[wgpu::ColorTargetState {
-                    format: sc_desc.format,
-                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                    write_mask: wgpu::ColorWrite::ALL,
-                }],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: None,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                clamp_depth: false, // Might be useful later
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-        });
+        let render_pipelines = create_blended_pipelines(
+            &device,
+            "Render Pipeline",
+            &render_pipeline_layout,
+            (&vs_module, "main"),
+            (&fs_module, "main"),
+            ShapeVertex::desc(),
+            render_format,
+            sample_count,
+        );
 
         // Setup texture render pipeline
         let texture_render_pipeline_layout =
@@ -375,46 +956,22 @@ impl WgpuBackend {
         let texture_fs_module = device
             .create_shader_module(&wgpu::include_spirv!("ressources/texture-shader.frag.spv"));
 
-        let texture_render_pipeline =
-            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some("Texture Render Pipeline"),
-                layout: Some(&texture_render_pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &texture_vs_module,
-                    entry_point: "main",
-                    buffers: &[TextureVertex::desc()],
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &texture_fs_module,
-                    entry_point: "main",
-                    targets: &[wgpu::ColorTargetState {
-                        format: sc_desc.format,
-                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                        write_mask: wgpu::ColorWrite::ALL,
-                    }],
-                }),
-                primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::TriangleList,
-                    strip_index_format: None,
-                    front_face: wgpu::FrontFace::Ccw,
-                    cull_mode: None,
-                    polygon_mode: wgpu::PolygonMode::Fill,
-                    clamp_depth: false,
-                    conservative: false,
-                },
-                depth_stencil: None,
-                multisample: wgpu::MultisampleState {
-                    count: 1,
-                    mask: !0,
-                    alpha_to_coverage_enabled: false,
-                },
-            });
+        let texture_render_pipelines = create_blended_pipelines(
+            &device,
+            "Texture Render Pipeline",
+            &texture_render_pipeline_layout,
+            (&texture_vs_module, "main"),
+            (&texture_fs_module, "main"),
+            TextureVertex::desc(),
+            render_format,
+            sample_count,
+        );
 
         // Setup text render pipeline
         let text_render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Text Render Pipeline Layout"),
-                bind_group_layouts: &[&uniform_bind_group_layout, &texture_bind_group_layout],
+                bind_group_layouts: &[&uniform_bind_group_layout, &text_bind_group_layout],
                 push_constant_ranges: &[],
             });
 
@@ -423,20 +980,44 @@ impl WgpuBackend {
         let text_fs_module =
             device.create_shader_module(&wgpu::include_spirv!("ressources/text-shader.frag.spv"));
 
-        let text_render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Text Render Pipeline"),
-            layout: Some(&text_render_pipeline_layout),
+        let text_render_pipelines = create_blended_pipelines(
+            &device,
+            "Text Render Pipeline",
+            &text_render_pipeline_layout,
+            (&text_vs_module, "main"),
+            (&text_fs_module, "main"),
+            TextTextureVertex::desc(),
+            render_format,
+            sample_count,
+        );
+
+        // Setup blur render pipeline
+        let blur_render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Blur Render Pipeline Layout"),
+                bind_group_layouts: &[&texture_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let blur_vs_module =
+            device.create_shader_module(&wgpu::include_spirv!("ressources/blur.vert.spv"));
+        let blur_fs_module =
+            device.create_shader_module(&wgpu::include_spirv!("ressources/blur.frag.spv"));
+
+        let blur_render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Blur Render Pipeline"),
+            layout: Some(&blur_render_pipeline_layout),
             vertex: wgpu::VertexState {
-                module: &text_vs_module,
+                module: &blur_vs_module,
                 entry_point: "main",
-                buffers: &[TextTextureVertex::desc()],
+                buffers: &[BlurVertex::desc()],
             },
             fragment: Some(wgpu::FragmentState {
-                module: &text_fs_module,
+                module: &blur_fs_module,
                 entry_point: "main",
                 targets: &[wgpu::ColorTargetState {
-                    format: sc_desc.format,
-                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    format: render_format,
+                    blend: None,
                     write_mask: wgpu::ColorWrite::ALL,
                 }],
             }),
@@ -457,152 +1038,413 @@ impl WgpuBackend {
             },
         });
 
-        // Setup vertex buffers
-        let data = vec![0; 1_000_000];
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: &data,
-            usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
-        });
+        // Setup blit render pipeline (copies the scene to the frame)
+        let blit_render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Blit Render Pipeline Layout"),
+                bind_group_layouts: &[&texture_bind_group_layout],
+                push_constant_ranges: &[],
+            });
 
-        let texture_vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Texture Vertex Buffer"),
-            contents: &data,
-            usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
-        });
+        let blit_vs_module =
+            device.create_shader_module(&wgpu::include_spirv!("ressources/blit.vert.spv"));
+        let blit_fs_module = if render_format == sc_desc.format {
+            device.create_shader_module(&wgpu::include_spirv!("ressources/blit.frag.spv"))
+        } else {
+            device.create_shader_module(&wgpu::include_spirv!("ressources/blit-encode.frag.spv"))
+        };
 
-        let text_vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Text Vertex Buffer"),
-            contents: &data,
-            usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+        let blit_render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Blit Render Pipeline"),
+            layout: Some(&blit_render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &blit_vs_module,
+                entry_point: "main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &blit_fs_module,
+                entry_point: "main",
+                targets: &[wgpu::ColorTargetState {
+                    format: sc_desc.format,
+                    // When embedded, the scene is drawn over the content of the target
+                    blend: match surface {
+                        Some(_) => None,
+                        None => Some(BlendMode::PremultipliedOver.blend_state()),
+                    },
+                    write_mask: wgpu::ColorWrite::ALL,
+                }],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                clamp_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
         });
 
-        // Setup text rendering
-        let font = ab_glyph::FontArc::try_from_slice(default_font).expect("Failed to parse font");
-        let glyph_brush = glyph_brush::GlyphBrushBuilder::using_font(font).build();
+        // Setup vertex buffers
+        let vertex_buffer_size = 1 << 20;
+        let vertex_buffer = create_vertex_buffer(&device, "Vertex Buffer", vertex_buffer_size);
 
-        let text_texture_size = wgpu::Extent3d {
-            width: glyph_brush.texture_dimensions().0,
-            height: glyph_brush.texture_dimensions().1,
-            depth_or_array_layers: 1,
-        };
+        let texture_vertex_buffer_size = 1 << 20;
+        let texture_vertex_buffer =
+            create_vertex_buffer(&device, "Texture Vertex Buffer", texture_vertex_buffer_size);
 
-        let text_texture = device.create_texture(&wgpu::TextureDescriptor {
-            size: text_texture_size,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::R8Unorm,
-            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
-            label: Some("Text Texture"),
-        });
+        // Text vertices are larger than the others, the buffer holds about 5000 glyphs before growing
+        let text_vertex_buffer_size = 31_250 * size_of::<TextTextureVertex>();
+        let text_vertex_buffer =
+            create_vertex_buffer(&device, "Text Vertex Buffer", text_vertex_buffer_size);
 
-        let text_view = text_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let custom_vertex_buffer_size = 1 << 16;
+        let custom_vertex_buffer =
+            create_vertex_buffer(&device, "Custom Vertex Buffer", custom_vertex_buffer_size);
 
-        let text_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &texture_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&text_view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&texture_sampler),
-                },
-            ],
-            label: Some("texture_bind_group"),
-        });
+        let blur_vertex_buffer_size = 64 * 12 * size_of::<BlurVertex>();
+        let blur_vertex_buffer =
+            create_vertex_buffer(&device, "Blur Vertex Buffer", blur_vertex_buffer_size);
+
+        // Setup text rendering
+        let fonts = Fonts::global();
+        fonts
+            .set_default_font(default_font)
+            .expect("Failed to parse font");
+        if settings.system_font_fallbacks {
+            fonts.add_system_fallbacks(Fonts::DEFAULT_FALLBACKS);
+        }
+        let glyph_brush = glyph_brush::GlyphBrushBuilder::using_fonts(fonts.all()).build();
+
+        let text_texture = create_glyph_texture(
+            &device,
+            "Text Texture",
+            wgpu::TextureFormat::R8Unorm,
+            glyph_brush.texture_dimensions(),
+        );
+        let sdf_atlas = SdfAtlas::new();
+        let sdf_texture = GlyphTexture::new(
+            &device,
+            "Distance Field Texture",
+            wgpu::TextureFormat::R8Unorm,
+            &sdf_atlas.atlas,
+        );
+        let mut color_atlas = ColorAtlas::new();
+        color_atlas.set_fonts(&fonts.font_data());
+        let color_texture = GlyphTexture::new(
+            &device,
+            "Color Glyph Texture",
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            &color_atlas.atlas,
+        );
+        let text_bind_group = create_text_bind_group(
+            &device,
+            &text_bind_group_layout,
+            (&text_texture, &texture_sampler),
+            (&sdf_texture.texture, &sdf_sampler),
+            &color_texture.texture,
+        );
 
         Self {
             surface,
             device,
             queue,
             sc_desc,
-            swap_chain,
+            render_format,
             size,
-
-            render_pipeline,
+            sample_count,
+            targets,
+            blit_render_pipeline,
+            layers: vec![Layer {
+                first_vertex: 0,
+                first_image: 0,
+                first_custom_draw: 0,
+                backdrop_blur: None,
+                target: None,
+                blend_mode: BlendMode::default(),
+                clip: ClipRegion::NONE,
+            }],
+            offscreen_targets: Vec::new(),
+            offscreen_pool: Vec::new(),
+            offscreen_stack: Vec::new(),
+            blend_mode: BlendMode::default(),
+            text_gamma: settings.text_gamma,
+            transforms: vec![Transform::IDENTITY],
+            clips: vec![ClipRegion::NONE],
+
+            render_pipelines,
             vertex_buffer,
+            vertex_buffer_size,
             vertices: Vec::new(),
-
-            texture_render_pipeline,
+            paint_bind_group_layout,
+            paint_bind_group,
+            paint_buffer,
+            paint_buffer_size,
+            color_stop_buffer,
+            color_stop_buffer_size,
+            paints,
+            color_stops,
+
+            texture_render_pipelines,
             texture_vertex_buffer,
+            texture_vertex_buffer_size,
             texture_sampler,
+            samplers: Vec::new(),
             texture_id_counter: 0,
             texture_bind_group_layout,
             texture_bind_groups: Vec::new(),
+            atlases: Vec::new(),
             images: Vec::new(),
 
-            text_render_pipeline,
+            blur_render_pipeline,
+            blur_vertex_buffer,
+            blur_vertex_buffer_size,
+
+            text_render_pipelines,
             text_vertex_buffer,
+            text_vertex_buffer_size,
             text_texture,
+            text_bind_group_layout,
             text_bind_group,
             glyph_brush,
+            text_sections: Vec::new(),
+            text_quads: Vec::new(),
             has_text: false,
             last_text_vertices_count: 0,
+            text_layer_ends: Vec::new(),
+            sdf_atlas,
+            sdf_texture,
+            sdf_sampler,
+            color_atlas,
+            color_texture,
+            glyph_quads: Vec::new(),
+
+            custom_pipelines: Vec::new(),
+            custom_draws: Vec::new(),
+            custom_vertices: Vec::new(),
+            custom_vertex_buffer,
+            custom_vertex_buffer_size,
 
-            uniforms,
             uniform_buffer,
+            uniform_buffer_size,
+            uniform_bind_group_layout,
             uniform_bind_group,
         }
     }
 
-    pub(crate) fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+    /**
+    Adapts the backend to a new size of the window, or of the texture it renders to.
+    **/
+    pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         self.size = new_size;
         self.sc_desc.width = new_size.width;
         self.sc_desc.height = new_size.height;
 
-        self.uniforms.screen_width = new_size.width as f32;
-        self.uniforms.screen_height = new_size.height as f32;
-        self.queue.write_buffer(
-            &self.uniform_buffer,
-            0,
-            bytemuck::cast_slice(&[self.uniforms]),
+        if let Some((surface, swap_chain)) = &mut self.surface {
+            *swap_chain = self.device.create_swap_chain(surface, &self.sc_desc);
+        }
+        self.targets = RenderTargets::new(
+            &self.device,
+            (new_size.width, new_size.height),
+            self.render_format,
+            self.sample_count,
+            &self.texture_bind_group_layout,
+            &self.texture_sampler,
         );
-
-        self.swap_chain = self.device.create_swap_chain(&self.surface, &self.sc_desc);
     }
 
-    pub(crate) fn update(&mut self) {
+    /**
+    Uploads everything drawn since the last frame to the GPU.
+    It must be called after drawing, and before rendering.
+    **/
+    pub fn update(&mut self) {
+        let vertices_size = self.vertices.len() * size_of::<ShapeVertex>();
+        if vertices_size > self.vertex_buffer_size {
+            self.vertex_buffer_size = vertices_size.next_power_of_two();
+            self.vertex_buffer =
+                create_vertex_buffer(&self.device, "Vertex Buffer", self.vertex_buffer_size);
+        }
         self.queue
             .write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
 
+        // Upload paints, growing the storage buffers if needed
+        let mut paint_buffers_reallocated = false;
+        let paints_size = self.paints.len() * size_of::<GpuPaint>();
+        if paints_size > self.paint_buffer_size {
+            self.paint_buffer_size = paints_size.next_power_of_two();
+            self.paint_buffer =
+                create_storage_buffer(&self.device, "Paint Buffer", self.paint_buffer_size);
+            paint_buffers_reallocated = true;
+        }
+        let color_stops_size = self.color_stops.len() * size_of::<GpuColorStop>();
+        if color_stops_size > self.color_stop_buffer_size {
+            self.color_stop_buffer_size = color_stops_size.next_power_of_two();
+            self.color_stop_buffer = create_storage_buffer(
+                &self.device,
+                "Color Stop Buffer",
+                self.color_stop_buffer_size,
+            );
+            paint_buffers_reallocated = true;
+        }
+        if paint_buffers_reallocated {
+            self.paint_bind_group = create_paint_bind_group(
+                &self.device,
+                &self.paint_bind_group_layout,
+                &self.paint_buffer,
+                &self.color_stop_buffer,
+            );
+        }
+        self.queue
+            .write_buffer(&self.paint_buffer, 0, bytemuck::cast_slice(&self.paints));
+        self.queue.write_buffer(
+            &self.color_stop_buffer,
+            0,
+            bytemuck::cast_slice(&self.color_stops),
+        );
+
+        // Upload the uniforms of each layer, and then those of blurred backdrops
+        let screen_size = (self.size.width, self.size.height);
+        let mut uniforms: Vec<Uniforms> = self
+            .layers
+            .iter()
+            .map(|layer| {
+                let size = match layer.target {
+                    Some(target) => self.offscreen_targets[target].size,
+                    None => screen_size,
+                };
+                Uniforms::new(
+                    size,
+                    layer.blend_mode,
+                    &layer.clip,
+                    self.render_format,
+                    self.text_gamma,
+                )
+            })
+            .collect();
+        for (_, _, clip) in self.layers.iter().filter_map(|layer| layer.backdrop_blur) {
+            uniforms.push(Uniforms::new(
+                screen_size,
+                BlendMode::Normal,
+                &clip,
+                self.render_format,
+                self.text_gamma,
+            ));
+        }
+        for (i, layer) in self.layers.iter().enumerate() {
+            let end = self
+                .layers
+                .get(i + 1)
+                .map(|next| next.first_custom_draw)
+                .unwrap_or_else(|| self.custom_draws.len());
+            let layer_uniforms = uniforms[i];
+            for draw in &self.custom_draws[layer.first_custom_draw..end] {
+                uniforms.push(Uniforms {
+                    transform: draw.transform.rows(),
+                    ..layer_uniforms
+                });
+            }
+        }
+        let uniforms_size = uniforms.len() * size_of::<Uniforms>();
+        if uniforms_size > self.uniform_buffer_size {
+            self.uniform_buffer_size = uniforms_size.next_power_of_two();
+            self.uniform_buffer = create_uniform_buffer(&self.device, self.uniform_buffer_size);
+            self.uniform_bind_group = create_uniform_bind_group(
+                &self.device,
+                &self.uniform_bind_group_layout,
+                &self.uniform_buffer,
+            );
+        }
+        self.queue
+            .write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&uniforms));
+
+        // Upload the vertices of custom pipelines
+        if self.custom_vertices.len() > self.custom_vertex_buffer_size {
+            self.custom_vertex_buffer_size = self.custom_vertices.len().next_power_of_two();
+            self.custom_vertex_buffer = create_vertex_buffer(
+                &self.device,
+                "Custom Vertex Buffer",
+                self.custom_vertex_buffer_size,
+            );
+        }
+        self.queue
+            .write_buffer(&self.custom_vertex_buffer, 0, &self.custom_vertices);
+        for pipeline in &mut self.custom_pipelines {
+            if let Some(uniforms) = &mut pipeline.uniforms {
+                uniforms.upload(&self.device, &self.queue);
+            }
+        }
+
         // Generate textured vertices
-        self.images.sort_by_key(|(id, _)| *id.id);
+        // Images are sorted by texture within each layer, so that they can be drawn with few bind group changes
+        for (i, layer) in self.layers.iter().enumerate() {
+            let end = self
+                .layers
+                .get(i + 1)
+                .map(|next| next.first_image)
+                .unwrap_or_else(|| self.images.len());
+            self.images[layer.first_image..end].sort_by_key(|(id, _)| id.texture);
+        }
         let mut texture_vertices = Vec::with_capacity(6 * self.images.len());
-        for (_, rect) in &self.images {
-            texture_vertices.push(TextureVertex {
-                position: [rect.max.0, rect.min.1],
-                tex_coords: [1.0, 0.0],
-            });
-            texture_vertices.push(TextureVertex {
-                position: [rect.min.0, rect.max.1],
-                tex_coords: [0.0, 1.0],
-            });
-            texture_vertices.push(TextureVertex {
-                position: [rect.min.0, rect.min.1],
-                tex_coords: [0.0, 0.0],
-            });
+        for (_, vertices) in &self.images {
+            texture_vertices.extend_from_slice(vertices);
+        }
 
-            texture_vertices.push(TextureVertex {
-                position: [rect.max.0, rect.min.1],
-                tex_coords: [1.0, 0.0],
-            });
-            texture_vertices.push(TextureVertex {
-                position: [rect.min.0, rect.max.1],
-                tex_coords: [0.0, 1.0],
-            });
-            texture_vertices.push(TextureVertex {
-                position: [rect.max.0, rect.max.1],
-                tex_coords: [1.0, 1.0],
-            });
+        // Blurred backdrops are drawn with the texture pipeline too, after the images
+        let mut blur_vertices = Vec::new();
+        for (rect, radius, _) in self.layers.iter().filter_map(|layer| layer.backdrop_blur) {
+            BlurVertex::backdrop_blur(rect, radius, screen_size, &mut blur_vertices);
+
+            let position = Rect {
+                min: screen_coords_to_wgpu(rect.min, screen_size),
+                max: screen_coords_to_wgpu(rect.max, screen_size),
+            };
+            let tex_coords = Rect {
+                min: (
+                    rect.min.0 / screen_size.0 as f32,
+                    rect.min.1 / screen_size.1 as f32,
+                ),
+                max: (
+                    rect.max.0 / screen_size.0 as f32,
+                    rect.max.1 / screen_size.1 as f32,
+                ),
+            };
+            TextureVertex::quad(position, tex_coords, &mut texture_vertices);
+        }
+        let texture_vertices_size = texture_vertices.len() * size_of::<TextureVertex>();
+        if texture_vertices_size > self.texture_vertex_buffer_size {
+            self.texture_vertex_buffer_size = texture_vertices_size.next_power_of_two();
+            self.texture_vertex_buffer = create_vertex_buffer(
+                &self.device,
+                "Texture Vertex Buffer",
+                self.texture_vertex_buffer_size,
+            );
         }
         self.queue.write_buffer(
             &self.texture_vertex_buffer,
             0,
             bytemuck::cast_slice(&texture_vertices),
         );
+        let blur_vertices_size = blur_vertices.len() * size_of::<BlurVertex>();
+        if blur_vertices_size > self.blur_vertex_buffer_size {
+            self.blur_vertex_buffer_size = blur_vertices_size.next_power_of_two();
+            self.blur_vertex_buffer = create_vertex_buffer(
+                &self.device,
+                "Blur Vertex Buffer",
+                self.blur_vertex_buffer_size,
+            );
+        }
+        self.queue.write_buffer(
+            &self.blur_vertex_buffer,
+            0,
+            bytemuck::cast_slice(&blur_vertices),
+        );
 
         // Update text rendering data
         if self.has_text {
@@ -643,66 +1485,91 @@ impl WgpuBackend {
                     TextVertex::from_glyph_vertex,
                 ) {
                     Ok(glyph_brush::BrushAction::Draw(quad_vertices)) => {
-                        let mut vertices = Vec::new();
-
-                        for quad_vertex in quad_vertices {
-                            quad_vertex.into_vertices(&mut vertices);
-                        }
-                        self.last_text_vertices_count = vertices.len() as u32;
-                        queue.write_buffer(
-                            &self.text_vertex_buffer,
-                            0,
-                            bytemuck::cast_slice(&vertices),
-                        );
+                        self.text_quads = quad_vertices;
                         break;
                     }
                     Ok(glyph_brush::BrushAction::ReDraw) => {
                         break;
                     }
                     Err(glyph_brush::BrushError::TextureTooSmall { suggested }) => {
-                        let text_texture_size = wgpu::Extent3d {
-                            width: suggested.0,
-                            height: suggested.1,
-                            depth_or_array_layers: 1,
-                        };
-
-                        self.text_texture = self.device.create_texture(&wgpu::TextureDescriptor {
-                            size: text_texture_size,
-                            mip_level_count: 1,
-                            sample_count: 1,
-                            dimension: wgpu::TextureDimension::D2,
-                            format: wgpu::TextureFormat::R8Unorm,
-                            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
-                            label: Some("Text Texture"),
-                        });
-
-                        let text_view = self
-                            .text_texture
-                            .create_view(&wgpu::TextureViewDescriptor::default());
-
-                        self.text_bind_group =
-                            self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-                                layout: &self.texture_bind_group_layout,
-                                entries: &[
-                                    wgpu::BindGroupEntry {
-                                        binding: 0,
-                                        resource: wgpu::BindingResource::TextureView(&text_view),
-                                    },
-                                    wgpu::BindGroupEntry {
-                                        binding: 1,
-                                        resource: wgpu::BindingResource::Sampler(
-                                            &self.texture_sampler,
-                                        ),
-                                    },
-                                ],
-                                label: Some("texture_bind_group"),
-                            });
+                        self.text_texture = create_glyph_texture(
+                            &self.device,
+                            "Text Texture",
+                            wgpu::TextureFormat::R8Unorm,
+                            suggested,
+                        );
+                        self.text_bind_group = create_text_bind_group(
+                            &self.device,
+                            &self.text_bind_group_layout,
+                            (&self.text_texture, &self.texture_sampler),
+                            (&self.sdf_texture.texture, &self.sdf_sampler),
+                            &self.color_texture.texture,
+                        );
                     }
                 }
             }
+
+            // The glyphs rasterized by the backend are uploaded, to new textures if the atlases have grown
+            let sdf_recreated =
+                self.sdf_texture
+                    .update(&self.device, &self.queue, &mut self.sdf_atlas.atlas);
+            let color_recreated =
+                self.color_texture
+                    .update(&self.device, &self.queue, &mut self.color_atlas.atlas);
+            if sdf_recreated || color_recreated {
+                self.text_bind_group = create_text_bind_group(
+                    &self.device,
+                    &self.text_bind_group_layout,
+                    (&self.text_texture, &self.texture_sampler),
+                    (&self.sdf_texture.texture, &self.sdf_sampler),
+                    &self.color_texture.texture,
+                );
+            }
+
+            // Glyphs are transformed every frame, since glyph_brush only knows about untransformed positions
+            // They are generated in the order sections were queued, so merging them with the glyphs drawn from the other atlases keeps layers contiguous
+            let mut vertices =
+                Vec::with_capacity(6 * (self.text_quads.len() + self.glyph_quads.len()));
+            self.text_layer_ends.clear();
+            let mut text_quads = self.text_quads.iter().peekable();
+            let mut glyph_quads = self.glyph_quads.iter().peekable();
+            let atlas_sizes = (self.sdf_texture.size, self.color_texture.size);
+            loop {
+                let quad = match (text_quads.peek(), glyph_quads.peek()) {
+                    (Some(text), Some(glyph)) if glyph.section < text.section => glyph_quads.next(),
+                    (Some(_), _) => text_quads.next(),
+                    (None, _) => glyph_quads.next(),
+                };
+                let quad = match quad {
+                    Some(quad) => quad,
+                    None => break,
+                };
+                let (layer, transform) = self.text_sections[quad.section];
+                quad.push_vertices(&transform, atlas_sizes, &mut vertices);
+                while self.text_layer_ends.len() <= layer {
+                    let end = self.text_layer_ends.last().copied().unwrap_or(0);
+                    self.text_layer_ends.push(end);
+                }
+                self.text_layer_ends[layer] = vertices.len() as u32;
+            }
+            self.last_text_vertices_count = vertices.len() as u32;
+            let vertices_size = vertices.len() * size_of::<TextTextureVertex>();
+            if vertices_size > self.text_vertex_buffer_size {
+                self.text_vertex_buffer_size = vertices_size.next_power_of_two();
+                self.text_vertex_buffer = create_vertex_buffer(
+                    &self.device,
+                    "Text Vertex Buffer",
+                    self.text_vertex_buffer_size,
+                );
+            }
+            self.queue
+                .write_buffer(&self.text_vertex_buffer, 0, bytemuck::cast_slice(&vertices));
         }
 
         // Sweep unused textures
+        for atlas in &mut self.atlases {
+            atlas.sweep();
+        }
         self.texture_bind_groups
             .retain(|(_, texture_id, texture, _)| {
                 if std::sync::Arc::strong_count(&texture_id.id) > 1 {
@@ -719,26 +1586,631 @@ impl WgpuBackend {
     It will be drawn at the next frame and then removed.
     **/
     pub fn add_vertex(&mut self, vertex: Vertex) {
-        self.vertices.push(vertex);
+        self.vertices
+            .push(self.shape_vertex(vertex.position, vertex.color, 0));
+    }
+
+    /// Creates a [ShapeVertex] with the current transform applied.
+    fn shape_vertex(&self, position: [f32; 2], color: [f32; 4], paint: u32) -> ShapeVertex {
+        let transformed = self.transform().apply((position[0], position[1]));
+        ShapeVertex {
+            position: [transformed.0, transformed.1],
+            color,
+            paint,
+            paint_position: position,
+        }
+    }
+
+    /**
+    Adds triangles filled with a [Paint].
+    Every 3 positions form a triangle, so the length of `positions` should be a multiple of 3.
+    It will be drawn at the next frame and then removed.
+    **/
+    pub fn add_triangles(&mut self, positions: &[[f32; 2]], paint: &Paint) {
+        let (color, paint) = match paint {
+            Paint::Solid(color) => (*color, 0),
+            gradient => {
+                self.paints
+                    .push(GpuPaint::from_paint(gradient, &mut self.color_stops));
+                ([1.0; 4], self.paints.len() as u32 - 1)
+            }
+        };
+
+        for position in positions {
+            self.vertices
+                .push(self.shape_vertex(*position, color, paint));
+        }
+    }
+
+    /**
+    Fills a rectangle with a [Paint].
+    **/
+    pub fn add_rect(&mut self, rect: Rect, paint: &Paint) {
+        let (min, max) = (rect.min, rect.max);
+        self.add_triangles(
+            &[
+                [min.0, min.1],
+                [max.0, min.1],
+                [min.0, max.1],
+                [max.0, min.1],
+                [max.0, max.1],
+                [min.0, max.1],
+            ],
+            paint,
+        );
     }
 
     /**
     Draws a text [Section](glyph_brush::Section).
     The text will be rasterized by [ab_glyph] and cached by [glyph_brush].
+    Glyphs of color fonts, like emoji, keep their own colors.
+    Its size can be known beforehand with [Fonts::measure_section].
+    **/
+    pub fn add_text(&mut self, mut text: glyph_brush::Section) {
+        self.prepare_text(&mut text);
+        let layout = text.layout;
+        self.queue_text(text, layout);
+    }
+
+    /**
+    Draws a text [Section](glyph_brush::Section) after shaping it: ligatures, joined Arabic letters and marks are drawn, and right to left text is ordered.
+    Glyphs are cached like the ones of [WgpuBackend::add_text], but laying the text out is slower.
+    Its size can be known beforehand with [Fonts::measure_shaped_section].
+    **/
+    pub fn add_shaped_text(&mut self, mut text: glyph_brush::Section) {
+        self.prepare_text(&mut text);
+        let layout = Fonts::global().shaping_layout(text.layout);
+        self.queue_text(text, layout);
+    }
+
+    /// Queues a section in glyph_brush, except for its color glyphs which are drawn from the [ColorAtlas].
+    fn queue_text<L>(&mut self, text: glyph_brush::Section, layout: L)
+    where
+        L: glyph_brush::GlyphPositioner + std::hash::Hash,
+    {
+        let color_atlas = &self.color_atlas;
+        if !text
+            .text
+            .iter()
+            .any(|text| color_atlas.has_color_glyphs(text.font_id))
+        {
+            self.glyph_brush.queue_custom_layout(text, &layout);
+            return;
+        }
+
+        let glyphs: Vec<_> = self
+            .glyph_brush
+            .glyphs_custom_layout(&text, &layout)
+            .cloned()
+            .collect();
+        for glyph in &glyphs {
+            if let Some(quad) = self.color_quad(&text, glyph) {
+                self.glyph_quads.push(quad);
+            }
+        }
+        let layout = ColorFilter {
+            layout,
+            font_data: Fonts::global().font_data(),
+        };
+        self.glyph_brush.queue_custom_layout(text, &layout);
+    }
+
+    /// The quad of a glyph drawn from the [ColorAtlas], if it is a color glyph.
+    fn color_quad(
+        &mut self,
+        text: &glyph_brush::Section,
+        glyph_brush::SectionGlyph {
+            section_index,
+            font_id,
+            glyph,
+            ..
+        }: &glyph_brush::SectionGlyph,
+    ) -> Option<TextVertex> {
+        if !self.color_atlas.has_color_glyphs(*font_id) {
+            return None;
+        }
+        let font = &self.glyph_brush.fonts()[font_id.0];
+        let extra = text.text[*section_index].extra;
+        // Layers using the color of the text are drawn with its straight color
+        let alpha = match self.blend_mode.premultiplied_input() {
+            true if extra.color[3] > 0.0 => extra.color[3],
+            _ => 1.0,
+        };
+        let foreground = [0, 1, 2].map(|channel| {
+            (extra.color[channel] / alpha * 255.0)
+                .round()
+                .clamp(0.0, 255.0) as u8
+        });
+        let color_glyph =
+            self.color_atlas
+                .glyph(*font_id, font, glyph.id, glyph.scale, foreground)?;
+
+        // Glyphs are drawn at the size of their strike
+        let scale = (
+            glyph.scale.x / color_glyph.size,
+            glyph.scale.y / color_glyph.size,
+        );
+        let bounds = color_glyph.bounds;
+        Some(TextVertex {
+            position: Rect {
+                min: (
+                    glyph.position.x + bounds.min.0 * scale.0,
+                    glyph.position.y + bounds.min.1 * scale.1,
+                ),
+                max: (
+                    glyph.position.x + bounds.max.0 * scale.0,
+                    glyph.position.y + bounds.max.1 * scale.1,
+                ),
+            },
+            tex_coords: color_glyph.tex_coords,
+            color: match color_glyph.tinted {
+                true => extra.color,
+                false => [1.0, 1.0, 1.0, extra.color[3]],
+            },
+            section: extra.z as usize,
+            source: GlyphSource::Color,
+        })
+    }
+
+    /**
+    Draws a text [Section](glyph_brush::Section) with signed distance fields, with an outline and a glow if [TextEffects] have them.
+    Glyphs are rasterized once into an atlas of distances to their edges, from which they are drawn sharp at any scale, even when the transform changes every frame.
+    Small text is slightly softer than with [WgpuBackend::add_text].
     **/
-    pub fn add_text(&mut self, text: glyph_brush::Section) {
+    pub fn add_sdf_text(&mut self, mut text: glyph_brush::Section, effects: &TextEffects) {
+        self.prepare_text(&mut text);
+        let glyphs: Vec<_> = self.glyph_brush.glyphs(&text).cloned().collect();
+        self.add_sdf_glyphs(&text, glyphs, effects);
+    }
+
+    /// Queues glyphs laid out by glyph_brush, to be drawn from the [SdfAtlas], or from the [ColorAtlas] for color glyphs.
+    fn add_sdf_glyphs(
+        &mut self,
+        text: &glyph_brush::Section,
+        glyphs: Vec<glyph_brush::SectionGlyph>,
+        effects: &TextEffects,
+    ) {
+        for section_glyph in glyphs {
+            if let Some(quad) = self.color_quad(text, &section_glyph) {
+                self.glyph_quads.push(quad);
+                continue;
+            }
+            let glyph_brush::SectionGlyph {
+                section_index,
+                font_id,
+                glyph,
+                ..
+            } = section_glyph;
+            let font = &self.glyph_brush.fonts()[font_id.0];
+            let sdf_glyph = match self.sdf_atlas.glyph(font_id, font, glyph.id) {
+                Some(sdf_glyph) => sdf_glyph,
+                None => continue,
+            };
+            let scale = (
+                glyph.scale.x / sdf::REFERENCE_SCALE,
+                glyph.scale.y / sdf::REFERENCE_SCALE,
+            );
+            let bounds = sdf_glyph.bounds;
+            let extra = text.text[section_index].extra;
+            self.glyph_quads.push(TextVertex {
+                position: Rect {
+                    min: (
+                        glyph.position.x + bounds.min.0 * scale.0,
+                        glyph.position.y + bounds.min.1 * scale.1,
+                    ),
+                    max: (
+                        glyph.position.x + bounds.max.0 * scale.0,
+                        glyph.position.y + bounds.max.1 * scale.1,
+                    ),
+                },
+                tex_coords: sdf_glyph.tex_coords,
+                color: extra.color,
+                section: extra.z as usize,
+                source: GlyphSource::DistanceField(2.0 * sdf::SPREAD * scale.1, *effects),
+            });
+        }
+    }
+
+    /// Draws a section with the method matching [TextOptions].
+    fn add_section_with_options(
+        &mut self,
+        mut section: glyph_brush::Section,
+        options: &TextOptions,
+    ) {
+        match (&options.sdf, options.shaping) {
+            (Some(effects), shaping) => {
+                self.prepare_text(&mut section);
+                let glyphs: Vec<_> = match shaping {
+                    true => {
+                        let layout = Fonts::global().shaping_layout(section.layout);
+                        self.glyph_brush
+                            .glyphs_custom_layout(&section, &layout)
+                            .cloned()
+                            .collect()
+                    }
+                    false => self.glyph_brush.glyphs(&section).cloned().collect(),
+                };
+                self.add_sdf_glyphs(&section, glyphs, effects);
+            }
+            (None, true) => self.add_shaped_text(section),
+            (None, false) => self.add_text(section),
+        }
+    }
+
+    fn prepare_text(&mut self, text: &mut glyph_brush::Section) {
+        // Fonts may have been registered since the last call
+        let known_fonts = self.glyph_brush.fonts().len();
+        if Fonts::global().len() > known_fonts {
+            for font in Fonts::global().all().drain(known_fonts..) {
+                self.glyph_brush.add_font(font);
+            }
+            self.color_atlas.set_fonts(&Fonts::global().font_data());
+        }
+
+        // The z coordinate is used to remember the section of the text, and thus its layer and transform
+        let section = self.text_sections.len() as f32;
+        self.text_sections
+            .push((self.layers.len() - 1, self.transform()));
+        for text in &mut text.text {
+            text.extra.z = section;
+        }
+
+        Fonts::global().apply_fallbacks(text);
         self.has_text = true;
-        self.glyph_brush.queue(text);
+    }
+
+    /**
+    Draws text inside of a rectangle.
+    Depending on [TextOptions], the text is wrapped, aligned, ellipsized and clipped to the rectangle.
+    **/
+    pub fn add_text_in_rect(&mut self, text: &str, rect: Rect, options: &TextOptions) {
+        if options.clip {
+            self.push_clip(rect);
+        }
+        for line in Fonts::global().fit_in_rect(text, rect, options) {
+            let section = glyph_brush::Section::default()
+                .with_screen_position(line.position)
+                .add_text(
+                    glyph_brush::Text::new(&line.text)
+                        .with_font_id(options.font)
+                        .with_scale(options.scale)
+                        .with_color(options.color),
+                );
+            self.add_section_with_options(section, options);
+        }
+        if options.clip {
+            self.pop_clip();
+        }
+    }
+
+    /**
+    Draws [RichText] inside of a rectangle, with the underlines and strikethroughs of its spans.
+    [TextOptions] give the style of spans that don't set it, and how the text is wrapped, aligned and clipped.
+    `max_lines` and `ellipsis` are not supported.
+
+    Returns the layout of the text, which tells which span is at a position (see [RichTextLayout::link_at]).
+    **/
+    pub fn add_rich_text(
+        &mut self,
+        text: &RichText,
+        rect: Rect,
+        options: &TextOptions,
+    ) -> RichTextLayout {
+        let layout = text.layout(Fonts::global(), rect, options);
+        if options.clip {
+            self.push_clip(rect);
+        }
+        let section = text.section(&layout, rect, options);
+        self.add_section_with_options(section, options);
+        for (decoration, color) in &layout.decorations {
+            self.add_rect(*decoration, &Paint::Solid(*color));
+        }
+        if options.clip {
+            self.pop_clip();
+        }
+        layout
+    }
+
+    /**
+    Draws the shadow of a rectangle with rounded corners (use a `corner_radius` of `0.0` for sharp corners).
+    The rectangle itself is not drawn, so the shadow should be added before it.
+    **/
+    pub fn add_shadow(&mut self, rect: Rect, corner_radius: f32, shadow: &Shadow) {
+        self.paints.push(GpuPaint::shadow(
+            shadow.shadowed_rect(rect),
+            (corner_radius + shadow.spread).max(0.0),
+            shadow.sigma(),
+        ));
+        let paint = self.paints.len() as u32 - 1;
+
+        let painted = shadow.painted_rect(rect);
+        for (x, y) in [(1, 0), (0, 1), (0, 0), (1, 0), (0, 1), (1, 1)].iter() {
+            let position = [
+                if *x == 0 {
+                    painted.min.0
+                } else {
+                    painted.max.0
+                },
+                if *y == 0 {
+                    painted.min.1
+                } else {
+                    painted.max.1
+                },
+            ];
+            self.vertices
+                .push(self.shape_vertex(position, shadow.color, paint));
+        }
+    }
+
+    /**
+    Blurs everything that has been drawn so far in the `rect` region, like a frosted glass.
+    Everything drawn after this call will appear on top of the blurred region.
+    The blur is rendered to textures of the size of the screen, which are reused by every frame.
+    Blurs are not supported in offscreen layers, where they are ignored with a warning.
+    **/
+    pub fn add_backdrop_blur(&mut self, rect: Rect, blur_radius: f32) {
+        if !self.offscreen_stack.is_empty() {
+            log::warn!(
+                "Backdrop blurs are not supported in offscreen layers, the blur of {:?} is ignored",
+                rect
+            );
+            return;
+        }
+
+        // The bounding box of the transformed rectangle is blurred, and then clipped to its actual shape
+        let transform = self.transform();
+        let clip = self.clip_region().intersect(rect, &transform);
+        let rect = transform.bounding_box(rect);
+        let rect = Rect {
+            min: (rect.min.0.max(0.0), rect.min.1.max(0.0)),
+            max: (
+                rect.max.0.min(self.size.width as f32),
+                rect.max.1.min(self.size.height as f32),
+            ),
+        };
+        if rect.width() <= 0.0 || rect.height() <= 0.0 {
+            return;
+        }
+
+        self.push_layer(Some((rect, blur_radius.max(0.0), clip)));
+    }
+
+    /**
+    Starts drawing to a new texture of the given size (in pixels) instead of the screen.
+    Everything drawn until [WgpuBackend::end_offscreen_layer] is called goes to this texture, with coordinates relative to it.
+    Offscreen layers can be nested.
+
+    This is useful to cache expensive content, to apply effects to a group of draw calls or to make thumbnails.
+    Backdrop blurs are ignored in offscreen layers, see [WgpuBackend::add_backdrop_blur].
+    Offscreen layers start without transform nor clip, and the previous ones are restored when they end.
+    See also [Area::render_to_texture].
+    **/
+    pub fn begin_offscreen_layer(&mut self, size: (u32, u32)) {
+        let size = (size.0.max(1), size.1.max(1));
+        let target = match self
+            .offscreen_pool
+            .iter()
+            .position(|target| target.size == size && target.is_unused())
+        {
+            Some(index) => self.offscreen_pool.swap_remove(index),
+            None => self.create_offscreen_target(size),
+        };
+        self.offscreen_targets.push(target);
+        self.offscreen_stack.push(OffscreenLayer {
+            target: self.offscreen_targets.len() - 1,
+            blend_mode: std::mem::take(&mut self.blend_mode),
+            transforms: std::mem::replace(&mut self.transforms, vec![Transform::IDENTITY]),
+            clips: std::mem::replace(&mut self.clips, vec![ClipRegion::NONE]),
+        });
+        self.push_layer(None);
+    }
+
+    /// Creates the texture of an offscreen layer, and the framebuffer it is drawn on when multisampling.
+    fn create_offscreen_target(&mut self, size: (u32, u32)) -> OffscreenTarget {
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.render_format,
+            usage: wgpu::TextureUsage::RENDER_ATTACHMENT
+                | wgpu::TextureUsage::SAMPLED
+                | wgpu::TextureUsage::COPY_SRC
+                | wgpu::TextureUsage::COPY_DST,
+            label: Some("Offscreen Layer Texture"),
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let options = TextureOptions {
+            format: TextureFormat::from_wgpu(self.render_format),
+            ..TextureOptions::default()
+        };
+        let mut texture_id = self.register_texture(texture, size, &options);
+        texture_id.premultiplied = true;
+
+        OffscreenTarget {
+            size,
+            multisampled_framebuffer: targets::create_multisampled_framebuffer(
+                &self.device,
+                size,
+                self.render_format,
+                self.sample_count,
+            ),
+            view,
+            texture_id,
+        }
+    }
+
+    /**
+    Stops drawing to the texture of the last offscreen layer begun with [WgpuBackend::begin_offscreen_layer], and returns it.
+    The texture can be drawn like any image, during this frame or later ones.
+    Once it is dropped, it is reused by the offscreen layers of the same size of the next frame.
+    Panics if there is no offscreen layer to end.
+    **/
+    pub fn end_offscreen_layer(&mut self) -> TextureId {
+        let offscreen_layer = self
+            .offscreen_stack
+            .pop()
+            .expect("There is no offscreen layer to end");
+        self.blend_mode = offscreen_layer.blend_mode;
+        self.transforms = offscreen_layer.transforms;
+        self.clips = offscreen_layer.clips;
+        self.push_layer(None);
+        self.offscreen_targets[offscreen_layer.target]
+            .texture_id
+            .clone()
+    }
+
+    /// Starts a new layer, drawn to the current target.
+    fn push_layer(&mut self, backdrop_blur: Option<(Rect, f32, ClipRegion)>) {
+        self.layers.push(Layer {
+            first_vertex: self.vertices.len() as u32,
+            first_image: self.images.len(),
+            first_custom_draw: self.custom_draws.len(),
+            backdrop_blur,
+            target: self.offscreen_stack.last().map(|layer| layer.target),
+            blend_mode: self.blend_mode,
+            clip: self.clip_region(),
+        });
+    }
+
+    /**
+    Sets how everything drawn from now on is combined with what has already been drawn.
+    Offscreen layers start with the default [BlendMode], and the previous mode is restored when they end.
+    **/
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        if blend_mode != self.blend_mode {
+            self.blend_mode = blend_mode;
+            self.push_layer(None);
+        }
+    }
+
+    pub fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    /**
+    Sets how the coverage of glyphs is adjusted, to balance the weight of light and dark text.
+    It applies to all the text of the frame, not only to the one drawn from now on.
+    **/
+    pub fn set_text_gamma(&mut self, text_gamma: TextGamma) {
+        self.text_gamma = text_gamma;
+    }
+
+    pub fn text_gamma(&self) -> TextGamma {
+        self.text_gamma
+    }
+
+    /**
+    Transforms everything drawn from now on, until [WgpuBackend::pop_transform] is called.
+    The transform applies to the coordinates of what is drawn, before the transforms that were already pushed.
+    Text is rasterized before being transformed, so it gets blurry when scaled up.
+    **/
+    pub fn push_transform(&mut self, transform: Transform) {
+        let current = transform.then(self.transform());
+        self.transforms.push(current);
+    }
+
+    /**
+    Removes the last transform pushed with [WgpuBackend::push_transform].
+    Panics if there is no transform to pop.
+    **/
+    pub fn pop_transform(&mut self) {
+        assert!(self.transforms.len() > 1, "There is no transform to pop");
+        self.transforms.pop();
+    }
+
+    /// The transform currently applied to what is drawn, combining all the pushed ones.
+    pub fn transform(&self) -> Transform {
+        *self.transforms.last().unwrap_or(&Transform::IDENTITY)
+    }
+
+    /**
+    Hides everything drawn from now on outside of a rectangle, until [WgpuBackend::pop_clip] is called.
+    The rectangle is transformed by the current transform, and nested clips are intersected.
+    **/
+    pub fn push_clip(&mut self, rect: Rect) {
+        let clip = self.clip_region().intersect(rect, &self.transform());
+        self.clips.push(clip);
+        self.push_layer(None);
+    }
+
+    /**
+    Removes the last clip pushed with [WgpuBackend::push_clip].
+    Panics if there is no clip to pop.
+    **/
+    pub fn pop_clip(&mut self) {
+        assert!(self.clips.len() > 1, "There is no clip to pop");
+        self.clips.pop();
+        self.push_layer(None);
+    }
+
+    /// The region outside of which nothing is currently drawn.
+    pub(crate) fn clip_region(&self) -> ClipRegion {
+        *self.clips.last().unwrap_or(&ClipRegion::NONE)
+    }
+
+    /// The size of what is currently drawn to: the screen or an offscreen layer.
+    fn target_size(&self) -> (u32, u32) {
+        match self.offscreen_stack.last() {
+            Some(layer) => self.offscreen_targets[layer.target].size,
+            None => (self.size.width, self.size.height),
+        }
     }
 
     /**
     Draws an image at the specified position.
     A [TextureId] can be obtained with [WgpuBackend::create_texture].
     **/
-    pub fn add_image(&mut self, mut position: Rect, texture_id: TextureId) {
-        position.min = screen_coords_to_wgpu(position.min, (self.size.width, self.size.height));
-        position.max = screen_coords_to_wgpu(position.max, (self.size.width, self.size.height));
-        self.images.push((texture_id, position));
+    pub fn add_image(&mut self, position: Rect, texture_id: TextureId) {
+        self.add_image_with_options(position, texture_id, &ImageOptions::default());
+    }
+
+    /**
+    Draws an image at the specified position, with more control than [WgpuBackend::add_image].
+    Only a part of the image can be drawn, and it can be tinted, faded, rotated and flipped.
+    See [ImageOptions] for details.
+    **/
+    pub fn add_image_with_options(
+        &mut self,
+        position: Rect,
+        texture_id: TextureId,
+        options: &ImageOptions,
+    ) {
+        let vertices = TextureVertex::image(
+            position,
+            &texture_id,
+            options,
+            &self.transform(),
+            self.target_size(),
+        );
+        self.images.push((texture_id, vertices));
+    }
+
+    /**
+    Draws an image cut in nine parts, so that its corners keep their size while its edges and center fill the rest of `position`.
+    This is useful for skinned buttons and panels.
+    See [NineSlice] for details.
+    **/
+    pub fn add_nine_slice(
+        &mut self,
+        position: Rect,
+        texture_id: TextureId,
+        nine_slice: &NineSlice,
+    ) {
+        let quads = TextureVertex::nine_slice(
+            position,
+            &texture_id,
+            nine_slice,
+            &self.transform(),
+            self.target_size(),
+        );
+        for vertices in quads {
+            self.images.push((texture_id.clone(), vertices));
+        }
     }
 
     /**
@@ -746,11 +2218,197 @@ impl WgpuBackend {
     Panics if the image data is not consistent with the indicated image dimensions (its len must be `4*width*height` bytes).
     **/
     pub fn create_texture(&mut self, image_dimensions: (u32, u32), image_rgba: &[u8]) -> TextureId {
+        self.create_texture_with_format(image_dimensions, TextureFormat::Rgba8Srgb, image_rgba)
+    }
+
+    /**
+    Same as [WgpuBackend::create_texture], but the pixels can be in any [TextureFormat].
+    Panics if the image data is not consistent with the indicated image dimensions (its len must be `bytes_per_pixel*width*height` bytes).
+    **/
+    pub fn create_texture_with_format(
+        &mut self,
+        image_dimensions: (u32, u32),
+        format: TextureFormat,
+        image_data: &[u8],
+    ) -> TextureId {
+        let options = TextureOptions {
+            format,
+            ..TextureOptions::default()
+        };
+        self.create_texture_with_options(image_dimensions, &options, image_data)
+    }
+
+    /**
+    Same as [WgpuBackend::create_texture_with_format], with control over how the texture is sampled.
+    See [TextureOptions] for details.
+    **/
+    pub fn create_texture_with_options(
+        &mut self,
+        image_dimensions: (u32, u32),
+        options: &TextureOptions,
+        image_data: &[u8],
+    ) -> TextureId {
+        assert_eq!(
+            image_dimensions.0 as usize
+                * image_dimensions.1 as usize
+                * options.format.bytes_per_pixel() as usize,
+            image_data.len()
+        );
+
+        let texture_id = self.create_empty_texture(image_dimensions, options);
+        self.write_texture(texture_id.texture, 0, (0, 0), image_dimensions, image_data);
+        self.write_mipmaps(&texture_id, image_data);
+
+        texture_id
+    }
+
+    /**
+    Overwrites the pixels of a region of an existing texture, without allocating a new one.
+    This is the way to go for content changing every frame, like videos or canvases.
+
+    `origin` is the position of the region in the image, in pixels.
+    The data must be in the format the texture was created with.
+    Panics if the region is out of the image or if the data is not consistent with the region dimensions.
+    **/
+    pub fn update_texture(
+        &mut self,
+        texture_id: &TextureId,
+        origin: (u32, u32),
+        dimensions: (u32, u32),
+        image_data: &[u8],
+    ) {
+        let size = texture_id.size();
+        let fits = |origin: u32, dimension: u32, size: u32| match origin.checked_add(dimension) {
+            Some(end) => end <= size,
+            None => false,
+        };
+        assert!(
+            fits(origin.0, dimensions.0, size.0) && fits(origin.1, dimensions.1, size.1),
+            "The updated region must be inside of the texture"
+        );
+        assert_eq!(
+            dimensions.0 as usize
+                * dimensions.1 as usize
+                * texture_id.format().bytes_per_pixel() as usize,
+            image_data.len()
+        );
+        if dimensions.0 == 0 || dimensions.1 == 0 {
+            return;
+        }
+
+        let position = (
+            texture_id.origin.0 + origin.0,
+            texture_id.origin.1 + origin.1,
+        );
+        if !texture_id.is_in_atlas() {
+            self.write_texture(texture_id.texture, 0, position, dimensions, image_data);
+            if dimensions == size {
+                self.write_mipmaps(texture_id, image_data);
+            }
+            return;
+        }
+
+        // The padding of atlas images must be updated along with their edges
+        let padding = texture::ATLAS_PADDING;
+        let padded = texture::pad_image(dimensions, image_data, padding);
+        let left = if origin.0 == 0 { 0 } else { padding };
+        let top = if origin.1 == 0 { 0 } else { padding };
+        let right = if origin.0 + dimensions.0 == size.0 {
+            dimensions.0 + 2 * padding
+        } else {
+            dimensions.0 + padding
+        };
+        let bottom = if origin.1 + dimensions.1 == size.1 {
+            dimensions.1 + 2 * padding
+        } else {
+            dimensions.1 + padding
+        };
+        let cropped = texture::crop(
+            (dimensions.0 + 2 * padding, dimensions.1 + 2 * padding),
+            &padded,
+            4,
+            (left, top, right - left, bottom - top),
+        );
+        self.write_texture(
+            texture_id.texture,
+            0,
+            (position.0 + left - padding, position.1 + top - padding),
+            (right - left, bottom - top),
+            &cropped,
+        );
+    }
+
+    /**
+    Same as [WgpuBackend::create_texture], but small images are packed together in a shared texture (an atlas).
+    Images of an atlas are drawn all at once, which is much faster when there are many of them, like icons.
+    Images too large to fit in an atlas get their own texture.
+    **/
+    pub fn create_atlas_texture(
+        &mut self,
+        image_dimensions: (u32, u32),
+        image_rgba: &[u8],
+    ) -> TextureId {
         assert_eq!(
             image_dimensions.0 as usize * image_dimensions.1 as usize * 4,
             image_rgba.len()
         );
 
+        if image_dimensions.0 > texture::ATLAS_MAX_IMAGE_SIZE
+            || image_dimensions.1 > texture::ATLAS_MAX_IMAGE_SIZE
+            || image_dimensions.0 == 0
+            || image_dimensions.1 == 0
+        {
+            return self.create_texture(image_dimensions, image_rgba);
+        }
+
+        let id = self.texture_id_counter;
+        self.texture_id_counter += 1;
+
+        let mut allocation = None;
+        for atlas in &mut self.atlases {
+            allocation = atlas.allocate(id, image_dimensions);
+            if allocation.is_some() {
+                break;
+            }
+        }
+        let (texture_id, position) = match allocation {
+            Some(allocation) => allocation,
+            None => {
+                let atlas_texture = self.create_empty_texture(
+                    (texture::ATLAS_SIZE, texture::ATLAS_SIZE),
+                    &TextureOptions::default(),
+                );
+                let mut atlas = Atlas::new(atlas_texture);
+                let allocation = atlas
+                    .allocate(id, image_dimensions)
+                    .expect("Image should fit in an empty atlas");
+                self.atlases.push(atlas);
+                allocation
+            }
+        };
+
+        let padding = texture::ATLAS_PADDING;
+        let padded = texture::pad_image(image_dimensions, image_rgba, padding);
+        self.write_texture(
+            texture_id.texture,
+            0,
+            position,
+            (
+                image_dimensions.0 + 2 * padding,
+                image_dimensions.1 + 2 * padding,
+            ),
+            &padded,
+        );
+
+        texture_id
+    }
+
+    /// Creates a GPU texture and its bind group, without initializing its content.
+    fn create_empty_texture(
+        &mut self,
+        image_dimensions: (u32, u32),
+        options: &TextureOptions,
+    ) -> TextureId {
         let texture_size = wgpu::Extent3d {
             width: image_dimensions.0,
             height: image_dimensions.1,
@@ -759,31 +2417,40 @@ impl WgpuBackend {
 
         let texture = self.device.create_texture(&wgpu::TextureDescriptor {
             size: texture_size,
-            mip_level_count: 1,
+            mip_level_count: options.mip_level_count(image_dimensions),
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
-            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+            format: options.format.to_wgpu(),
+            usage: wgpu::TextureUsage::SAMPLED
+                | wgpu::TextureUsage::COPY_DST
+                | wgpu::TextureUsage::COPY_SRC,
             label: Some("Texture"),
         });
 
-        self.queue.write_texture(
-            wgpu::ImageCopyTexture {
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-            },
-            image_rgba,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: std::num::NonZeroU32::new(4 * image_dimensions.0),
-                rows_per_image: std::num::NonZeroU32::new(image_dimensions.1),
-            },
-            texture_size,
-        );
+        self.register_texture(texture, image_dimensions, options)
+    }
 
+    /// Creates the bind group of a GPU texture, and gives it a [TextureId].
+    fn register_texture(
+        &mut self,
+        texture: wgpu::Texture,
+        image_dimensions: (u32, u32),
+        options: &TextureOptions,
+    ) -> TextureId {
         let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
 
+        let sampler_key = (options.filter, options.wrap);
+        if !self.samplers.iter().any(|(key, _)| *key == sampler_key) {
+            let sampler = self.device.create_sampler(&options.sampler_descriptor());
+            self.samplers.push((sampler_key, sampler));
+        }
+        let sampler = &self
+            .samplers
+            .iter()
+            .find(|(key, _)| *key == sampler_key)
+            .expect("Sampler should have been created")
+            .1;
+
         let texture_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &self.texture_bind_group_layout,
             entries: &[
@@ -793,7 +2460,7 @@ impl WgpuBackend {
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&self.texture_sampler),
+                    resource: wgpu::BindingResource::Sampler(sampler),
                 },
             ],
             label: Some("texture_bind_group"),
@@ -801,7 +2468,8 @@ impl WgpuBackend {
 
         let texture_id_usize = self.texture_id_counter;
         self.texture_id_counter += 1;
-        let texture_id = TextureId::new(texture_id_usize);
+        let mut texture_id = TextureId::new(texture_id_usize, image_dimensions, options.format);
+        texture_id.mip_level_count = options.mip_level_count(image_dimensions);
         self.texture_bind_groups.push((
             texture_id_usize,
             texture_id.clone(),
@@ -812,67 +2480,574 @@ impl WgpuBackend {
         texture_id
     }
 
-    pub(crate) fn render(&mut self) -> Result<(), wgpu::SwapChainError> {
-        let frame = self.swap_chain.get_current_frame()?.output;
+    /// Generates and writes the smaller mip levels of a texture from its full size data.
+    fn write_mipmaps(&self, texture_id: &TextureId, image_data: &[u8]) {
+        let mut dimensions = texture_id.size();
+        let mut data = std::borrow::Cow::Borrowed(image_data);
+        for mip_level in 1..texture_id.mip_level_count {
+            let (next_dimensions, next_data) =
+                texture::downsample(dimensions, &data, texture_id.format());
+            self.write_texture(
+                texture_id.texture,
+                mip_level,
+                (0, 0),
+                next_dimensions,
+                &next_data,
+            );
+            dimensions = next_dimensions;
+            data = std::borrow::Cow::Owned(next_data);
+        }
+    }
+
+    /// Copies pixels to a region of a GPU texture.
+    fn write_texture(
+        &self,
+        texture: usize,
+        mip_level: u32,
+        origin: (u32, u32),
+        dimensions: (u32, u32),
+        image_data: &[u8],
+    ) {
+        let index = self
+            .texture_bind_groups
+            .binary_search_by_key(&texture, |(id, _, _, _)| *id)
+            .expect("Texture should exist");
+
+        self.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture_bind_groups[index].2,
+                mip_level,
+                origin: wgpu::Origin3d {
+                    x: origin.0,
+                    y: origin.1,
+                    z: 0,
+                },
+            },
+            image_data,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(
+                    image_data.len() as u32 / dimensions.1.max(1),
+                ),
+                rows_per_image: std::num::NonZeroU32::new(dimensions.1),
+            },
+            wgpu::Extent3d {
+                width: dimensions.0,
+                height: dimensions.1,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    /**
+    Creates a render pipeline with user provided shaders and vertices, to draw what built-in methods can't.
+    Draw with it using [WgpuBackend::add_custom].
+    See [CustomPipelineDescriptor] for what the shaders must do to respect transforms, clips and blend modes.
+    **/
+    pub fn create_custom_pipeline(
+        &mut self,
+        descriptor: CustomPipelineDescriptor,
+    ) -> CustomPipelineId {
+        let vs_module = self
+            .device
+            .create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some(descriptor.label),
+                source: descriptor.vertex_shader.source,
+                flags: wgpu::ShaderFlags::VALIDATION,
+            });
+        let fs_module = self
+            .device
+            .create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some(descriptor.label),
+                source: descriptor.fragment_shader.source,
+                flags: wgpu::ShaderFlags::VALIDATION,
+            });
+
+        // Pipeline specific uniforms are bound after the uniforms of the backend
+        let uniforms = match descriptor.uniforms_size {
+            0 => None,
+            size => Some(CustomUniforms::new(&self.device, size)),
+        };
+
+        let mut bind_group_layouts = vec![&self.uniform_bind_group_layout];
+        if let Some(uniforms) = &uniforms {
+            bind_group_layouts.push(&uniforms.layout);
+        }
+        let layout = self
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(descriptor.label),
+                bind_group_layouts: &bind_group_layouts,
+                push_constant_ranges: &[],
+            });
+
+        let vertex_stride = descriptor.vertex_layout.array_stride;
+        let pipelines = create_blended_pipelines(
+            &self.device,
+            descriptor.label,
+            &layout,
+            (&vs_module, descriptor.vertex_shader.entry_point),
+            (&fs_module, descriptor.fragment_shader.entry_point),
+            descriptor.vertex_layout,
+            self.render_format,
+            self.sample_count,
+        );
+
+        self.custom_pipelines.push(CustomPipeline {
+            pipelines,
+            vertex_stride,
+            uniforms,
+        });
+        CustomPipelineId(self.custom_pipelines.len() - 1)
+    }
+
+    /**
+    Sets the value of the uniforms specific to a custom pipeline (see [CustomPipelineDescriptor::uniforms_size]).
+    The value is used by the draws of the pipeline made after this call, until it is set again, so each draw can have its own value.
+    Panics if the pipeline has no uniforms, or if the data does not have their size.
+    **/
+    pub fn write_custom_uniforms(&mut self, pipeline: CustomPipelineId, data: &[u8]) {
+        self.custom_pipelines[pipeline.0]
+            .uniforms
+            .as_mut()
+            .expect("The pipeline has no uniforms")
+            .staging
+            .set(data);
+    }
+
+    /**
+    Draws vertices with a pipeline created by [WgpuBackend::create_custom_pipeline].
+    The vertices must match the `vertex_layout` of the pipeline, and form a triangle list.
+    They are drawn on top of everything drawn before, so many vertices should be drawn at once rather than one shape at a time.
+    Panics if the size of the vertices is not a multiple of the `array_stride` of the layout.
+    **/
+    pub fn add_custom<V: bytemuck::Pod>(&mut self, pipeline: CustomPipelineId, vertices: &[V]) {
+        let bytes: &[u8] = bytemuck::cast_slice(vertices);
+        if bytes.is_empty() {
+            return;
+        }
+
+        let custom_pipeline = &mut self.custom_pipelines[pipeline.0];
+        assert_eq!(
+            bytes.len() as u64 % custom_pipeline.vertex_stride.max(1),
+            0,
+            "The vertices do not match the vertex layout of the pipeline"
+        );
+        let uniforms_offset = custom_pipeline
+            .uniforms
+            .as_mut()
+            .map(|uniforms| uniforms.staging.stage());
+        let vertices = custom::push_vertices(&mut self.custom_vertices, bytes);
+        self.custom_draws.push(CustomDraw {
+            pipeline,
+            vertices,
+            transform: self.transform(),
+            uniforms_offset,
+        });
+
+        // What is drawn next must be drawn on top
+        self.push_layer(None);
+    }
+
+    /**
+    Reads back the last rendered frame, as displayed in the window.
+    Widgets draw before the frame is rendered, so calling this while drawing returns the previous frame.
+    This waits for the GPU to be done, so it should not be called every frame.
+    **/
+    pub fn read_frame(&self) -> Screenshot {
+        self.read_texture_region(
+            &self.targets.scene_texture,
+            (0, 0),
+            (self.sc_desc.width, self.sc_desc.height),
+            TextureFormat::from_wgpu(self.render_format),
+            self.surface.is_none(),
+        )
+    }
+
+    /**
+    Reads back the pixels of a texture, like one returned by [WgpuBackend::end_offscreen_layer].
+    Offscreen layers are rendered along with the frame, so they can only be read once the frame they were drawn in has been rendered.
+    This waits for the GPU to be done, so it should not be called every frame.
+    **/
+    pub fn read_texture(&self, texture_id: &TextureId) -> Screenshot {
+        let index = self
+            .texture_bind_groups
+            .binary_search_by_key(&texture_id.texture, |(id, _, _, _)| *id)
+            .expect("Texture should exist");
+        self.read_texture_region(
+            &self.texture_bind_groups[index].2,
+            texture_id.origin,
+            texture_id.size(),
+            texture_id.format(),
+            texture_id.premultiplied,
+        )
+    }
+
+    /// Copies a region of a GPU texture to a buffer, and waits for it to be readable.
+    fn read_texture_region(
+        &self,
+        texture: &wgpu::Texture,
+        origin: (u32, u32),
+        size: (u32, u32),
+        format: TextureFormat,
+        premultiplied: bool,
+    ) -> Screenshot {
+        if size.0 == 0 || size.1 == 0 {
+            return readback::to_screenshot(size, format, premultiplied, &[]);
+        }
+
+        let padded_bytes_per_row =
+            readback::padded_bytes_per_row(size.0 * format.bytes_per_pixel());
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Readback Buffer"),
+            size: (padded_bytes_per_row * size.1) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+
         let mut encoder = self
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render Encoder"),
+                label: Some("Readback Encoder"),
             });
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: origin.0,
+                    y: origin.1,
+                    z: 0,
+                },
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(padded_bytes_per_row),
+                    rows_per_image: std::num::NonZeroU32::new(size.1),
+                },
+            },
+            wgpu::Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.queue.submit(std::iter::once(encoder.finish()));
 
-        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("Render Pass"),
+        let slice = buffer.slice(..);
+        let mapping = slice.map_async(wgpu::MapMode::Read);
+        self.device.poll(wgpu::Maintain::Wait);
+        futures::executor::block_on(mapping).expect("Failed to read the texture back");
+        let screenshot =
+            readback::to_screenshot(size, format, premultiplied, &slice.get_mapped_range());
+        buffer.unmap();
+        screenshot
+    }
+
+    /// The range of text vertices belonging to a layer.
+    fn text_layer_range(&self, layer: usize) -> std::ops::Range<u32> {
+        let total = self.last_text_vertices_count;
+        let start = match layer {
+            0 => 0,
+            layer => self
+                .text_layer_ends
+                .get(layer - 1)
+                .copied()
+                .unwrap_or(total),
+        };
+        let end = self.text_layer_ends.get(layer).copied().unwrap_or(total);
+        start..end
+    }
+
+    pub(crate) fn render(&mut self) -> Result<(), wgpu::SwapChainError> {
+        let (_, swap_chain) = self
+            .surface
+            .as_ref()
+            .expect("The backend has no window, use render_to_view or render_into_pass instead");
+        let frame = swap_chain.get_current_frame()?.output;
+        self.render_to_view(&frame.view);
+        Ok(())
+    }
+
+    /**
+    Renders the frame to a texture view of the caller, instead of the window.
+    The texture must have the format the backend was created with, and the size it was last resized to.
+    **/
+    pub fn render_to_view(&mut self, view: &wgpu::TextureView) {
+        let mut encoder = self.encode_scene();
+        let mut blit_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Blit Pass"),
             color_attachments: &[wgpu::RenderPassColorAttachment {
-                view: &frame.view,
+                view,
                 resolve_target: None,
                 ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    load: wgpu::LoadOp::Load,
                     store: true,
                 },
             }],
             depth_stencil_attachment: None,
         });
+        self.blit(&mut blit_pass);
+        std::mem::drop(blit_pass);
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+        self.end_frame();
+    }
+
+    /**
+    Renders the frame in a render pass of the caller, to draw on top of a 3D viewport for example.
+    The layers of the frame are rendered and submitted right away, and only their final copy is recorded in the pass.
+    The pass must target a texture with the format the backend was created with, without multisampling.
+    The commands of the pass must be submitted before the next frame is rendered.
+    **/
+    pub fn render_into_pass<'a>(&'a mut self, render_pass: &mut wgpu::RenderPass<'a>) {
+        let encoder = self.encode_scene();
+        self.queue.submit(std::iter::once(encoder.finish()));
+        self.end_frame();
+
+        let backend: &'a WgpuBackend = self;
+        backend.blit(render_pass);
+    }
 
-        render_pass.set_pipeline(&self.render_pipeline);
-        render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
-        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        render_pass.draw(0..self.vertices.len() as u32, 0..1);
-
-        if !self.images.is_empty() {
-            render_pass.set_pipeline(&self.texture_render_pipeline);
-            render_pass.set_vertex_buffer(0, self.texture_vertex_buffer.slice(..));
-
-            let mut image_id = 0;
-            'image_rendering: for (id, _, _, texture_bind_group) in &self.texture_bind_groups {
-                if *self.images[image_id].0.id == *id {
-                    render_pass.set_bind_group(1, texture_bind_group, &[]);
-                    while *self.images[image_id].0.id == *id {
-                        render_pass.draw((image_id * 6) as u32..((image_id + 1) * 6) as u32, 0..1);
-                        image_id += 1;
-                        if image_id >= self.images.len() {
-                            break 'image_rendering;
+    /// Copies the scene to the target of a render pass.
+    fn blit<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        render_pass.set_pipeline(&self.blit_render_pipeline);
+        render_pass.set_bind_group(0, &self.targets.scene_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    /// Records the passes rendering the layers of the frame to the scene texture and to offscreen targets.
+    fn encode_scene(&self) -> wgpu::CommandEncoder {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Render Encoder"),
+            });
+
+        let mut blur_index = 0;
+        let mut custom_uniforms_index = self.layers.len()
+            + self
+                .layers
+                .iter()
+                .filter(|layer| layer.backdrop_blur.is_some())
+                .count();
+        let mut cleared_targets = vec![false; self.offscreen_targets.len()];
+        let mut first_layer = 0;
+        while first_layer < self.layers.len() {
+            // Consecutive layers drawing to the same target share a pass, so that clips and blend modes don't each resolve the multisampled framebuffer
+            // Backdrop blurs need what the previous layers have drawn, so they begin a new pass
+            let target = self.layers[first_layer].target;
+            let last_layer = (first_layer + 1..self.layers.len())
+                .find(|i| {
+                    self.layers[*i].target != target || self.layers[*i].backdrop_blur.is_some()
+                })
+                .unwrap_or(self.layers.len());
+
+            // Blur the backdrop horizontally, then vertically
+            if self.layers[first_layer].backdrop_blur.is_some() {
+                let sources = [
+                    &self.targets.scene_bind_group,
+                    &self.targets.blur_bind_groups[0],
+                ];
+                for (pass, source) in sources.iter().enumerate() {
+                    let mut blur_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("Blur Pass"),
+                        color_attachments: &[wgpu::RenderPassColorAttachment {
+                            view: &self.targets.blur_views[pass],
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                                store: true,
+                            },
+                        }],
+                        depth_stencil_attachment: None,
+                    });
+
+                    let first_vertex = ((blur_index * 2 + pass) * 6) as u32;
+                    blur_pass.set_pipeline(&self.blur_render_pipeline);
+                    blur_pass.set_bind_group(0, source, &[]);
+                    blur_pass.set_vertex_buffer(0, self.blur_vertex_buffer.slice(..));
+                    blur_pass.draw(first_vertex..first_vertex + 6, 0..1);
+                }
+            }
+
+            // When multisampling, we draw on the multisampled framebuffer and resolve it into the scene
+            // Targets are cleared by the first layer drawing to them
+            let mut render_pass =
+                self.begin_layer_pass(&mut encoder, first_layer, &mut cleared_targets);
+            for (i, layer) in self
+                .layers
+                .iter()
+                .enumerate()
+                .take(last_layer)
+                .skip(first_layer)
+            {
+                let next_layer = self.layers.get(i + 1);
+                let vertices = layer.first_vertex
+                    ..next_layer
+                        .map(|next| next.first_vertex)
+                        .unwrap_or(self.vertices.len() as u32);
+                let images = layer.first_image
+                    ..next_layer
+                        .map(|next| next.first_image)
+                        .unwrap_or_else(|| self.images.len());
+                let text_vertices = self.text_layer_range(i);
+                let custom_draws = layer.first_custom_draw
+                    ..next_layer
+                        .map(|next| next.first_custom_draw)
+                        .unwrap_or_else(|| self.custom_draws.len());
+
+                let blend_mode = layer.blend_mode;
+                render_pass.set_bind_group(0, &self.uniform_bind_group, &[Uniforms::offset(i)]);
+
+                if layer.backdrop_blur.is_some() {
+                    let first_vertex = ((self.images.len() + blur_index) * 6) as u32;
+                    render_pass.set_bind_group(
+                        0,
+                        &self.uniform_bind_group,
+                        &[Uniforms::offset(self.layers.len() + blur_index)],
+                    );
+                    render_pass
+                        .set_pipeline(&self.texture_render_pipelines[BlendMode::Normal.index()]);
+                    render_pass.set_bind_group(1, &self.targets.blur_bind_groups[1], &[]);
+                    render_pass.set_vertex_buffer(0, self.texture_vertex_buffer.slice(..));
+                    render_pass.draw(first_vertex..first_vertex + 6, 0..1);
+                    blur_index += 1;
+                    render_pass.set_bind_group(0, &self.uniform_bind_group, &[Uniforms::offset(i)]);
+                }
+
+                if !vertices.is_empty() {
+                    render_pass.set_pipeline(&self.render_pipelines[blend_mode.index()]);
+                    render_pass.set_bind_group(1, &self.paint_bind_group, &[]);
+                    render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+                    render_pass.draw(vertices, 0..1);
+                }
+
+                if !images.is_empty() {
+                    render_pass.set_pipeline(&self.texture_render_pipelines[blend_mode.index()]);
+                    render_pass.set_vertex_buffer(0, self.texture_vertex_buffer.slice(..));
+
+                    // Images using the same texture are consecutive and drawn at once
+                    let mut image = images.start;
+                    while image < images.end {
+                        let id = self.images[image].0.texture;
+                        let batch_end = (image..images.end)
+                            .find(|i| self.images[*i].0.texture != id)
+                            .unwrap_or(images.end);
+
+                        if let Ok(index) = self
+                            .texture_bind_groups
+                            .binary_search_by_key(&id, |(id, _, _, _)| *id)
+                        {
+                            render_pass.set_bind_group(1, &self.texture_bind_groups[index].3, &[]);
+                            render_pass.draw((image * 6) as u32..(batch_end * 6) as u32, 0..1);
                         }
+                        image = batch_end;
                     }
                 }
-            }
-        }
 
-        if self.has_text {
-            render_pass.set_pipeline(&self.text_render_pipeline);
-            render_pass.set_bind_group(1, &self.text_bind_group, &[]);
-            render_pass.set_vertex_buffer(0, self.text_vertex_buffer.slice(..));
-            render_pass.draw(0..self.last_text_vertices_count, 0..1);
+                if self.has_text && !text_vertices.is_empty() {
+                    render_pass.set_pipeline(&self.text_render_pipelines[blend_mode.index()]);
+                    render_pass.set_bind_group(1, &self.text_bind_group, &[]);
+                    render_pass.set_vertex_buffer(0, self.text_vertex_buffer.slice(..));
+                    render_pass.draw(text_vertices, 0..1);
+                }
 
-            self.has_text = false;
+                for draw in &self.custom_draws[custom_draws] {
+                    let pipeline = &self.custom_pipelines[draw.pipeline.0];
+                    render_pass.set_pipeline(&pipeline.pipelines[blend_mode.index()]);
+                    render_pass.set_bind_group(
+                        0,
+                        &self.uniform_bind_group,
+                        &[Uniforms::offset(custom_uniforms_index)],
+                    );
+                    if let (Some(uniforms), Some(offset)) =
+                        (&pipeline.uniforms, draw.uniforms_offset)
+                    {
+                        render_pass.set_bind_group(1, &uniforms.bind_group, &[offset]);
+                    }
+                    render_pass.set_vertex_buffer(
+                        0,
+                        self.custom_vertex_buffer.slice(draw.vertices.clone()),
+                    );
+                    let vertex_count =
+                        (draw.vertices.end - draw.vertices.start) / pipeline.vertex_stride.max(1);
+                    render_pass.draw(0..vertex_count as u32, 0..1);
+                    custom_uniforms_index += 1;
+                }
+            }
+            first_layer = last_layer;
         }
 
-        std::mem::drop(render_pass);
+        encoder
+    }
 
-        self.queue.submit(std::iter::once(encoder.finish()));
+    /// Begins the pass drawing a layer and the next ones that share its target.
+    fn begin_layer_pass<'a>(
+        &'a self,
+        encoder: &'a mut wgpu::CommandEncoder,
+        i: usize,
+        cleared_targets: &mut [bool],
+    ) -> wgpu::RenderPass<'a> {
+        let (view, resolve_target, load) = match self.layers[i].target {
+            Some(target) => {
+                let offscreen_target = &self.offscreen_targets[target];
+                let (view, resolve_target) = offscreen_target.color_attachment();
+                let load = if cleared_targets[target] {
+                    wgpu::LoadOp::Load
+                } else {
+                    wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT)
+                };
+                cleared_targets[target] = true;
+                (view, resolve_target, load)
+            }
+            None => {
+                let (view, resolve_target) = self.targets.color_attachment();
+                let load = match (i, &self.surface) {
+                    (0, Some(_)) => wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    (0, None) => wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    _ => wgpu::LoadOp::Load,
+                };
+                (view, resolve_target, load)
+            }
+        };
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target,
+                ops: wgpu::Operations { load, store: true },
+            }],
+            depth_stencil_attachment: None,
+        })
+    }
+
+    /// Forgets what has been drawn during the frame.
+    fn end_frame(&mut self) {
         self.vertices.clear();
+        self.paints.truncate(1);
+        self.color_stops.truncate(1);
         self.images.clear();
-
-        Ok(())
+        self.custom_draws.clear();
+        self.custom_vertices.clear();
+        for pipeline in &mut self.custom_pipelines {
+            if let Some(uniforms) = &mut pipeline.uniforms {
+                uniforms.staging.clear();
+            }
+        }
+        self.layers.truncate(1);
+        // Targets not reused during the frame are dropped, and their textures with them
+        self.offscreen_pool = std::mem::take(&mut self.offscreen_targets);
+        self.offscreen_stack.clear();
+        self.blend_mode = BlendMode::default();
+        self.transforms.truncate(1);
+        self.clips.truncate(1);
+        self.text_sections.clear();
+        self.glyph_quads.clear();
+        self.has_text = false;
+        self.color_atlas.evict_if_full();
     }
 }
 
@@ -898,4 +3073,12 @@ mod tests {
             (0.0, -1.0)
         );
     }
+
+    #[test]
+    fn test_uniforms_alignment() {
+        assert_eq!(
+            size_of::<Uniforms>() as wgpu::BufferAddress,
+            wgpu::BIND_BUFFER_ALIGNMENT
+        );
+    }
 }
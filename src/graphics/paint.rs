@@ -0,0 +1,337 @@
+use super::Color;
+use crate::rect::Rect;
+
+/// Defines how a gradient is extended outside of its `0.0..=1.0` range.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SpreadMode {
+    /// The colors of the first and last stops are extended. (default)
+    #[default]
+    Pad,
+    /// The gradient is repeated.
+    Repeat,
+    /// The gradient is repeated, reversing its direction every other time.
+    Reflect,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorStop {
+    /// Position of the stop along the gradient, usually between `0.0` and `1.0`.
+    pub offset: f32,
+    pub color: [f32; 4],
+}
+
+/// An ordered list of [ColorStop]s, along with the [SpreadMode] to use outside of them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gradient {
+    stops: Vec<ColorStop>,
+    spread: SpreadMode,
+}
+
+impl Gradient {
+    /// Creates a gradient from its stops, which don't need to be sorted.
+    /// Stops with a NaN offset are placed at `0.0`.
+    /// Panics if there is no stop.
+    pub fn new(mut stops: Vec<ColorStop>) -> Gradient {
+        assert!(
+            !stops.is_empty(),
+            "A gradient needs at least one color stop"
+        );
+        for stop in &mut stops {
+            if stop.offset.is_nan() {
+                stop.offset = 0.0;
+            }
+        }
+        stops.sort_by(|a, b| a.offset.total_cmp(&b.offset));
+        Gradient {
+            stops,
+            spread: SpreadMode::default(),
+        }
+    }
+
+    pub fn with_spread(mut self, spread: SpreadMode) -> Gradient {
+        self.spread = spread;
+        self
+    }
+
+    pub fn stops(&self) -> &[ColorStop] {
+        &self.stops
+    }
+
+    pub fn spread(&self) -> SpreadMode {
+        self.spread
+    }
+
+    /// Computes the color at position `t` along the gradient.
+    /// Like in CSS, colors are interpolated between their sRGB components.
+    /// This is the same computation as the one done on the GPU by the shape shader.
+    pub fn color_at(&self, t: f32) -> [f32; 4] {
+        let t = match self.spread {
+            SpreadMode::Pad => t.clamp(0.0, 1.0),
+            SpreadMode::Repeat => t - t.floor(),
+            SpreadMode::Reflect => 1.0 - ((t - 2.0 * (t / 2.0).floor()) - 1.0).abs(),
+        };
+
+        let mut previous = &self.stops[0];
+        if t <= previous.offset {
+            return previous.color;
+        }
+        for next in &self.stops[1..] {
+            if t <= next.offset {
+                let factor = (t - previous.offset) / (next.offset - previous.offset).max(0.000001);
+                let mut color = [0.0; 4];
+                for (i, channel) in color.iter_mut().enumerate() {
+                    *channel = previous.color[i] + (next.color[i] - previous.color[i]) * factor;
+                }
+                return color;
+            }
+            previous = next;
+        }
+        previous.color
+    }
+}
+
+/// Describes how the inside of a shape is filled.
+///
+/// Coordinates are in pixels, relative to the screen.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Paint {
+    /// A single color, in sRGB (see [Color]).
+    Solid([f32; 4]),
+    /// Colors vary along the line from `start` (position `0.0`) to `end` (position `1.0`).
+    LinearGradient {
+        start: (f32, f32),
+        end: (f32, f32),
+        gradient: Gradient,
+    },
+    /// Colors vary with the distance to `center`, reaching position `1.0` at `radius`.
+    RadialGradient {
+        center: (f32, f32),
+        radius: f32,
+        gradient: Gradient,
+    },
+    /// Colors vary with the angle around `center`.
+    /// The gradient starts at `angle` (in radians, `0.0` pointing to the right) and goes clockwise.
+    ConicGradient {
+        center: (f32, f32),
+        angle: f32,
+        gradient: Gradient,
+    },
+}
+
+impl From<[f32; 4]> for Paint {
+    fn from(color: [f32; 4]) -> Self {
+        Paint::Solid(color)
+    }
+}
+
+impl From<Color> for Paint {
+    fn from(color: Color) -> Self {
+        Paint::Solid(color.to_srgb())
+    }
+}
+
+/// GPU representation of a [Paint] or of a [Shadow].
+/// Must match the `Paint` struct in shader.frag (std430 layout).
+///
+/// | kind | meaning | p0 | p1 | f0 | f1 |
+/// |---|---|---|---|---|---|
+/// | 0 | vertex color | | | | |
+/// | 1 | linear gradient | start | end | | |
+/// | 2 | radial gradient | center | | radius | |
+/// | 3 | conic gradient | center | | | angle |
+/// | 4 | shadow | rect min | rect max | corner radius | sigma |
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(super) struct GpuPaint {
+    kind: u32,
+    spread: u32,
+    first_stop: u32,
+    stop_count: u32,
+    p0: [f32; 2],
+    p1: [f32; 2],
+    f0: f32,
+    f1: f32,
+}
+
+/// GPU representation of a [ColorStop].
+/// Must match the `ColorStop` struct in shader.frag (std430 layout).
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(super) struct GpuColorStop {
+    color: [f32; 4],
+    offset: f32,
+    _padding: [f32; 3],
+}
+
+impl GpuPaint {
+    /// The paint at index 0 of the paint buffer.
+    /// It tells the shader to use the color of the vertices.
+    pub(super) const VERTEX_COLOR: GpuPaint = GpuPaint {
+        kind: 0,
+        spread: 0,
+        first_stop: 0,
+        stop_count: 1,
+        p0: [0.0; 2],
+        p1: [0.0; 2],
+        f0: 0.0,
+        f1: 0.0,
+    };
+
+    /// Converts a [Paint] and appends its stops to `stops`.
+    /// [Paint::Solid] is converted to a single-stop gradient, but it is cheaper to use vertex colors instead.
+    pub(super) fn from_paint(paint: &Paint, stops: &mut Vec<GpuColorStop>) -> GpuPaint {
+        let solid_gradient;
+        let (kind, p0, p1, f0, f1, gradient) = match paint {
+            Paint::Solid(color) => {
+                solid_gradient = Gradient::new(vec![ColorStop {
+                    offset: 0.0,
+                    color: *color,
+                }]);
+                (1, (0.0, 0.0), (0.0, 0.0), 0.0, 0.0, &solid_gradient)
+            }
+            Paint::LinearGradient {
+                start,
+                end,
+                gradient,
+            } => (1, *start, *end, 0.0, 0.0, gradient),
+            Paint::RadialGradient {
+                center,
+                radius,
+                gradient,
+            } => (2, *center, (0.0, 0.0), *radius, 0.0, gradient),
+            Paint::ConicGradient {
+                center,
+                angle,
+                gradient,
+            } => (3, *center, (0.0, 0.0), 0.0, *angle, gradient),
+        };
+
+        let first_stop = stops.len() as u32;
+        stops.extend(gradient.stops.iter().map(|stop| GpuColorStop {
+            color: stop.color,
+            offset: stop.offset,
+            _padding: [0.0; 3],
+        }));
+
+        GpuPaint {
+            kind,
+            spread: match gradient.spread {
+                SpreadMode::Pad => 0,
+                SpreadMode::Repeat => 1,
+                SpreadMode::Reflect => 2,
+            },
+            first_stop,
+            stop_count: gradient.stops.len() as u32,
+            p0: [p0.0, p0.1],
+            p1: [p1.0, p1.1],
+            f0,
+            f1,
+        }
+    }
+
+    /// Paints the shadow of a rounded rectangle.
+    /// The shadowed `rect` must already be offset and spread.
+    pub(super) fn shadow(rect: Rect, corner_radius: f32, sigma: f32) -> GpuPaint {
+        GpuPaint {
+            kind: 4,
+            p0: [rect.min.0, rect.min.1],
+            p1: [rect.max.0, rect.max.1],
+            f0: corner_radius,
+            f1: sigma,
+            ..GpuPaint::VERTEX_COLOR
+        }
+    }
+}
+
+impl GpuColorStop {
+    /// Storage buffers cannot be empty, so this is always at index 0 of the stop buffer.
+    pub(super) const PLACEHOLDER: GpuColorStop = GpuColorStop {
+        color: [0.0; 4],
+        offset: 0.0,
+        _padding: [0.0; 3],
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+    const BLUE: [f32; 4] = [0.0, 0.0, 1.0, 1.0];
+
+    fn red_to_blue() -> Gradient {
+        Gradient::new(vec![
+            ColorStop {
+                offset: 1.0,
+                color: BLUE,
+            },
+            ColorStop {
+                offset: 0.0,
+                color: RED,
+            },
+        ])
+    }
+
+    #[test]
+    fn test_color_at() {
+        let gradient = red_to_blue();
+        assert_eq!(gradient.stops()[0].color, RED);
+        assert_eq!(gradient.color_at(0.0), RED);
+        assert_eq!(gradient.color_at(0.5), [0.5, 0.0, 0.5, 1.0]);
+        assert_eq!(gradient.color_at(1.0), BLUE);
+
+        let gradient = Gradient::new(vec![
+            ColorStop {
+                offset: 1.0,
+                color: BLUE,
+            },
+            ColorStop {
+                offset: f32::NAN,
+                color: RED,
+            },
+        ]);
+        assert_eq!(gradient.stops()[0].offset, 0.0);
+        assert_eq!(gradient.color_at(0.5), [0.5, 0.0, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn test_spread_modes() {
+        let pad = red_to_blue();
+        assert_eq!(pad.color_at(-1.0), RED);
+        assert_eq!(pad.color_at(1.25), BLUE);
+
+        let repeat = red_to_blue().with_spread(SpreadMode::Repeat);
+        assert_eq!(repeat.color_at(1.25), [0.75, 0.0, 0.25, 1.0]);
+        assert_eq!(repeat.color_at(-0.75), [0.75, 0.0, 0.25, 1.0]);
+
+        let reflect = red_to_blue().with_spread(SpreadMode::Reflect);
+        assert_eq!(reflect.color_at(1.25), [0.25, 0.0, 0.75, 1.0]);
+        assert_eq!(reflect.color_at(2.25), [0.75, 0.0, 0.25, 1.0]);
+    }
+
+    #[test]
+    fn test_gpu_paint() {
+        let mut stops = vec![GpuColorStop::PLACEHOLDER];
+        let paint = GpuPaint::from_paint(&Paint::Solid(RED), &mut stops);
+        assert_eq!(paint.kind, 1);
+        assert_eq!(paint.first_stop, 1);
+        assert_eq!(paint.stop_count, 1);
+
+        let paint = GpuPaint::from_paint(
+            &Paint::RadialGradient {
+                center: (10.0, 20.0),
+                radius: 5.0,
+                gradient: red_to_blue().with_spread(SpreadMode::Reflect),
+            },
+            &mut stops,
+        );
+        assert_eq!(paint.kind, 2);
+        assert_eq!(paint.spread, 2);
+        assert_eq!(paint.first_stop, 2);
+        assert_eq!(paint.stop_count, 2);
+        assert_eq!(paint.p0, [10.0, 20.0]);
+        assert_eq!(stops.len(), 4);
+        assert_eq!(std::mem::size_of::<GpuPaint>(), 40);
+        assert_eq!(std::mem::size_of::<GpuColorStop>(), 32);
+    }
+}
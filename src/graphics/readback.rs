@@ -0,0 +1,147 @@
+use super::texture::{linear_to_srgb, srgb_to_linear, TextureFormat};
+
+/// An image read back from the GPU, see [WgpuBackend::read_frame](super::WgpuBackend::read_frame).
+/// Pixels are stored row by row, as RGBA with straight (not premultiplied) alpha, 4 bytes per pixel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Screenshot {
+    /// Width and height, in pixels.
+    pub size: (u32, u32),
+    pub data: Vec<u8>,
+}
+
+impl Screenshot {
+    /// Returns the RGBA color of a pixel.
+    /// Panics if the pixel is out of the image.
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        assert!(
+            x < self.size.0 && y < self.size.1,
+            "The pixel must be inside of the image"
+        );
+        let index = 4 * (y as usize * self.size.0 as usize + x as usize);
+        [
+            self.data[index],
+            self.data[index + 1],
+            self.data[index + 2],
+            self.data[index + 3],
+        ]
+    }
+
+    /// Encodes the image as PNG.
+    #[cfg(feature = "png")]
+    pub fn write_png<W: std::io::Write>(&self, writer: W) -> Result<(), png::EncodingError> {
+        let mut encoder = png::Encoder::new(writer, self.size.0, self.size.1);
+        encoder.set_color(png::ColorType::RGBA);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.data)
+    }
+
+    /// Saves the image as a PNG file.
+    #[cfg(feature = "png")]
+    pub fn save_png<P: AsRef<std::path::Path>>(&self, path: P) -> Result<(), png::EncodingError> {
+        let file = std::fs::File::create(path)?;
+        self.write_png(std::io::BufWriter::new(file))
+    }
+}
+
+/// The number of bytes of each row of a texture copied to a buffer.
+/// Rows must be aligned to [wgpu::COPY_BYTES_PER_ROW_ALIGNMENT].
+pub(super) fn padded_bytes_per_row(unpadded_bytes_per_row: u32) -> u32 {
+    let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let padding = (alignment - unpadded_bytes_per_row % alignment) % alignment;
+    unpadded_bytes_per_row + padding
+}
+
+/// Converts pixels copied from a texture to a [Screenshot], removing the padding at the end of each row.
+/// Premultiplied colors (like those of offscreen layers) are converted back to straight alpha.
+pub(super) fn to_screenshot(
+    size: (u32, u32),
+    format: TextureFormat,
+    premultiplied: bool,
+    padded_data: &[u8],
+) -> Screenshot {
+    let bytes_per_pixel = format.bytes_per_pixel() as usize;
+    let padded_bytes_per_row = padded_bytes_per_row(size.0 * bytes_per_pixel as u32) as usize;
+
+    let mut data = Vec::with_capacity(4 * size.0 as usize * size.1 as usize);
+    for row in padded_data
+        .chunks(padded_bytes_per_row)
+        .take(size.1 as usize)
+    {
+        for pixel in row[..size.0 as usize * bytes_per_pixel].chunks(bytes_per_pixel) {
+            let mut rgba = match format {
+                TextureFormat::Rgba8Srgb | TextureFormat::Rgba8 => {
+                    [pixel[0], pixel[1], pixel[2], pixel[3]]
+                }
+                TextureFormat::Bgra8Srgb | TextureFormat::Bgra8 => {
+                    [pixel[2], pixel[1], pixel[0], pixel[3]]
+                }
+                // Single channel textures are drawn as opacity
+                TextureFormat::R8 => [255, 255, 255, pixel[0]],
+            };
+            let alpha = rgba[3] as u32;
+            if premultiplied && alpha > 0 && alpha < 255 {
+                // sRGB textures premultiply linear colors, so they are divided by the alpha in linear space
+                let srgb = matches!(format, TextureFormat::Rgba8Srgb | TextureFormat::Bgra8Srgb);
+                for channel in &mut rgba[..3] {
+                    *channel = match srgb {
+                        true => linear_to_srgb(srgb_to_linear(*channel) * 255.0 / alpha as f32),
+                        false => (*channel as u32 * 255 / alpha).min(255) as u8,
+                    };
+                }
+            }
+            data.extend_from_slice(&rgba);
+        }
+    }
+
+    Screenshot { size, data }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_row_padding() {
+        assert_eq!(padded_bytes_per_row(0), 0);
+        assert_eq!(padded_bytes_per_row(4), 256);
+        assert_eq!(padded_bytes_per_row(256), 256);
+        assert_eq!(padded_bytes_per_row(257), 512);
+    }
+
+    #[test]
+    fn test_to_screenshot() {
+        // Two BGRA pixels per row, padded to 256 bytes
+        let mut data = vec![0; 512];
+        data[..8].copy_from_slice(&[1, 2, 3, 255, 4, 5, 6, 255]);
+        data[256..264].copy_from_slice(&[7, 8, 9, 255, 10, 11, 12, 255]);
+        let screenshot = to_screenshot((2, 2), TextureFormat::Bgra8Srgb, false, &data);
+        assert_eq!(screenshot.data.len(), 16);
+        assert_eq!(screenshot.pixel(0, 0), [3, 2, 1, 255]);
+        assert_eq!(screenshot.pixel(1, 1), [12, 11, 10, 255]);
+
+        let screenshot = to_screenshot((1, 1), TextureFormat::Rgba8, true, &[50, 100, 0, 128]);
+        assert_eq!(screenshot.pixel(0, 0), [99, 199, 0, 128]);
+
+        // Mid gray at half opacity, premultiplied in linear space, reads back as mid gray
+        let screenshot = to_screenshot((1, 1), TextureFormat::Rgba8Srgb, true, &[93, 93, 93, 128]);
+        let [gray, _, _, alpha] = screenshot.pixel(0, 0);
+        assert!((127..=129).contains(&gray));
+        assert_eq!(alpha, 128);
+
+        let screenshot = to_screenshot((1, 1), TextureFormat::R8, false, &[42]);
+        assert_eq!(screenshot.pixel(0, 0), [255, 255, 255, 42]);
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn test_write_png() {
+        let screenshot = Screenshot {
+            size: (1, 1),
+            data: vec![255, 0, 0, 255],
+        };
+        let mut png = Vec::new();
+        screenshot.write_png(&mut png).unwrap();
+        assert_eq!(&png[1..4], b"PNG");
+    }
+}
@@ -0,0 +1,21 @@
+#version 450
+
+layout(location=0) in vec2 v_tex_coords;
+layout(location=0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform texture2D t_source;
+layout(set = 0, binding = 1) uniform sampler s_source;
+
+vec3 linear_to_srgb(vec3 color) {
+    color = max(color, vec3(0.0));
+    return mix(1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055, color * 12.92, step(color, vec3(0.0031308)));
+}
+
+// Copies the scene to a frame that is not sRGB, encoding the colors like an sRGB frame would do
+void main() {
+    vec4 color = texture(sampler2D(t_source, s_source), v_tex_coords);
+    if (color.a > 0.0) {
+        color.rgb = linear_to_srgb(color.rgb / color.a) * color.a;
+    }
+    f_color = color;
+}
@@ -0,0 +1,10 @@
+#version 450
+
+layout(location=0) out vec2 v_tex_coords;
+
+// Draws a triangle covering the whole screen, without any vertex buffer
+void main() {
+    vec2 tex_coords = vec2(float((gl_VertexIndex << 1) & 2), float(gl_VertexIndex & 2));
+    v_tex_coords = tex_coords;
+    gl_Position = vec4(tex_coords * vec2(2.0, -2.0) + vec2(-1.0, 1.0), 0.0, 1.0);
+}
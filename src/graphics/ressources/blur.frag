@@ -0,0 +1,27 @@
+#version 450
+
+layout(location=0) in vec2 v_tex_coords;
+layout(location=1) in vec2 v_direction;
+layout(location=2) in float v_radius;
+layout(location=0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform texture2D t_source;
+layout(set = 0, binding = 1) uniform sampler s_source;
+
+// One pass of a separable gaussian blur
+// v_direction is the size of a texel along the blurred axis
+void main() {
+    float sigma = max(v_radius / 2.0, 0.5);
+    int samples = int(ceil(v_radius));
+
+    vec4 sum = textureLod(sampler2D(t_source, s_source), v_tex_coords, 0.0);
+    float total_weight = 1.0;
+    for (int i = 1; i <= samples; i++) {
+        float weight = exp(-float(i * i) / (2.0 * sigma * sigma));
+        vec2 offset = v_direction * float(i);
+        sum += textureLod(sampler2D(t_source, s_source), v_tex_coords + offset, 0.0) * weight;
+        sum += textureLod(sampler2D(t_source, s_source), v_tex_coords - offset, 0.0) * weight;
+        total_weight += 2.0 * weight;
+    }
+    f_color = sum / total_weight;
+}
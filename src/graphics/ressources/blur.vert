@@ -0,0 +1,17 @@
+#version 450
+
+layout(location=0) in vec2 a_position;
+layout(location=1) in vec2 a_tex_coords;
+layout(location=2) in vec2 a_direction;
+layout(location=3) in float a_radius;
+
+layout(location=0) out vec2 v_tex_coords;
+layout(location=1) out vec2 v_direction;
+layout(location=2) out float v_radius;
+
+void main() {
+    v_tex_coords = a_tex_coords;
+    v_direction = a_direction;
+    v_radius = a_radius;
+    gl_Position = vec4(a_position, 0.0, 1.0);
+}
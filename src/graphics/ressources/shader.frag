@@ -1,8 +1,195 @@
 #version 450
 
 layout(location=0) in vec4 v_color;
+layout(location=1) in vec2 v_position;
+layout(location=2) flat in uint v_paint;
 layout(location=0) out vec4 f_color;
 
+// Must match GpuPaint in paint.rs
+struct Paint {
+    uint kind;
+    uint spread;
+    uint first_stop;
+    uint stop_count;
+    vec2 p0;
+    vec2 p1;
+    float f0;
+    float f1;
+};
+
+// Must match GpuColorStop in paint.rs
+struct ColorStop {
+    vec4 color;
+    float offset;
+};
+
+layout(set=1, binding=0) readonly buffer Paints {
+    Paint paints[];
+};
+
+layout(set=1, binding=1) readonly buffer ColorStops {
+    ColorStop stops[];
+};
+
+layout(set=0, binding=0)
+uniform Uniforms {
+    float screen_width;
+    float screen_height;
+    uint premultiplied_input;
+    uint clip_plane_count;
+    vec4 clip_planes[12];
+    vec4 transform[2];
+    uint encode_srgb;
+    float text_gamma;
+    float text_contrast;
+};
+
+// Clip planes are normalized, so their equations give distances in pixels
+// Edges are smoothed over one pixel, so that rotated clips are anti-aliased
+float clip_coverage() {
+    float coverage = 1.0;
+    for (uint i = 0u; i < clip_plane_count; i++) {
+        float distance = dot(clip_planes[i].xyz, vec3(gl_FragCoord.xy, 1.0));
+        coverage *= clamp(distance + 0.5, 0.0, 1.0);
+    }
+    return coverage;
+}
+
+// Colors are given in sRGB, and converted to linear space so that they are blended like light
+vec3 srgb_to_linear(vec3 color) {
+    color = max(color, vec3(0.0));
+    return mix(pow((color + 0.055) / 1.055, vec3(2.4)), color / 12.92, step(color, vec3(0.04045)));
+}
+
+vec3 linear_to_srgb(vec3 color) {
+    color = max(color, vec3(0.0));
+    return mix(1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055, color * 12.92, step(color, vec3(0.0031308)));
+}
+
+// Converts a color given to the backend to linear space, and premultiplies it by its alpha
+// Colors are output premultiplied, so that blend modes can be expressed with blend factors
+vec4 linear_premultiplied(vec4 color) {
+    if (premultiplied_input == 0u) {
+        return vec4(srgb_to_linear(color.rgb) * color.a, color.a);
+    }
+    // Premultiplied colors are converted without their alpha
+    if (color.a > 0.0) {
+        color.rgb = srgb_to_linear(color.rgb / color.a) * color.a;
+    }
+    return color;
+}
+
+// Targets that are not sRGB store colors as they are output, so colors are encoded like sRGB targets would do
+vec4 encode_output(vec4 color) {
+    if (encode_srgb != 0u && color.a > 0.0) {
+        color.rgb = linear_to_srgb(color.rgb / color.a) * color.a;
+    }
+    return color;
+}
+
+const uint KIND_SOLID = 0u;
+const uint KIND_LINEAR = 1u;
+const uint KIND_RADIAL = 2u;
+const uint KIND_SHADOW = 4u;
+
+const uint SPREAD_REPEAT = 1u;
+const uint SPREAD_REFLECT = 2u;
+
+const float TAU = 6.28318530718;
+const float PI = 3.14159265359;
+
+float gradient_position(Paint paint, vec2 position) {
+    if (paint.kind == KIND_LINEAR) {
+        vec2 direction = paint.p1 - paint.p0;
+        return dot(position - paint.p0, direction) / max(dot(direction, direction), 0.000001);
+    } else if (paint.kind == KIND_RADIAL) {
+        return length(position - paint.p0) / max(paint.f0, 0.000001);
+    } else {
+        vec2 delta = position - paint.p0;
+        return fract((atan(delta.y, delta.x) - paint.f1) / TAU);
+    }
+}
+
+float apply_spread(uint spread, float t) {
+    if (spread == SPREAD_REPEAT) {
+        return fract(t);
+    } else if (spread == SPREAD_REFLECT) {
+        return 1.0 - abs(mod(t, 2.0) - 1.0);
+    } else {
+        return clamp(t, 0.0, 1.0);
+    }
+}
+
+vec4 sample_stops(Paint paint, float t) {
+    ColorStop previous = stops[paint.first_stop];
+    if (t <= previous.offset) {
+        return previous.color;
+    }
+    for (uint i = 1u; i < paint.stop_count; i++) {
+        ColorStop next = stops[paint.first_stop + i];
+        if (t <= next.offset) {
+            float factor = (t - previous.offset) / max(next.offset - previous.offset, 0.000001);
+            return mix(previous.color, next.color, factor);
+        }
+        previous = next;
+    }
+    return previous.color;
+}
+
+// Shadows of rounded rectangles, computed analytically
+// See https://madebyevan.com/shaders/fast-rounded-rectangle-shadows/
+
+float gaussian(float x, float sigma) {
+    return exp(-(x * x) / (2.0 * sigma * sigma)) / (sqrt(2.0 * PI) * sigma);
+}
+
+vec2 approximate_erf(vec2 x) {
+    vec2 s = sign(x);
+    vec2 a = abs(x);
+    x = 1.0 + (0.278393 + (0.230389 + 0.078108 * (a * a)) * a) * a;
+    x *= x;
+    return s - s / (x * x);
+}
+
+float rounded_box_shadow_x(float x, float y, float sigma, float corner, vec2 half_size) {
+    float delta = min(half_size.y - corner - abs(y), 0.0);
+    float curved = half_size.x - corner + sqrt(max(0.0, corner * corner - delta * delta));
+    vec2 integral = 0.5 + 0.5 * approximate_erf((x + vec2(-curved, curved)) * (sqrt(0.5) / sigma));
+    return integral.y - integral.x;
+}
+
+float rounded_box_shadow(vec2 lower, vec2 upper, vec2 position, float sigma, float corner) {
+    vec2 center = (lower + upper) * 0.5;
+    vec2 half_size = (upper - lower) * 0.5;
+    corner = min(corner, min(half_size.x, half_size.y));
+    position -= center;
+
+    // The shadow is integrated along the Y axis with a few samples
+    float low = position.y - half_size.y;
+    float high = position.y + half_size.y;
+    float start = clamp(-3.0 * sigma, low, high);
+    float end = clamp(3.0 * sigma, low, high);
+    float step = (end - start) / 4.0;
+    float y = start + step * 0.5;
+    float value = 0.0;
+    for (int i = 0; i < 4; i++) {
+        value += rounded_box_shadow_x(position.x, position.y - y, sigma, corner, half_size) * gaussian(y, sigma) * step;
+        y += step;
+    }
+    return value;
+}
+
 void main() {
-    f_color = v_color;
+    Paint paint = paints[v_paint];
+    vec4 color = v_color;
+    float coverage = 1.0;
+    if (paint.kind == KIND_SHADOW) {
+        coverage = rounded_box_shadow(paint.p0, paint.p1, v_position, paint.f1, paint.f0);
+    } else if (paint.kind != KIND_SOLID) {
+        float t = apply_spread(paint.spread, gradient_position(paint, v_position));
+        color *= sample_stops(paint, t);
+    }
+
+    // Gradients are interpolated between sRGB colors, like in CSS
+    f_color = encode_output(linear_premultiplied(color) * coverage * clip_coverage());
 }
@@ -2,17 +2,26 @@
 
 layout(location=0) in vec2 a_position;
 layout(location=1) in vec4 a_color;
+layout(location=2) in uint a_paint;
+layout(location=3) in vec2 a_paint_position;
 
 layout(location=0) out vec4 v_color;
+layout(location=1) out vec2 v_position;
+layout(location=2) flat out uint v_paint;
 
 layout(set=0, binding=0)
 uniform Uniforms {
     float screen_width;
     float screen_height;
+    uint premultiplied_input;
+    uint clip_plane_count;
+    vec4 clip_planes[12];
 };
 
 void main() {
     v_color = a_color;
+    v_position = a_paint_position;
+    v_paint = a_paint;
     vec2 p = ((2.0 / vec2(screen_width, screen_height)) * a_position - 1.0) * vec2(1.0, -1.0);
     gl_Position = vec4(p, 0.0, 1.0);
 }
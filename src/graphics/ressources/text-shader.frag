@@ -2,12 +2,111 @@
 
 layout(location=0) in vec2 v_tex_coords;
 layout(location=1) in vec4 text_color;
+// The range of distances of the distance field in pixels (zero for glyphs of the glyph cache), the outline width, the glow radius, and 1 for color glyphs
+layout(location=2) in vec4 effects;
+layout(location=3) in vec4 outline_color;
+layout(location=4) in vec4 glow_color;
 layout(location=0) out vec4 f_color;
 
+layout(set=0, binding=0)
+uniform Uniforms {
+    float screen_width;
+    float screen_height;
+    uint premultiplied_input;
+    uint clip_plane_count;
+    vec4 clip_planes[12];
+    vec4 transform[2];
+    uint encode_srgb;
+    float text_gamma;
+    float text_contrast;
+};
+
+// Clip planes are normalized, so their equations give distances in pixels
+// Edges are smoothed over one pixel, so that rotated clips are anti-aliased
+float clip_coverage() {
+    float coverage = 1.0;
+    for (uint i = 0u; i < clip_plane_count; i++) {
+        float distance = dot(clip_planes[i].xyz, vec3(gl_FragCoord.xy, 1.0));
+        coverage *= clamp(distance + 0.5, 0.0, 1.0);
+    }
+    return coverage;
+}
+
+// Colors are given in sRGB, and converted to linear space so that they are blended like light
+vec3 srgb_to_linear(vec3 color) {
+    color = max(color, vec3(0.0));
+    return mix(pow((color + 0.055) / 1.055, vec3(2.4)), color / 12.92, step(color, vec3(0.04045)));
+}
+
+vec3 linear_to_srgb(vec3 color) {
+    color = max(color, vec3(0.0));
+    return mix(1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055, color * 12.92, step(color, vec3(0.0031308)));
+}
+
+// Converts a color given to the backend to linear space, and premultiplies it by its alpha
+// Colors are output premultiplied, so that blend modes can be expressed with blend factors
+vec4 linear_premultiplied(vec4 color) {
+    if (premultiplied_input == 0u) {
+        return vec4(srgb_to_linear(color.rgb) * color.a, color.a);
+    }
+    // Premultiplied colors are converted without their alpha
+    if (color.a > 0.0) {
+        color.rgb = srgb_to_linear(color.rgb / color.a) * color.a;
+    }
+    return color;
+}
+
+// Targets that are not sRGB store colors as they are output, so colors are encoded like sRGB targets would do
+vec4 encode_output(vec4 color) {
+    if (encode_srgb != 0u && color.a > 0.0) {
+        color.rgb = linear_to_srgb(color.rgb / color.a) * color.a;
+    }
+    return color;
+}
+
 layout(set = 1, binding = 0) uniform texture2D t_diffuse;
 layout(set = 1, binding = 1) uniform sampler s_diffuse;
+layout(set = 1, binding = 2) uniform texture2D t_distance_field;
+layout(set = 1, binding = 3) uniform sampler s_distance_field;
+// Color glyphs, premultiplied, sampled like distance fields since they are scaled too
+layout(set = 1, binding = 4) uniform texture2D t_color_glyphs;
+
+// Thickens light text, which looks thinner than dark text when blended in linear space, and raises the contrast of edges
+// See TextGamma in settings.rs
+float adjust_coverage(float coverage, vec4 color) {
+    float luminance = dot(color.rgb, vec3(0.2126, 0.7152, 0.0722)) / max(color.a, 0.0001);
+    coverage = pow(coverage, mix(1.0, 1.0 / text_gamma, clamp(luminance, 0.0, 1.0)));
+    return clamp(coverage + text_contrast * coverage * (1.0 - coverage), 0.0, 1.0);
+}
 
 void main() {
     vec4 p_color = texture(sampler2D(t_diffuse, s_diffuse), v_tex_coords);
-    f_color = text_color * vec4(1.0, 1.0, 1.0, p_color.r);
+    vec4 color = linear_premultiplied(text_color);
+
+    // The distance to the edge of the glyph in pixels, positive inside
+    // Derivatives are taken before branching, and give the size of a screen pixel so that edges stay sharp at any scale
+    float distance = (texture(sampler2D(t_distance_field, s_distance_field), v_tex_coords).r - 0.5) * effects.x;
+    float pixel = max(length(vec2(dFdx(distance), dFdy(distance))), 0.0001);
+
+    if (effects.w > 0.0) {
+        // Color glyphs keep their colors, unless they are grayscale bitmaps which are white
+        vec4 glyph = texture(sampler2D(t_color_glyphs, s_distance_field), v_tex_coords);
+        f_color = encode_output(glyph * color * clip_coverage());
+    } else if (effects.x > 0.0) {
+        vec4 result = vec4(0.0);
+        if (effects.z > 0.0) {
+            float glow = 1.0 - smoothstep(0.0, effects.z, -(distance + effects.y));
+            result = linear_premultiplied(glow_color) * glow;
+        }
+        if (effects.y > 0.0) {
+            float outline = clamp((distance + effects.y) / pixel + 0.5, 0.0, 1.0);
+            vec4 outline_premultiplied = linear_premultiplied(outline_color) * outline;
+            result = outline_premultiplied + result * (1.0 - outline_premultiplied.a);
+        }
+        float fill = adjust_coverage(clamp(distance / pixel + 0.5, 0.0, 1.0), color);
+        result = color * fill + result * (1.0 - color.a * fill);
+        f_color = encode_output(result * clip_coverage());
+    } else {
+        f_color = encode_output(color * adjust_coverage(p_color.r, color) * clip_coverage());
+    }
 }
@@ -3,19 +3,31 @@
 layout(location=0) in vec2 a_position;
 layout(location=1) in vec2 a_tex_coords;
 layout(location=2) in vec4 text_color;
+layout(location=3) in vec4 effects;
+layout(location=4) in vec4 outline_color;
+layout(location=5) in vec4 glow_color;
 
 layout(location=0) out vec2 v_tex_coords;
 layout(location=1) out vec4 text_color_out;
+layout(location=2) out vec4 effects_out;
+layout(location=3) out vec4 outline_color_out;
+layout(location=4) out vec4 glow_color_out;
 
 layout(set=0, binding=0)
 uniform Uniforms {
     float screen_width;
     float screen_height;
+    uint premultiplied_input;
+    uint clip_plane_count;
+    vec4 clip_planes[12];
 };
 
 void main() {
     v_tex_coords = a_tex_coords;
     text_color_out = text_color;
+    effects_out = effects;
+    outline_color_out = outline_color;
+    glow_color_out = glow_color;
     vec2 p = ((2.0 / vec2(screen_width, screen_height)) * a_position - 1.0) * vec2(1.0, -1.0);
     gl_Position = vec4(p, 0.0, 1.0);
 }
@@ -1,11 +1,86 @@
 #version 450
 
 layout(location=0) in vec2 v_tex_coords;
+layout(location=1) in vec4 v_color;
+layout(location=2) flat in uint v_kind;
 layout(location=0) out vec4 f_color;
 
+layout(set=0, binding=0)
+uniform Uniforms {
+    float screen_width;
+    float screen_height;
+    uint premultiplied_input;
+    uint clip_plane_count;
+    vec4 clip_planes[12];
+    vec4 transform[2];
+    uint encode_srgb;
+    float text_gamma;
+    float text_contrast;
+};
+
+// Clip planes are normalized, so their equations give distances in pixels
+// Edges are smoothed over one pixel, so that rotated clips are anti-aliased
+float clip_coverage() {
+    float coverage = 1.0;
+    for (uint i = 0u; i < clip_plane_count; i++) {
+        float distance = dot(clip_planes[i].xyz, vec3(gl_FragCoord.xy, 1.0));
+        coverage *= clamp(distance + 0.5, 0.0, 1.0);
+    }
+    return coverage;
+}
+
+// Colors are given in sRGB, and converted to linear space so that they are blended like light
+vec3 srgb_to_linear(vec3 color) {
+    color = max(color, vec3(0.0));
+    return mix(pow((color + 0.055) / 1.055, vec3(2.4)), color / 12.92, step(color, vec3(0.04045)));
+}
+
+vec3 linear_to_srgb(vec3 color) {
+    color = max(color, vec3(0.0));
+    return mix(1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055, color * 12.92, step(color, vec3(0.0031308)));
+}
+
+// Converts a color given to the backend to linear space, and premultiplies it by its alpha
+// Colors are output premultiplied, so that blend modes can be expressed with blend factors
+vec4 linear_premultiplied(vec4 color) {
+    if (premultiplied_input == 0u) {
+        return vec4(srgb_to_linear(color.rgb) * color.a, color.a);
+    }
+    // Premultiplied colors are converted without their alpha
+    if (color.a > 0.0) {
+        color.rgb = srgb_to_linear(color.rgb / color.a) * color.a;
+    }
+    return color;
+}
+
+// Targets that are not sRGB store colors as they are output, so colors are encoded like sRGB targets would do
+vec4 encode_output(vec4 color) {
+    if (encode_srgb != 0u && color.a > 0.0) {
+        color.rgb = linear_to_srgb(color.rgb / color.a) * color.a;
+    }
+    return color;
+}
+
 layout(set = 1, binding = 0) uniform texture2D t_diffuse;
 layout(set = 1, binding = 1) uniform sampler s_diffuse;
 
+const uint KIND_ALPHA_MASK = 1u;
+const uint KIND_PREMULTIPLIED = 2u;
+
 void main() {
-    f_color = texture(sampler2D(t_diffuse, s_diffuse), v_tex_coords);
+    vec4 texel = texture(sampler2D(t_diffuse, s_diffuse), v_tex_coords);
+    if (v_kind == KIND_ALPHA_MASK) {
+        // Single channel textures are used as opacity
+        texel = vec4(1.0, 1.0, 1.0, texel.r);
+    } else if (v_kind == KIND_PREMULTIPLIED && encode_srgb != 0u && texel.a > 0.0) {
+        // Offscreen layers have the format of the target, so their colors are encoded too
+        texel.rgb = srgb_to_linear(texel.rgb / texel.a) * texel.a;
+    }
+
+    // Colors of offscreen layers already are premultiplied
+    vec4 tint = linear_premultiplied(v_color);
+    if (v_kind == KIND_ALPHA_MASK || (v_kind != KIND_PREMULTIPLIED && premultiplied_input == 0u)) {
+        texel.rgb *= texel.a;
+    }
+    f_color = encode_output(texel * tint * clip_coverage());
 }
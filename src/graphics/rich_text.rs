@@ -0,0 +1,465 @@
+use super::font::FontStyle;
+use super::text::{Fonts, GlyphMetrics, TextMetrics, TextOptions};
+use crate::rect::Rect;
+use glyph_brush::ab_glyph::PxScale;
+use glyph_brush::{FontId, HorizontalAlign, Layout, Section, Text, VerticalAlign};
+
+/// A part of a [RichText] sharing the same style.
+/// Unset attributes are taken from the [TextOptions] the text is drawn with.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Span {
+    pub text: String,
+    /// Draws the span with the bold font of the family of [TextOptions::font], if one is registered (see [Fonts::query]).
+    pub bold: bool,
+    /// Draws the span with the italic font of the family of [TextOptions::font], if one is registered.
+    pub italic: bool,
+    /// Replaces [TextOptions::color].
+    pub color: Option<[f32; 4]>,
+    /// Replaces [TextOptions::scale].
+    pub scale: Option<f32>,
+    pub underline: bool,
+    pub strikethrough: bool,
+    /// The target of the span if it is a link, returned by [RichTextLayout::link_at].
+    pub link: Option<String>,
+    /// Reserves room for an object drawn by the caller, like an image, instead of drawing the text of the span.
+    /// It is an advance and an ascent above the baseline in pixels, see [Span::inline_box].
+    pub inline_box: Option<(f32, f32)>,
+}
+
+impl Span {
+    pub fn new(text: impl Into<String>) -> Span {
+        Span {
+            text: text.into(),
+            ..Span::default()
+        }
+    }
+
+    pub fn bold(mut self) -> Span {
+        self.bold = true;
+        self
+    }
+
+    pub fn italic(mut self) -> Span {
+        self.italic = true;
+        self
+    }
+
+    pub fn underline(mut self) -> Span {
+        self.underline = true;
+        self
+    }
+
+    pub fn strikethrough(mut self) -> Span {
+        self.strikethrough = true;
+        self
+    }
+
+    pub fn with_color(mut self, color: [f32; 4]) -> Span {
+        self.color = Some(color);
+        self
+    }
+
+    pub fn with_scale(mut self, scale: f32) -> Span {
+        self.scale = Some(scale);
+        self
+    }
+
+    /// A span reserving an advance and an ascent above the baseline, in pixels, for an object drawn by the caller.
+    /// The object is placed at the rectangle of the span in [RichTextLayout::inline_boxes].
+    /// Like a word, it doesn't break from the text around it, and lines are as high as text of that ascent.
+    pub fn inline_box(advance: f32, ascent: f32) -> Span {
+        Span {
+            inline_box: Some((advance, ascent)),
+            ..Span::default()
+        }
+    }
+
+    /// Makes the span a link, which is underlined.
+    pub fn with_link(mut self, link: impl Into<String>) -> Span {
+        self.link = Some(link.into());
+        self.underline = true;
+        self
+    }
+}
+
+/// A paragraph mixing styles, made of [Span]s.
+/// It is drawn with [WgpuBackend::add_rich_text](super::WgpuBackend::add_rich_text).
+///
+/// Images or widgets are placed inside of the paragraph with spans reserving room for them, see [Span::inline_box].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RichText {
+    pub spans: Vec<Span>,
+}
+
+impl RichText {
+    pub fn new() -> RichText {
+        RichText::default()
+    }
+
+    pub fn add_span(mut self, span: Span) -> RichText {
+        self.spans.push(span);
+        self
+    }
+
+    /// Lays the text out in a rectangle, like [WgpuBackend::add_rich_text](super::WgpuBackend::add_rich_text) draws it.
+    /// This can be used to know which span is under the cursor.
+    pub fn layout(&self, fonts: &Fonts, rect: Rect, options: &TextOptions) -> RichTextLayout {
+        // Bold and italic spans use other fonts of the same family
+        let family = fonts
+            .description(options.font)
+            .map(|description| description.family)
+            .unwrap_or_default();
+        let span_fonts: Vec<FontId> = self
+            .spans
+            .iter()
+            .map(|span| {
+                let weight = if span.bold { 700 } else { 400 };
+                let style = if span.italic {
+                    FontStyle::Italic
+                } else {
+                    FontStyle::Normal
+                };
+                match (span.bold, span.italic) {
+                    (false, false) => options.font,
+                    _ => fonts.query(&family, weight, style).unwrap_or(options.font),
+                }
+            })
+            .collect();
+
+        let inline_boxes = self
+            .spans
+            .iter()
+            .zip(&span_fonts)
+            .map(|(span, font)| span.inline_box.map(|size| fonts.inline_box(*font, size)))
+            .collect();
+
+        let mut layout = RichTextLayout {
+            metrics: TextMetrics::default(),
+            decorations: Vec::new(),
+            inline_boxes: Vec::new(),
+            position: (
+                rect.min.0
+                    + rect.width()
+                        * match options.h_align {
+                            HorizontalAlign::Left => 0.0,
+                            HorizontalAlign::Center => 0.5,
+                            HorizontalAlign::Right => 1.0,
+                        },
+                0.0,
+            ),
+            fonts: span_fonts,
+            boxes: inline_boxes,
+            links: self.spans.iter().map(|span| span.link.clone()).collect(),
+        };
+        let section = self.section(&layout, rect, options);
+        let mut metrics = match options.shaping {
+            true => fonts.measure_shaped_section(&section),
+            false => fonts.measure_section(&section),
+        };
+
+        // Lines are laid out from the top, and then moved to be aligned vertically
+        let offset = (rect.height() - metrics.bounds.max.1)
+            * match options.v_align {
+                VerticalAlign::Top => 0.0,
+                VerticalAlign::Center => 0.5,
+                VerticalAlign::Bottom => 1.0,
+            };
+        let top = rect.min.1 + offset;
+        translate(&mut metrics, top);
+        layout.position.1 = top;
+
+        for line in &metrics.lines {
+            let glyphs = &metrics.glyphs[line.glyphs.clone()];
+            for glyph in glyphs {
+                if let Some((_, ascent)) = self.spans[glyph.section_index].inline_box {
+                    layout.inline_boxes.push((
+                        glyph.section_index,
+                        Rect {
+                            min: (glyph.bounds.min.0, line.baseline - ascent.max(0.0)),
+                            max: (glyph.bounds.max.0, line.baseline),
+                        },
+                    ));
+                }
+            }
+
+            // Spaces at the end of lines are not decorated
+            let end = glyphs
+                .iter()
+                .rposition(|glyph| {
+                    let text = &self.spans[glyph.section_index].text;
+                    !text[glyph.byte_index..].starts_with(char::is_whitespace)
+                })
+                .map_or(0, |index| index + 1);
+
+            // Decorations cover the glyphs of a span that are next to each other on screen
+            // In bidirectional text, they are not the ones next to each other in the text
+            let mut glyphs: Vec<&GlyphMetrics> = glyphs[..end].iter().collect();
+            glyphs.sort_by(|a, b| a.bounds.min.0.total_cmp(&b.bounds.min.0));
+            let mut start = 0;
+            while start < glyphs.len() {
+                let span_index = glyphs[start].section_index;
+                let count = glyphs[start..]
+                    .iter()
+                    .take_while(|glyph| glyph.section_index == span_index)
+                    .count();
+                let span = &self.spans[span_index];
+                let (left, right) = glyphs[start..start + count].iter().fold(
+                    (f32::INFINITY, f32::NEG_INFINITY),
+                    |(left, right), glyph| {
+                        (left.min(glyph.bounds.min.0), right.max(glyph.bounds.max.0))
+                    },
+                );
+                let color = span.color.unwrap_or(options.color);
+                let decorations = fonts.decoration_metrics(
+                    layout.fonts[span_index],
+                    span.scale.unwrap_or(options.scale),
+                );
+                let mut decorate = |(offset, thickness): (f32, f32)| {
+                    let y = line.baseline + offset;
+                    layout.decorations.push((
+                        Rect {
+                            min: (left, y - thickness / 2.0),
+                            max: (right, y + thickness / 2.0),
+                        },
+                        color,
+                    ));
+                };
+                if span.underline {
+                    decorate(decorations.underline);
+                }
+                if span.strikethrough {
+                    decorate(decorations.strikethrough);
+                }
+                start += count;
+            }
+        }
+
+        layout.metrics = metrics;
+        layout
+    }
+
+    /// The section drawing the spans, with their first line at the top of the layout.
+    pub(super) fn section<'a>(
+        &'a self,
+        layout: &RichTextLayout,
+        rect: Rect,
+        options: &TextOptions,
+    ) -> Section<'a> {
+        let width = if options.wrap {
+            rect.width()
+        } else {
+            f32::INFINITY
+        };
+        let texts = self
+            .spans
+            .iter()
+            .zip(&layout.fonts)
+            .zip(&layout.boxes)
+            .map(|((span, font), inline_box)| match inline_box {
+                // Inline boxes are invisible spaces
+                Some((text, scale)) => Text::new(text)
+                    .with_font_id(*font)
+                    .with_scale(*scale)
+                    .with_color([0.0; 4]),
+                None => Text::new(&span.text)
+                    .with_font_id(*font)
+                    .with_scale(span.scale.unwrap_or(options.scale))
+                    .with_color(span.color.unwrap_or(options.color)),
+            })
+            .collect();
+        Section::default()
+            .with_screen_position(layout.position)
+            .with_bounds((width, f32::INFINITY))
+            .with_layout(Layout::default_wrap().h_align(options.h_align))
+            .with_text(texts)
+    }
+}
+
+/// Moves measured text down.
+fn translate(metrics: &mut TextMetrics, dy: f32) {
+    let translate_rect = |rect: &mut Rect| {
+        rect.min.1 += dy;
+        rect.max.1 += dy;
+    };
+    translate_rect(&mut metrics.bounds);
+    for glyph in &mut metrics.glyphs {
+        translate_rect(&mut glyph.bounds);
+    }
+    for line in &mut metrics.lines {
+        line.baseline += dy;
+        translate_rect(&mut line.bounds);
+    }
+}
+
+/// The layout of a [RichText], see [RichText::layout].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RichTextLayout {
+    /// The glyphs and lines of the text, the `section_index` of glyphs being the index of their span.
+    pub metrics: TextMetrics,
+    /// The underlines and strikethroughs, with their color.
+    pub decorations: Vec<(Rect, [f32; 4])>,
+    /// The rectangles reserved by [Span::inline_box], from the baseline to the ascent, with the index of their span.
+    pub inline_boxes: Vec<(usize, Rect)>,
+    /// The position of the section drawing the text.
+    position: (f32, f32),
+    /// The font of each span.
+    fonts: Vec<FontId>,
+    /// The text and scale laid out for each span that is an inline box.
+    boxes: Vec<Option<(&'static str, PxScale)>>,
+    links: Vec<Option<String>>,
+}
+
+impl RichTextLayout {
+    /// Returns the index of the span drawn at a point, in the coordinates the text was laid out in.
+    pub fn span_at(&self, (x, y): (f32, f32)) -> Option<usize> {
+        self.metrics
+            .glyphs
+            .iter()
+            .find(|glyph| {
+                let bounds = glyph.bounds;
+                x >= bounds.min.0 && x < bounds.max.0 && y >= bounds.min.1 && y < bounds.max.1
+            })
+            .map(|glyph| glyph.section_index)
+    }
+
+    /// Returns the link of the span drawn at a point, if it is a link.
+    pub fn link_at(&self, point: (f32, f32)) -> Option<&str> {
+        let span = self.span_at(point)?;
+        self.links[span].as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fonts() -> Fonts {
+        let fonts = Fonts::default();
+        fonts
+            .load_static_font(include_bytes!("ressources/Inconsolata-Regular.ttf"))
+            .unwrap();
+        fonts
+    }
+
+    #[test]
+    fn test_layout() {
+        let fonts = fonts();
+        let text = RichText::new()
+            .add_span(Span::new("Read the "))
+            .add_span(Span::new("docs").with_link("https://docs.rs/unigui"))
+            .add_span(Span::new(" now").bold().strikethrough().with_scale(30.0));
+        let options = TextOptions {
+            scale: 20.0,
+            ..TextOptions::default()
+        };
+        let layout = text.layout(&fonts, Rect::sized(10.0, 10.0, 1000.0, 100.0), &options);
+        assert_eq!(layout.metrics.lines.len(), 1);
+        assert_eq!(layout.metrics.glyphs.len(), 17);
+        assert_eq!(layout.metrics.bounds.min, (10.0, 10.0));
+        // Without a bold font, the regular one is used
+        assert_eq!(layout.fonts, vec![FontId(0); 3]);
+
+        // The link is underlined, and the last span is struck through
+        assert_eq!(layout.decorations.len(), 2);
+        let (underline, _) = layout.decorations[0];
+        let (strikethrough, _) = layout.decorations[1];
+        let baseline = layout.metrics.lines[0].baseline;
+        assert!(underline.min.1 > baseline);
+        assert!(strikethrough.max.1 < baseline);
+        let docs = &layout.metrics.glyphs[9..13];
+        assert_eq!(underline.min.0, docs[0].bounds.min.0);
+        assert_eq!(underline.max.0, docs[3].bounds.max.0);
+        // The space starting the span is decorated, but not the ones ending lines
+        assert_eq!(strikethrough.min.0, layout.metrics.glyphs[13].bounds.min.0);
+    }
+
+    #[test]
+    fn test_inline_boxes() {
+        let fonts = fonts();
+        let text = RichText::new()
+            .add_span(Span::new("a "))
+            .add_span(Span::inline_box(30.0, 25.0))
+            .add_span(Span::new(" b"));
+        let options = TextOptions {
+            scale: 20.0,
+            ..TextOptions::default()
+        };
+        let layout = text.layout(&fonts, Rect::sized(0.0, 0.0, 1000.0, 100.0), &options);
+        assert_eq!(layout.metrics.lines.len(), 1);
+        let line = &layout.metrics.lines[0];
+        assert!(line.ascent >= 25.0);
+
+        // The box is as wide as its advance, and stands on the baseline
+        assert_eq!(layout.inline_boxes.len(), 1);
+        let (span, rect) = layout.inline_boxes[0];
+        assert_eq!(span, 1);
+        assert!((rect.width() - 30.0).abs() < 0.01);
+        assert_eq!(rect.max.1, line.baseline);
+        assert_eq!(rect.min.1, line.baseline - 25.0);
+        let glyphs = &layout.metrics.glyphs;
+        assert_eq!(rect.min.0, glyphs[1].bounds.max.0);
+        assert_eq!(rect.max.0, glyphs[3].bounds.min.0);
+        assert_eq!(
+            layout.span_at((rect.min.0 + 15.0, line.baseline - 5.0)),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn test_bidirectional_decorations() {
+        let fonts = fonts();
+        // The right to left text of both spans is drawn reversed, with the one of the second span between the two runs of the first span
+        let text = RichText::new()
+            .add_span(Span::new("abc \u{5d0}\u{5d1}").underline())
+            .add_span(Span::new("\u{5d2}\u{5d3} def"));
+        let options = TextOptions {
+            scale: 20.0,
+            shaping: true,
+            ..TextOptions::default()
+        };
+        let layout = text.layout(&fonts, Rect::sized(0.0, 0.0, 1000.0, 100.0), &options);
+        assert_eq!(layout.decorations.len(), 2);
+        for glyph in layout
+            .metrics
+            .glyphs
+            .iter()
+            .filter(|glyph| glyph.section_index == 1)
+        {
+            for (underline, _) in &layout.decorations {
+                assert!(
+                    underline.max.0 <= glyph.bounds.min.0 || underline.min.0 >= glyph.bounds.max.0
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_hit_testing() {
+        let fonts = fonts();
+        let text = RichText::new()
+            .add_span(Span::new("one "))
+            .add_span(Span::new("two").with_link("two"))
+            .add_span(Span::new(" three"));
+        let options = TextOptions {
+            scale: 20.0,
+            v_align: VerticalAlign::Bottom,
+            ..TextOptions::default()
+        };
+        let rect = Rect::sized(0.0, 0.0, 1000.0, 100.0);
+        let layout = text.layout(&fonts, rect, &options);
+        assert_eq!(layout.metrics.bounds.max.1, 100.0);
+
+        let center = |rect: Rect| {
+            (
+                (rect.min.0 + rect.max.0) / 2.0,
+                (rect.min.1 + rect.max.1) / 2.0,
+            )
+        };
+        let glyphs = &layout.metrics.glyphs;
+        assert_eq!(layout.span_at(center(glyphs[0].bounds)), Some(0));
+        assert_eq!(layout.span_at(center(glyphs[5].bounds)), Some(1));
+        assert_eq!(layout.link_at(center(glyphs[5].bounds)), Some("two"));
+        assert_eq!(layout.link_at(center(glyphs[9].bounds)), None);
+        assert_eq!(layout.span_at((500.0, 50.0)), None);
+    }
+}
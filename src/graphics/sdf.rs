@@ -0,0 +1,269 @@
+use super::texture::GlyphAtlas;
+use crate::rect::Rect;
+use glyph_brush::ab_glyph::{Font, FontArc, GlyphId, PxScale};
+use glyph_brush::FontId;
+use std::collections::HashMap;
+
+/// Effects drawn around text rendered with distance fields, see [WgpuBackend::add_sdf_text](super::WgpuBackend::add_sdf_text).
+/// Effects are drawn within the padding of glyphs, so their width is limited to a sixth of the scale of the text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextEffects {
+    /// The width of the line drawn around glyphs, in pixels, or zero for no outline.
+    pub outline_width: f32,
+    pub outline_color: [f32; 4],
+    /// The distance over which the glow around glyphs (and their outline) fades out, in pixels, or zero for no glow.
+    pub glow_radius: f32,
+    pub glow_color: [f32; 4],
+}
+
+impl Default for TextEffects {
+    fn default() -> Self {
+        TextEffects {
+            outline_width: 0.0,
+            outline_color: [0.0, 0.0, 0.0, 1.0],
+            glow_radius: 0.0,
+            glow_color: [0.0, 0.0, 0.0, 0.5],
+        }
+    }
+}
+
+/// The scale glyphs are rasterized at before their distance field is computed.
+pub(super) const REFERENCE_SCALE: f32 = 48.0;
+
+/// The distance to the edge of glyphs stored in distance fields, in pixels at [REFERENCE_SCALE].
+/// Glyphs are padded by this distance.
+pub(super) const SPREAD: f32 = 8.0;
+
+/// A glyph stored in an [SdfAtlas].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct SdfGlyph {
+    /// The rectangle of the glyph in the atlas, in pixels.
+    pub(super) tex_coords: Rect,
+    /// The rectangle covered by the glyph and its padding, relative to its origin on the baseline, at [REFERENCE_SCALE].
+    pub(super) bounds: Rect,
+}
+
+/// The distance fields of the glyphs drawn with [WgpuBackend::add_sdf_text](super::WgpuBackend::add_sdf_text).
+/// Unlike the glyph cache of glyph_brush, glyphs are computed once for all scales, and never evicted.
+pub(super) struct SdfAtlas {
+    pub(super) atlas: GlyphAtlas,
+    /// The glyphs computed so far, [None] for glyphs without outline like spaces.
+    glyphs: HashMap<(FontId, GlyphId), Option<SdfGlyph>>,
+}
+
+impl SdfAtlas {
+    pub(super) fn new() -> SdfAtlas {
+        SdfAtlas {
+            atlas: GlyphAtlas::new(1),
+            glyphs: HashMap::new(),
+        }
+    }
+
+    /// Returns the distance field of a glyph, computing it if it is not in the atlas yet.
+    /// Returns [None] if the glyph has no outline, or if the atlas can't grow anymore.
+    pub(super) fn glyph(
+        &mut self,
+        font_id: FontId,
+        font: &FontArc,
+        id: GlyphId,
+    ) -> Option<SdfGlyph> {
+        if let Some(glyph) = self.glyphs.get(&(font_id, id)) {
+            return *glyph;
+        }
+        let outline = match font.outline_glyph(id.with_scale(PxScale::from(REFERENCE_SCALE))) {
+            Some(outline) => outline,
+            None => {
+                self.glyphs.insert((font_id, id), None);
+                return None;
+            }
+        };
+
+        let padding = SPREAD.ceil() as u32;
+        let bounds = outline.px_bounds();
+        let width = bounds.width() as u32 + 2 * padding;
+        let height = bounds.height() as u32 + 2 * padding;
+        let mut coverage = vec![0.0; (width * height) as usize];
+        outline.draw(|x, y, c| {
+            coverage[((y + padding) * width + x + padding) as usize] = c;
+        });
+        let field = distance_field(&coverage, (width as usize, height as usize));
+        let (x, y) = self.atlas.insert((width, height), &field)?;
+
+        let padding = padding as f32;
+        let glyph = SdfGlyph {
+            tex_coords: Rect {
+                min: (x as f32, y as f32),
+                max: ((x + width) as f32, (y + height) as f32),
+            },
+            bounds: Rect {
+                min: (bounds.min.x - padding, bounds.min.y - padding),
+                max: (bounds.max.x + padding, bounds.max.y + padding),
+            },
+        };
+        self.glyphs.insert((font_id, id), Some(glyph));
+        Some(glyph)
+    }
+}
+
+/// Computes the signed distance field of a coverage bitmap.
+/// Values are 0.5 on the edge of the shape, and reach 1 inside and 0 outside at [SPREAD] pixels from it.
+pub(super) fn distance_field(coverage: &[f32], (width, height): (usize, usize)) -> Vec<u8> {
+    // Squared distances to the nearest covered pixel, and to the nearest uncovered one
+    // Partially covered pixels are on the edge, which is offset depending on their coverage
+    let (mut outside, mut inside): (Vec<f64>, Vec<f64>) = coverage
+        .iter()
+        .map(|&c| match c {
+            c if c >= 1.0 => (0.0, INFINITY),
+            c if c <= 0.0 => (INFINITY, 0.0),
+            c => {
+                let offset = 0.5 - c as f64;
+                (offset.max(0.0).powi(2), (-offset).max(0.0).powi(2))
+            }
+        })
+        .unzip();
+    distance_transform(&mut outside, (width, height));
+    distance_transform(&mut inside, (width, height));
+
+    outside
+        .iter()
+        .zip(&inside)
+        .map(|(outside, inside)| {
+            let distance = inside.sqrt() - outside.sqrt();
+            let value = 0.5 + distance / (2.0 * SPREAD as f64);
+            (value.clamp(0.0, 1.0) * 255.0).round() as u8
+        })
+        .collect()
+}
+
+const INFINITY: f64 = 1e20;
+
+/// Replaces squared distances to seeds (pixels at zero) by the squared euclidean distance to the nearest seed.
+/// This is the algorithm of Felzenszwalb and Huttenlocher, applied to columns and then to rows.
+fn distance_transform(grid: &mut [f64], (width, height): (usize, usize)) {
+    let length = width.max(height);
+    let mut line = vec![0.0; length];
+    let mut result = vec![0.0; length];
+    let mut parabolas = vec![0; length];
+    let mut boundaries = vec![0.0; length + 1];
+
+    for x in 0..width {
+        for (y, value) in line[..height].iter_mut().enumerate() {
+            *value = grid[y * width + x];
+        }
+        transform_line(
+            &line[..height],
+            &mut result,
+            &mut parabolas,
+            &mut boundaries,
+        );
+        for (y, value) in result[..height].iter().enumerate() {
+            grid[y * width + x] = *value;
+        }
+    }
+    for y in 0..height {
+        line[..width].copy_from_slice(&grid[y * width..(y + 1) * width]);
+        transform_line(&line[..width], &mut result, &mut parabolas, &mut boundaries);
+        grid[y * width..(y + 1) * width].copy_from_slice(&result[..width]);
+    }
+}
+
+/// The one-dimensional distance transform: the lower envelope of the parabolas rooted at each value.
+fn transform_line(
+    values: &[f64],
+    result: &mut [f64],
+    parabolas: &mut [usize],
+    boundaries: &mut [f64],
+) {
+    let intersection = |q: usize, r: usize| {
+        let (qf, rf) = (q as f64, r as f64);
+        ((values[q] + qf * qf) - (values[r] + rf * rf)) / (2.0 * (qf - rf))
+    };
+
+    let mut k = 0;
+    parabolas[0] = 0;
+    boundaries[0] = -INFINITY;
+    boundaries[1] = INFINITY;
+    for q in 1..values.len() {
+        let mut s = intersection(q, parabolas[k]);
+        while s <= boundaries[k] {
+            k -= 1;
+            s = intersection(q, parabolas[k]);
+        }
+        k += 1;
+        parabolas[k] = q;
+        boundaries[k] = s;
+        boundaries[k + 1] = INFINITY;
+    }
+
+    k = 0;
+    for (q, result) in result.iter_mut().enumerate().take(values.len()) {
+        while boundaries[k + 1] < q as f64 {
+            k += 1;
+        }
+        let offset = q as f64 - parabolas[k] as f64;
+        *result = offset * offset + values[parabolas[k]];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distance_field() {
+        // A square of 10 pixels, in the middle of a 40 pixels image
+        let size = 40;
+        let coverage: Vec<f32> = (0..size * size)
+            .map(|i| {
+                let (x, y) = (i % size, i / size);
+                match (15..25).contains(&x) && (15..25).contains(&y) {
+                    true => 1.0,
+                    false => 0.0,
+                }
+            })
+            .collect();
+        let field = distance_field(&coverage, (size, size));
+        let at = |x: usize, y: usize| field[y * size + x];
+
+        // Inside of the square, values grow with the distance to the edge
+        assert!(at(20, 20) > at(16, 20));
+        assert!(at(15, 20) > 128);
+        // Outside, they decrease until the spread is reached
+        assert!(at(14, 20) < 128);
+        assert!(at(10, 20) < at(14, 20));
+        assert_eq!(at(0, 0), 0);
+        // Distances are euclidean
+        let corner = at(15 - 4, 15 - 3);
+        let expected = 0.5 - 5.0 / (2.0 * SPREAD);
+        assert!((corner as f32 / 255.0 - expected).abs() < 0.02);
+        // The field is symmetric
+        assert_eq!(at(12, 20), at(27, 20));
+    }
+
+    #[test]
+    fn test_atlas() {
+        let font =
+            FontArc::try_from_slice(include_bytes!("ressources/Inconsolata-Regular.ttf")).unwrap();
+        let mut atlas = SdfAtlas::new();
+        let a = atlas.glyph(FontId(0), &font, font.glyph_id('A')).unwrap();
+        assert_eq!(atlas.glyph(FontId(0), &font, font.glyph_id('A')), Some(a));
+        // Spaces have no distance field
+        assert_eq!(atlas.glyph(FontId(0), &font, font.glyph_id(' ')), None);
+
+        // Glyphs are padded, and are above the baseline
+        assert!(a.bounds.min.1 < -REFERENCE_SCALE / 2.0);
+        assert!(a.bounds.max.1 >= SPREAD);
+        assert_eq!(a.tex_coords.width(), a.bounds.width());
+        let width = atlas.atlas.size().0 as usize;
+        let (first_row, rows) = atlas.atlas.take_changes().unwrap();
+        assert_eq!(first_row, 0);
+        assert_eq!(rows.len(), a.tex_coords.height() as usize * width);
+        // The field is stored at the position of the glyph
+        let x = a.tex_coords.min.0 as usize;
+        let center = (a.tex_coords.height() as usize / 2) * width + x;
+        assert!(rows[center..center + a.tex_coords.width() as usize]
+            .iter()
+            .any(|value| *value > 128));
+        assert_eq!(atlas.atlas.take_changes(), None);
+    }
+}
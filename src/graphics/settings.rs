@@ -0,0 +1,68 @@
+/// The anti-aliasing method used by the render pipelines.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AntiAliasing {
+    /// No anti-aliasing. Edges of shapes will be jagged.
+    None,
+    /// Multisample anti-aliasing with 4 samples, the only count above 1 supported by every adapter.
+    /// The scene is rendered to a multisampled texture that is resolved into the frame.
+    Msaa,
+}
+
+impl AntiAliasing {
+    pub(crate) fn sample_count(&self) -> u32 {
+        match self {
+            AntiAliasing::None => 1,
+            AntiAliasing::Msaa => 4,
+        }
+    }
+}
+
+impl Default for AntiAliasing {
+    fn default() -> Self {
+        AntiAliasing::None
+    }
+}
+
+/// Adjusts the coverage of glyphs drawn from coverage or distance fields, but not of color glyphs.
+///
+/// Text is blended in linear space, which makes light text on dark backgrounds look thinner than dark text on light backgrounds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextGamma {
+    /// Thickens light text: coverage is raised to the power of `1.0 / gamma` for white text, and kept as is for black text.
+    /// `1.0` leaves text unchanged, and values around `1.5` make light text look as heavy as dark text.
+    pub gamma: f32,
+    /// Increases the coverage of partially covered pixels, from `0.0` (unchanged) to `1.0`, which makes edges crisper and strokes heavier.
+    pub contrast: f32,
+}
+
+impl Default for TextGamma {
+    fn default() -> Self {
+        TextGamma {
+            gamma: 1.0,
+            contrast: 0.0,
+        }
+    }
+}
+
+/// Options chosen at startup, before the [WgpuBackend](super::WgpuBackend) is created.
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    pub anti_aliasing: AntiAliasing,
+    /// Loads the fonts of [Fonts::DEFAULT_FALLBACKS](super::Fonts::DEFAULT_FALLBACKS) that are installed on the system, so that characters missing from the default font can be drawn.
+    /// This scans the font directories, which slows down startup.
+    pub system_font_fallbacks: bool,
+    /// Can be changed later with [WgpuBackend::set_text_gamma](super::WgpuBackend::set_text_gamma).
+    pub text_gamma: TextGamma,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_count() {
+        assert_eq!(AntiAliasing::None.sample_count(), 1);
+        assert_eq!(AntiAliasing::Msaa.sample_count(), 4);
+        assert_eq!(Settings::default().anti_aliasing.sample_count(), 1);
+    }
+}
@@ -0,0 +1,592 @@
+//! Text shaping with [rustybuzz], and layout of bidirectional text with [unicode_bidi].
+
+use glyph_brush::ab_glyph::{self, point, Font, PxScale, ScaleFont};
+use glyph_brush::{
+    BuiltInLineBreaker, FontId, GlyphPositioner, HorizontalAlign, Layout, LineBreak, LineBreaker,
+    SectionGeometry, SectionGlyph, SectionText, ToSectionText, VerticalAlign,
+};
+use rustybuzz::{Direction, Face, UnicodeBuffer};
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use unicode_bidi::{BidiInfo, Level};
+use unicode_script::{Script, UnicodeScript};
+
+/// The file of a font and the index of the font in it, needed to read its layout tables.
+/// Fonts registered without their data are drawn without substitutions and positioning.
+pub(super) type FontData = Option<(&'static [u8], u32)>;
+
+/// A glyph of a shaped run, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ShapedGlyph {
+    id: u16,
+    /// The byte index of the first character the glyph comes from.
+    cluster: usize,
+    advance: f32,
+    offset: (f32, f32),
+}
+
+/// Shapes a run of characters of the same font, script and direction, starting at a byte index of the text.
+/// The text of the paragraph before and after the run is given as context, so that characters join across runs.
+/// Glyphs are returned in logical order. Placed from left to right in the visual order of the run, they are drawn at the pen position plus their offset.
+fn shape<F: Font>(
+    font: &F,
+    face: Option<&Face>,
+    run: &str,
+    start: usize,
+    (before, after): (&str, &str),
+    scale: PxScale,
+    direction: Direction,
+) -> Vec<ShapedGlyph> {
+    let scaled = font.as_scaled(scale);
+    let (h_factor, v_factor) = (scaled.h_scale_factor(), scaled.v_scale_factor());
+    let face = match face {
+        Some(face) => face,
+        // Without the tables of the font, glyphs are only kerned
+        None => {
+            let mut glyphs: Vec<ShapedGlyph> = run
+                .char_indices()
+                .map(|(index, character)| {
+                    let id = font.glyph_id(character);
+                    ShapedGlyph {
+                        id: id.0,
+                        cluster: start + index,
+                        advance: scaled.h_advance(id),
+                        offset: (0.0, 0.0),
+                    }
+                })
+                .collect();
+            for i in 1..glyphs.len() {
+                let (first, second) = (glyphs[i - 1].id, glyphs[i].id);
+                glyphs[i - 1].advance +=
+                    scaled.kern(ab_glyph::GlyphId(first), ab_glyph::GlyphId(second));
+            }
+            return glyphs;
+        }
+    };
+
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str(run);
+    buffer.set_pre_context(before);
+    buffer.set_post_context(after);
+    buffer.guess_segment_properties();
+    buffer.set_direction(direction);
+    let output = rustybuzz::shape(face, &[], buffer);
+    let mut glyphs: Vec<ShapedGlyph> = output
+        .glyph_infos()
+        .iter()
+        .zip(output.glyph_positions())
+        .map(|(info, position)| ShapedGlyph {
+            id: info.glyph_id as u16,
+            cluster: start + info.cluster as usize,
+            advance: position.x_advance as f32 * h_factor,
+            offset: (
+                position.x_offset as f32 * h_factor,
+                -position.y_offset as f32 * v_factor,
+            ),
+        })
+        .collect();
+    // Right to left runs are shaped in visual order
+    if direction == Direction::RightToLeft {
+        glyphs.reverse();
+    }
+    glyphs
+}
+
+/// Whether a character takes the script of the characters around it.
+fn is_common(script: Script) -> bool {
+    matches!(script, Script::Common | Script::Inherited | Script::Unknown)
+}
+
+/// A shaped glyph with its context, while a paragraph is laid out.
+#[derive(Debug, Clone, Copy)]
+struct ParagraphGlyph {
+    glyph: ShapedGlyph,
+    section_index: usize,
+    font_id: FontId,
+    scale: PxScale,
+    level: Level,
+    whitespace: bool,
+}
+
+/// A line of glyphs placed horizontally, from the left of the line.
+struct PlacedLine {
+    glyphs: Vec<(ParagraphGlyph, f32, f32)>,
+    width: f32,
+    ascent: f32,
+    descent: f32,
+    line_gap: f32,
+}
+
+/// Lays out sections like a [Layout] does, but shapes text with the layout tables of fonts and orders bidirectional text.
+/// The glyphs of each line are returned in the order of the text.
+#[derive(Debug, Clone)]
+pub(super) struct ShapingLayout {
+    pub(super) layout: Layout<BuiltInLineBreaker>,
+    /// Indexed by [FontId].
+    pub(super) font_data: Vec<FontData>,
+}
+
+impl Hash for ShapingLayout {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        "shaping".hash(state);
+        self.layout.hash(state);
+    }
+}
+
+impl GlyphPositioner for ShapingLayout {
+    fn calculate_glyphs<F, S>(
+        &self,
+        fonts: &[F],
+        geometry: &SectionGeometry,
+        sections: &[S],
+    ) -> Vec<SectionGlyph>
+    where
+        F: Font,
+        S: ToSectionText,
+    {
+        let (line_breaker, h_align, v_align, max_width) = match self.layout {
+            Layout::SingleLine {
+                line_breaker,
+                h_align,
+                v_align,
+            } => (line_breaker, h_align, v_align, f32::INFINITY),
+            Layout::Wrap {
+                line_breaker,
+                h_align,
+                v_align,
+            } => (line_breaker, h_align, v_align, geometry.bounds.0),
+        };
+        let texts: Vec<SectionText> = sections.iter().map(|s| s.to_section_text()).collect();
+        if fonts.is_empty() || texts.is_empty() {
+            return Vec::new();
+        }
+        let font_id = |id: FontId| if id.0 < fonts.len() { id } else { FontId(0) };
+
+        // Sections are laid out as a single text
+        let mut text = String::new();
+        let mut starts = Vec::with_capacity(texts.len());
+        for section in &texts {
+            starts.push(text.len());
+            text.push_str(section.text);
+        }
+        let section_at = |byte: usize| starts.partition_point(|start| *start <= byte) - 1;
+        let breaks: Vec<LineBreak> = line_breaker.line_breaks(&text).collect();
+
+        // Faces are only parsed for the fonts of the sections
+        let mut faces: HashMap<FontId, Option<Face>> = HashMap::new();
+
+        let mut paragraphs = Vec::new();
+        let mut start = 0;
+        for line_break in &breaks {
+            if let LineBreak::Hard(end) = line_break {
+                paragraphs.push(start..*end);
+                start = *end;
+            }
+        }
+        if start < text.len() || paragraphs.is_empty() {
+            paragraphs.push(start..text.len());
+        }
+
+        let mut lines = Vec::new();
+        for paragraph in paragraphs {
+            let content = text[paragraph.clone()].trim_end_matches(['\n', '\r']);
+            let characters: Vec<(usize, char)> = content
+                .char_indices()
+                .map(|(index, character)| (paragraph.start + index, character))
+                .collect();
+            let bidi = BidiInfo::new(content, None);
+            let paragraph_level = bidi
+                .paragraphs
+                .first()
+                .map_or_else(Level::ltr, |paragraph| paragraph.level);
+            let content_end = paragraph.start + content.len();
+            let levels: Vec<Level> = characters
+                .iter()
+                .map(|(byte, _)| bidi.levels[byte - paragraph.start])
+                .collect();
+
+            // Shape runs of characters sharing a font, a scale, a direction and a script
+            // Runs span sections, so that glyphs of the same font are kerned across sections
+            let mut glyphs: Vec<ParagraphGlyph> = Vec::new();
+            let mut run_start = 0;
+            while run_start < characters.len() {
+                let section = &texts[section_at(characters[run_start].0)];
+                let id = font_id(section.font_id);
+                let level = levels[run_start];
+                let mut script = Script::Common;
+                let mut run_end = run_start;
+                while run_end < characters.len() {
+                    let (byte, character) = characters[run_end];
+                    let character_script = character.script();
+                    let character_section = &texts[section_at(byte)];
+                    if font_id(character_section.font_id) != id
+                        || character_section.scale != section.scale
+                        || levels[run_end] != level
+                        || (!is_common(script)
+                            && !is_common(character_script)
+                            && character_script != script)
+                    {
+                        break;
+                    }
+                    if is_common(script) {
+                        script = character_script;
+                    }
+                    run_end += 1;
+                }
+
+                let start = characters[run_start].0;
+                let end = characters
+                    .get(run_end)
+                    .map_or(content_end, |(byte, _)| *byte);
+                let direction = match level.is_rtl() {
+                    false => Direction::LeftToRight,
+                    true => Direction::RightToLeft,
+                };
+                let face = faces.entry(id).or_insert_with(|| {
+                    let (data, index) = self.font_data.get(id.0).copied().flatten()?;
+                    Face::from_slice(data, index)
+                });
+                for glyph in shape(
+                    &fonts[id.0],
+                    face.as_ref(),
+                    &text[start..end],
+                    start,
+                    (&text[paragraph.start..start], &text[end..content_end]),
+                    section.scale,
+                    direction,
+                ) {
+                    glyphs.push(ParagraphGlyph {
+                        glyph,
+                        section_index: section_at(glyph.cluster),
+                        font_id: id,
+                        scale: section.scale,
+                        level,
+                        whitespace: text[glyph.cluster..].starts_with(char::is_whitespace),
+                    });
+                }
+                run_start = run_end;
+            }
+
+            // Glyphs are in the order of the text, so the glyphs of a range of bytes are consecutive
+            let glyph_range = |range: Range<usize>| {
+                glyphs.partition_point(|glyph| glyph.glyph.cluster < range.start)
+                    ..glyphs.partition_point(|glyph| glyph.glyph.cluster < range.end)
+            };
+            // The advances of the glyphs before each glyph, so that widths are measured in constant time
+            let mut advance_sums = Vec::with_capacity(glyphs.len() + 1);
+            advance_sums.push(0.0);
+            for glyph in &glyphs {
+                advance_sums.push(advance_sums[advance_sums.len() - 1] + glyph.glyph.advance);
+            }
+
+            // Wrap lines at the last break opportunity that fits
+            let width = |range: Range<usize>| -> f32 {
+                let end = range.start + text[range.clone()].trim_end().len();
+                let glyph_range = glyph_range(range.start..end);
+                advance_sums[glyph_range.end] - advance_sums[glyph_range.start]
+            };
+            let mut line_ranges = Vec::new();
+            let mut line_start = paragraph.start;
+            let mut last_fit = None;
+            let soft_breaks = breaks
+                .iter()
+                .filter_map(|line_break| match line_break {
+                    LineBreak::Soft(offset)
+                        if *offset > paragraph.start && *offset < content_end =>
+                    {
+                        Some(*offset)
+                    }
+                    _ => None,
+                })
+                .chain(std::iter::once(content_end));
+            for offset in soft_breaks {
+                if width(line_start..offset) <= max_width {
+                    last_fit = Some(offset);
+                    continue;
+                }
+                match last_fit {
+                    Some(fit) if fit > line_start => {
+                        line_ranges.push(line_start..fit);
+                        line_start = fit;
+                        last_fit = if width(line_start..offset) <= max_width {
+                            Some(offset)
+                        } else {
+                            None
+                        };
+                    }
+                    // A word wider than the bounds gets its own line
+                    _ => {
+                        line_ranges.push(line_start..offset);
+                        line_start = offset;
+                        last_fit = None;
+                    }
+                }
+            }
+            if line_start < content_end || line_ranges.is_empty() {
+                line_ranges.push(line_start..content_end);
+            }
+
+            for range in line_ranges {
+                let indices: Vec<usize> = glyph_range(range.clone()).collect();
+                let mut line_levels: Vec<Level> =
+                    indices.iter().map(|index| glyphs[*index].level).collect();
+                // Trailing whitespace goes to the end of the line in the paragraph direction (rule L1)
+                for (level, index) in line_levels.iter_mut().zip(&indices).rev() {
+                    if !glyphs[*index].whitespace {
+                        break;
+                    }
+                    *level = paragraph_level;
+                }
+
+                // Place glyphs from left to right
+                let mut positions: Vec<Option<(f32, f32)>> = vec![None; glyphs.len()];
+                let mut pen = 0.0;
+                for order in BidiInfo::reorder_visual(&line_levels) {
+                    let glyph = &glyphs[indices[order]].glyph;
+                    positions[indices[order]] = Some((pen + glyph.offset.0, glyph.offset.1));
+                    pen += glyph.advance;
+                }
+
+                let line_fonts: Vec<(FontId, PxScale)> = match indices.is_empty() {
+                    false => indices
+                        .iter()
+                        .map(|index| (glyphs[*index].font_id, glyphs[*index].scale))
+                        .collect(),
+                    true => {
+                        let section =
+                            &texts[section_at(range.start.min(text.len().saturating_sub(1)))];
+                        vec![(font_id(section.font_id), section.scale)]
+                    }
+                };
+                let mut line = PlacedLine {
+                    glyphs: Vec::with_capacity(indices.len()),
+                    width: pen,
+                    ascent: 0.0,
+                    descent: 0.0,
+                    line_gap: 0.0,
+                };
+                for (id, scale) in line_fonts {
+                    let font = fonts[id.0].as_scaled(scale);
+                    line.ascent = line.ascent.max(font.ascent());
+                    line.descent = line.descent.min(font.descent());
+                    line.line_gap = line.line_gap.max(font.line_gap());
+                }
+                for index in indices {
+                    let (x, y) = positions[index].unwrap_or((0.0, 0.0));
+                    line.glyphs.push((glyphs[index], x, y));
+                }
+                lines.push(line);
+            }
+        }
+
+        // Place lines vertically, and align them
+        let height: f32 = lines
+            .iter()
+            .map(|line| line.ascent - line.descent + line.line_gap)
+            .sum::<f32>()
+            - lines.last().map_or(0.0, |line| line.line_gap);
+        let (x, y) = geometry.screen_position;
+        let mut top = match v_align {
+            VerticalAlign::Top => y,
+            VerticalAlign::Center => y - height / 2.0,
+            VerticalAlign::Bottom => y - height,
+        };
+        let mut section_glyphs = Vec::new();
+        for line in lines {
+            let left = match h_align {
+                HorizontalAlign::Left => x,
+                HorizontalAlign::Center => x - line.width / 2.0,
+                HorizontalAlign::Right => x - line.width,
+            };
+            let baseline = top + line.ascent;
+            for (glyph, glyph_x, glyph_y) in line.glyphs {
+                section_glyphs.push(SectionGlyph {
+                    section_index: glyph.section_index,
+                    byte_index: glyph.glyph.cluster - starts[glyph.section_index],
+                    glyph: ab_glyph::Glyph {
+                        id: ab_glyph::GlyphId(glyph.glyph.id),
+                        scale: glyph.scale,
+                        position: point(left + glyph_x, baseline + glyph_y),
+                    },
+                    font_id: glyph.font_id,
+                });
+            }
+            top = baseline - line.descent + line.line_gap;
+        }
+        section_glyphs
+    }
+
+    fn bounds_rect(&self, geometry: &SectionGeometry) -> ab_glyph::Rect {
+        self.layout.bounds_rect(geometry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glyph_brush::ab_glyph::FontRef;
+
+    const FONT: &[u8] = include_bytes!("ressources/Inconsolata-Regular.ttf");
+
+    fn layout(
+        text: &str,
+        layout: Layout<BuiltInLineBreaker>,
+        bounds: (f32, f32),
+    ) -> Vec<SectionGlyph> {
+        let font = FontRef::try_from_slice(FONT).unwrap();
+        let shaping = ShapingLayout {
+            layout,
+            font_data: vec![Some((FONT, 0))],
+        };
+        let geometry = SectionGeometry {
+            screen_position: (0.0, 0.0),
+            bounds,
+        };
+        let section = SectionText {
+            text,
+            scale: PxScale::from(20.0),
+            font_id: FontId(0),
+        };
+        shaping.calculate_glyphs(&[font], &geometry, &[section])
+    }
+
+    #[test]
+    fn test_same_as_builtin_layout() {
+        let font = FontRef::try_from_slice(FONT).unwrap();
+        let section = SectionText {
+            text: "Hello world, shaped",
+            scale: PxScale::from(20.0),
+            font_id: FontId(0),
+        };
+        let geometry = SectionGeometry {
+            screen_position: (10.0, 20.0),
+            bounds: (100.0, f32::INFINITY),
+        };
+        let builtin = Layout::default_wrap().calculate_glyphs(&[&font], &geometry, &[section]);
+        let shaping = ShapingLayout {
+            layout: Layout::default_wrap(),
+            font_data: vec![Some((FONT, 0))],
+        };
+        let shaped = shaping.calculate_glyphs(&[&font], &geometry, &[section]);
+
+        let positions = |glyphs: &[SectionGlyph]| -> Vec<(usize, u16, (f32, f32))> {
+            glyphs
+                .iter()
+                .filter(|glyph| glyph.glyph.id != font.glyph_id(' '))
+                .map(|glyph| {
+                    let position = glyph.glyph.position;
+                    (
+                        glyph.byte_index,
+                        glyph.glyph.id.0,
+                        (position.x.round(), position.y.round()),
+                    )
+                })
+                .collect()
+        };
+        assert_eq!(positions(&shaped), positions(&builtin));
+    }
+
+    #[test]
+    fn test_right_to_left() {
+        // Hebrew letters are drawn from right to left, even without glyphs for them
+        let glyphs = layout(
+            "ab אבג",
+            Layout::default_single_line(),
+            (f32::INFINITY, f32::INFINITY),
+        );
+        let mut visual: Vec<(f32, usize)> = glyphs
+            .iter()
+            .map(|glyph| (glyph.glyph.position.x, glyph.byte_index))
+            .collect();
+        visual.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        let order: Vec<usize> = visual.iter().map(|(_, index)| *index).collect();
+        assert_eq!(order, vec![0, 1, 2, 7, 5, 3]);
+
+        // Brackets are mirrored
+        let font = FontRef::try_from_slice(FONT).unwrap();
+        let glyphs = layout(
+            "א(",
+            Layout::default_single_line(),
+            (f32::INFINITY, f32::INFINITY),
+        );
+        assert_eq!(glyphs[1].glyph.id, font.glyph_id(')'));
+
+        // Explicit overrides reverse left to right letters
+        let glyphs = layout(
+            "a \u{202E}bc\u{202C} d",
+            Layout::default_single_line(),
+            (f32::INFINITY, f32::INFINITY),
+        );
+        let mut visual: Vec<(f32, usize)> = glyphs
+            .iter()
+            .filter(|glyph| [0, 5, 6, 11].contains(&glyph.byte_index))
+            .map(|glyph| (glyph.glyph.position.x, glyph.byte_index))
+            .collect();
+        visual.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        let order: Vec<usize> = visual.iter().map(|(_, index)| *index).collect();
+        assert_eq!(order, vec![0, 6, 5, 11]);
+    }
+
+    #[test]
+    fn test_wrap_and_align() {
+        let advance = FontRef::try_from_slice(FONT)
+            .unwrap()
+            .as_scaled(PxScale::from(20.0))
+            .h_advance(ab_glyph::GlyphId(
+                FontRef::try_from_slice(FONT).unwrap().glyph_id('a').0,
+            ));
+        let glyphs = layout(
+            "aaa bbb\nc",
+            Layout::default_wrap().h_align(HorizontalAlign::Right),
+            (5.0 * advance, f32::INFINITY),
+        );
+        let lines: Vec<f32> = glyphs.iter().map(|glyph| glyph.glyph.position.y).collect();
+        assert!(lines[0] == lines[2] && lines[4] > lines[0] && lines[7] > lines[4]);
+        // Right aligned lines end at the position of the section
+        let last = glyphs.last().unwrap();
+        assert!((last.glyph.position.x + advance).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_sections() {
+        // Words spanning sections of different fonts are not broken between sections
+        let font = FontRef::try_from_slice(FONT).unwrap();
+        let advance = font
+            .as_scaled(PxScale::from(20.0))
+            .h_advance(ab_glyph::GlyphId(font.glyph_id('a').0));
+        let shaping = ShapingLayout {
+            layout: Layout::default_wrap(),
+            font_data: vec![Some((FONT, 0)), Some((FONT, 0))],
+        };
+        let geometry = SectionGeometry {
+            screen_position: (0.0, 0.0),
+            bounds: (7.5 * advance, f32::INFINITY),
+        };
+        let sections = [
+            SectionText {
+                text: "aaa",
+                scale: PxScale::from(20.0),
+                font_id: FontId(0),
+            },
+            SectionText {
+                text: "bbb ccc",
+                scale: PxScale::from(20.0),
+                font_id: FontId(1),
+            },
+        ];
+        let glyphs = shaping.calculate_glyphs(&[&font, &font], &geometry, &sections);
+        let glyph = |section_index, byte_index| {
+            glyphs
+                .iter()
+                .find(|glyph| {
+                    glyph.section_index == section_index && glyph.byte_index == byte_index
+                })
+                .unwrap()
+        };
+        assert_eq!(glyph(0, 2).font_id, FontId(0));
+        assert_eq!(glyph(1, 0).font_id, FontId(1));
+        assert_eq!(glyph(0, 2).glyph.position.y, glyph(1, 0).glyph.position.y);
+        assert!(glyph(1, 4).glyph.position.y > glyph(1, 0).glyph.position.y);
+    }
+}
@@ -0,0 +1,188 @@
+use super::TextureId;
+
+/// The format of the textures the scene is drawn on, given the format of the frame.
+/// Colors are blended in linear space only on sRGB textures, so the sRGB equivalent of the frame format is used when there is one.
+/// The scene is then encoded to sRGB when it is copied to a frame that is not sRGB.
+pub(super) fn render_format(frame_format: wgpu::TextureFormat) -> wgpu::TextureFormat {
+    match frame_format {
+        wgpu::TextureFormat::Rgba8Unorm => wgpu::TextureFormat::Rgba8UnormSrgb,
+        wgpu::TextureFormat::Bgra8Unorm => wgpu::TextureFormat::Bgra8UnormSrgb,
+        format => format,
+    }
+}
+
+/// Creates a texture that can be both rendered to and sampled, along with the bind group to sample it.
+fn create_sampled_target(
+    device: &wgpu::Device,
+    size: (u32, u32),
+    format: wgpu::TextureFormat,
+    texture_bind_group_layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+    label: &str,
+) -> (wgpu::Texture, wgpu::TextureView, wgpu::BindGroup) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        size: wgpu::Extent3d {
+            width: size.0,
+            height: size.1,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsage::RENDER_ATTACHMENT
+            | wgpu::TextureUsage::SAMPLED
+            | wgpu::TextureUsage::COPY_SRC,
+        label: Some(label),
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: texture_bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+        label: Some("texture_bind_group"),
+    });
+
+    (texture, view, bind_group)
+}
+
+/// Creates the texture the scene is rendered to before being resolved.
+/// Returns [None] if multisampling is disabled.
+pub(super) fn create_multisampled_framebuffer(
+    device: &wgpu::Device,
+    size: (u32, u32),
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> Option<wgpu::TextureView> {
+    if sample_count <= 1 {
+        return None;
+    }
+
+    let multisampled_frame_descriptor = wgpu::TextureDescriptor {
+        size: wgpu::Extent3d {
+            width: size.0,
+            height: size.1,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
+        label: Some("Multisampled Framebuffer"),
+    };
+
+    Some(
+        device
+            .create_texture(&multisampled_frame_descriptor)
+            .create_view(&wgpu::TextureViewDescriptor::default()),
+    )
+}
+
+/// The offscreen textures a frame is rendered to.
+/// They have the size of the screen, so they are recreated when it is resized.
+///
+/// The scene is drawn on the `scene` texture (through the multisampled framebuffer if any), and then copied to the frame.
+/// Rendering offscreen allows effects to read what has already been drawn.
+pub(super) struct RenderTargets {
+    pub(super) multisampled_framebuffer: Option<wgpu::TextureView>,
+    /// Kept to read the frame back, see [WgpuBackend::read_frame](super::WgpuBackend::read_frame).
+    pub(super) scene_texture: wgpu::Texture,
+    pub(super) scene_view: wgpu::TextureView,
+    pub(super) scene_bind_group: wgpu::BindGroup,
+    /// Two textures used alternatively by the passes of the blur effects.
+    pub(super) blur_views: [wgpu::TextureView; 2],
+    pub(super) blur_bind_groups: [wgpu::BindGroup; 2],
+}
+
+impl RenderTargets {
+    pub(super) fn new(
+        device: &wgpu::Device,
+        size: (u32, u32),
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+    ) -> RenderTargets {
+        let (scene_texture, scene_view, scene_bind_group) = create_sampled_target(
+            device,
+            size,
+            format,
+            texture_bind_group_layout,
+            sampler,
+            "Scene Texture",
+        );
+        let (_, blur_view0, blur_bind_group0) = create_sampled_target(
+            device,
+            size,
+            format,
+            texture_bind_group_layout,
+            sampler,
+            "Blur Texture",
+        );
+        let (_, blur_view1, blur_bind_group1) = create_sampled_target(
+            device,
+            size,
+            format,
+            texture_bind_group_layout,
+            sampler,
+            "Blur Texture",
+        );
+
+        RenderTargets {
+            multisampled_framebuffer: create_multisampled_framebuffer(
+                device,
+                size,
+                format,
+                sample_count,
+            ),
+            scene_texture,
+            scene_view,
+            scene_bind_group,
+            blur_views: [blur_view0, blur_view1],
+            blur_bind_groups: [blur_bind_group0, blur_bind_group1],
+        }
+    }
+
+    /// Returns the attachment to draw on, and the texture it must be resolved to if multisampling is enabled.
+    pub(super) fn color_attachment(&self) -> (&wgpu::TextureView, Option<&wgpu::TextureView>) {
+        match &self.multisampled_framebuffer {
+            Some(multisampled_view) => (multisampled_view, Some(&self.scene_view)),
+            None => (&self.scene_view, None),
+        }
+    }
+}
+
+/// A texture drawn to during a frame, instead of the screen.
+/// See [WgpuBackend::begin_offscreen_layer](super::WgpuBackend::begin_offscreen_layer).
+pub(super) struct OffscreenTarget {
+    pub(super) size: (u32, u32),
+    pub(super) multisampled_framebuffer: Option<wgpu::TextureView>,
+    pub(super) view: wgpu::TextureView,
+    /// Keeps the texture alive until it has been drawn to, and while it is pooled.
+    pub(super) texture_id: TextureId,
+}
+
+impl OffscreenTarget {
+    /// Returns the attachment to draw on, and the texture it must be resolved to if multisampling is enabled.
+    pub(super) fn color_attachment(&self) -> (&wgpu::TextureView, Option<&wgpu::TextureView>) {
+        match &self.multisampled_framebuffer {
+            Some(multisampled_view) => (multisampled_view, Some(&self.view)),
+            None => (&self.view, None),
+        }
+    }
+
+    /// Whether the texture is only kept by the target and by the backend, so that the target can be drawn to again.
+    pub(super) fn is_unused(&self) -> bool {
+        std::sync::Arc::strong_count(&self.texture_id.id) <= 2
+    }
+}
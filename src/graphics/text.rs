@@ -0,0 +1,1126 @@
+use super::color_glyph::can_draw_glyphs;
+use super::font::{scan_system_fonts, FontDescription, FontError, FontStyle, SystemFont};
+use super::sdf::TextEffects;
+use super::shaping::{FontData, ShapingLayout};
+use crate::rect::Rect;
+use glyph_brush::ab_glyph::{Font, FontArc, FontRef, PxScale, ScaleFont};
+use glyph_brush::{
+    BuiltInLineBreaker, FontId, GlyphPositioner, HorizontalAlign, Layout, Section, SectionGeometry,
+    Text, VerticalAlign,
+};
+use std::sync::{OnceLock, RwLock};
+
+static GLOBAL_FONTS: OnceLock<Fonts> = OnceLock::new();
+
+/// The fonts used to draw and measure text.
+///
+/// A single instance is shared by the whole application (see [Fonts::global]), so that widgets can measure their text in [Widget::allocate_area](crate::widget::Widget::allocate_area), without access to the backend.
+/// [FontId(0)](FontId) is reserved for the default font given to the first backend, even when other fonts are registered before it.
+/// Until then, it draws with the first registered font.
+///
+/// Fonts are never removed, so a [FontId] stays valid for the whole life of the application.
+/// It can be given to [Text::with_font_id] or [TextOptions::font].
+///
+/// Characters that a font doesn't have are drawn with the first font of the [fallbacks](Fonts::set_fallbacks) that has them.
+#[derive(Default)]
+pub struct Fonts {
+    registry: RwLock<Registry>,
+    system_fonts: OnceLock<Vec<SystemFont>>,
+}
+
+#[derive(Default)]
+struct Registry {
+    /// Indexed by [FontId].
+    fonts: Vec<FontArc>,
+    descriptions: Vec<FontDescription>,
+    /// The files of the fonts, read by the shaper.
+    data: Vec<FontData>,
+    fallbacks: Vec<FontId>,
+    /// Whether [FontId(0)](FontId) is the default font, and not a copy of the first registered font.
+    has_default: bool,
+}
+
+impl Registry {
+    /// Measures a section laid out by `layout`, see [Fonts::measure_section].
+    fn measure<X: Clone, L: GlyphPositioner>(
+        &self,
+        section: &Section<X>,
+        layout: &L,
+    ) -> TextMetrics {
+        let fonts = &self.fonts;
+        if fonts.is_empty() {
+            return TextMetrics::default();
+        }
+        let font_id = |id: FontId| if id.0 < fonts.len() { id } else { FontId(0) };
+
+        let (split_texts, origins) = self.with_fallbacks(&section.text);
+        let texts: Vec<_> = split_texts
+            .iter()
+            .map(|text| glyph_brush::SectionText {
+                text: text.text,
+                scale: text.scale,
+                font_id: font_id(text.font_id),
+            })
+            .collect();
+        let geometry = SectionGeometry {
+            screen_position: section.screen_position,
+            bounds: section.bounds,
+        };
+        let glyphs = layout.calculate_glyphs(fonts, &geometry, &texts);
+
+        let mut metrics = TextMetrics::default();
+        for glyph in glyphs {
+            let font = fonts[glyph.font_id.0].as_scaled(glyph.glyph.scale);
+            let (x, baseline) = (glyph.glyph.position.x, glyph.glyph.position.y);
+            let advance = font.h_advance(glyph.glyph.id);
+
+            // Glyphs of a line share the same baseline, except marks placed above or below their base
+            let new_line = metrics
+                .lines
+                .last()
+                .map(|line| baseline > line.baseline + (line.ascent - line.descent) / 2.0)
+                .unwrap_or(true);
+            if new_line {
+                metrics.lines.push(LineMetrics {
+                    baseline,
+                    ascent: 0.0,
+                    descent: 0.0,
+                    line_gap: 0.0,
+                    bounds: Rect {
+                        min: (x, baseline),
+                        max: (x, baseline),
+                    },
+                    glyphs: metrics.glyphs.len()..metrics.glyphs.len(),
+                });
+            }
+            let line = metrics.lines.last_mut().unwrap();
+            line.ascent = line.ascent.max(font.ascent());
+            line.descent = line.descent.min(font.descent());
+            line.line_gap = line.line_gap.max(font.line_gap());
+            line.bounds.min.0 = line.bounds.min.0.min(x);
+            line.bounds.max.0 = line.bounds.max.0.max(x + advance);
+            line.glyphs.end += 1;
+
+            let (section_index, offset) = origins[glyph.section_index];
+            metrics.glyphs.push(GlyphMetrics {
+                section_index,
+                byte_index: offset + glyph.byte_index,
+                font_id: glyph.font_id,
+                bounds: Rect {
+                    min: (x, baseline - font.ascent()),
+                    max: (x + advance, baseline - font.descent()),
+                },
+            });
+        }
+
+        self.add_blank_lines(section, &mut metrics);
+        for line in &mut metrics.lines {
+            line.bounds.min.1 = line.baseline - line.ascent;
+            line.bounds.max.1 = line.baseline - line.descent;
+        }
+        if let Some(first) = metrics.lines.first() {
+            metrics.bounds = first.bounds;
+            for line in &metrics.lines[1..] {
+                metrics.bounds.min.0 = metrics.bounds.min.0.min(line.bounds.min.0);
+                metrics.bounds.max.0 = metrics.bounds.max.0.max(line.bounds.max.0);
+                metrics.bounds.max.1 = metrics.bounds.max.1.max(line.bounds.max.1);
+            }
+        }
+        metrics
+    }
+
+    /// Adds the lines of a measured section that have no glyph, like the ones between two line breaks.
+    fn add_blank_lines<X>(&self, section: &Section<X>, metrics: &mut TextMetrics) {
+        let mut starts = Vec::with_capacity(section.text.len());
+        let mut text = String::new();
+        for part in &section.text {
+            starts.push(text.len());
+            text.push_str(part.text);
+        }
+        let breaks: Vec<usize> = text.match_indices('\n').map(|(index, _)| index).collect();
+        if text.is_empty() {
+            return;
+        }
+        let glyphs = &metrics.glyphs;
+        let paragraph_of = |line: &LineMetrics| match glyphs.get(line.glyphs.start) {
+            Some(glyph) => breaks
+                .partition_point(|index| *index < starts[glyph.section_index] + glyph.byte_index),
+            None => 0,
+        };
+        let blank_line = |paragraph: usize, glyphs: usize| {
+            let start = match paragraph {
+                0 => 0,
+                _ => breaks[paragraph - 1],
+            };
+            let part = &section.text[starts.partition_point(|index| *index <= start) - 1];
+            let font = match self.fonts.get(part.font_id.0) {
+                Some(font) => font.as_scaled(part.scale),
+                None => self.fonts[0].as_scaled(part.scale),
+            };
+            LineMetrics {
+                baseline: 0.0,
+                ascent: font.ascent(),
+                descent: font.descent(),
+                line_gap: font.line_gap(),
+                bounds: Rect {
+                    min: (section.screen_position.0, 0.0),
+                    max: (section.screen_position.0, 0.0),
+                },
+                glyphs: glyphs..glyphs,
+            }
+        };
+
+        // Each paragraph has at least a line
+        let measured = std::mem::take(&mut metrics.lines);
+        let mut lines: Vec<LineMetrics> = Vec::with_capacity(breaks.len() + 1);
+        let mut measured = measured.into_iter().peekable();
+        let mut placed = None;
+        for paragraph in 0..=breaks.len() {
+            let mut has_lines = false;
+            while let Some(line) = measured.next_if(|line| paragraph_of(line) == paragraph) {
+                placed.get_or_insert(lines.len());
+                lines.push(line);
+                has_lines = true;
+            }
+            if !has_lines {
+                let glyphs = lines.last().map_or(0, |line| line.glyphs.end);
+                lines.push(blank_line(paragraph, glyphs));
+            }
+        }
+
+        // Blank lines are placed around the measured ones, or from the top of the section
+        let placed = match placed {
+            Some(placed) => placed,
+            None => {
+                lines[0].baseline = section.screen_position.1 + lines[0].ascent;
+                0
+            }
+        };
+        for i in (0..placed).rev() {
+            let next = &lines[i + 1];
+            lines[i].baseline = next.baseline - next.ascent - lines[i].line_gap + lines[i].descent;
+        }
+        for i in placed + 1..lines.len() {
+            if lines[i].glyphs.is_empty() {
+                let previous = &lines[i - 1];
+                lines[i].baseline =
+                    previous.baseline - previous.descent + previous.line_gap + lines[i].ascent;
+            }
+        }
+        if metrics.glyphs.is_empty() {
+            let height = lines[lines.len() - 1].baseline
+                - lines[lines.len() - 1].descent
+                - (lines[0].baseline - lines[0].ascent);
+            let v_align = match section.layout {
+                Layout::SingleLine { v_align, .. } | Layout::Wrap { v_align, .. } => v_align,
+            };
+            let offset = match v_align {
+                VerticalAlign::Top => 0.0,
+                VerticalAlign::Center => -height / 2.0,
+                VerticalAlign::Bottom => -height,
+            };
+            for line in &mut lines {
+                line.baseline += offset;
+            }
+        }
+        metrics.lines = lines;
+    }
+
+    /// The font loaded from this data, if any.
+    fn find_data(&self, data: &[u8], index: u32) -> Option<FontId> {
+        self.data
+            .iter()
+            .position(|font| *font == Some((data, index)))
+            .map(FontId)
+    }
+
+    fn register(&mut self, font: FontArc, description: FontDescription, data: FontData) -> FontId {
+        if self.fonts.is_empty() {
+            // Keeps the first id for the default font, without matching queries until then
+            self.fonts.push(font.clone());
+            self.descriptions.push(FontDescription {
+                family: String::new(),
+                ..description.clone()
+            });
+            self.data.push(None);
+        }
+        self.fonts.push(font);
+        self.descriptions.push(description);
+        self.data.push(data);
+        FontId(self.fonts.len() - 1)
+    }
+
+    fn shaping_layout(&self, layout: Layout<BuiltInLineBreaker>) -> ShapingLayout {
+        ShapingLayout {
+            layout,
+            font_data: self.data.clone(),
+        }
+    }
+
+    /// Splits texts so that each character is drawn with a font that has it, see [font_runs].
+    /// Returns the new texts, with the index of the text they come from and their offset in it.
+    /// The layout still breaks lines and shapes characters over the whole text, so splitting it changes neither.
+    fn with_fallbacks<'a, X: Clone>(
+        &self,
+        texts: &[Text<'a, X>],
+    ) -> (Vec<Text<'a, X>>, Vec<(usize, usize)>) {
+        if self.fallbacks.is_empty() {
+            let origins = (0..texts.len()).map(|index| (index, 0)).collect();
+            return (texts.to_vec(), origins);
+        }
+
+        let has_glyph = |id: FontId, character| match self.fonts.get(id.0) {
+            Some(font) => font.glyph_id(character).0 != 0,
+            None => false,
+        };
+        let mut split_texts = Vec::with_capacity(texts.len());
+        let mut origins = Vec::with_capacity(texts.len());
+        for (index, text) in texts.iter().enumerate() {
+            let mut chain = vec![text.font_id];
+            chain.extend(self.fallbacks.iter().filter(|id| **id != text.font_id));
+            for (range, font_id) in font_runs(text.text, &chain, has_glyph) {
+                origins.push((index, range.start));
+                split_texts.push(Text {
+                    text: &text.text[range],
+                    font_id,
+                    ..text.clone()
+                });
+            }
+        }
+        (split_texts, origins)
+    }
+}
+
+/// Splits a text in runs of characters drawn with the same font: the first of `fonts` that has them.
+/// Whitespace and control characters stay in the current run, and characters that no font has are drawn with the first font.
+fn font_runs(
+    text: &str,
+    fonts: &[FontId],
+    has_glyph: impl Fn(FontId, char) -> bool,
+) -> Vec<(std::ops::Range<usize>, FontId)> {
+    let mut runs: Vec<(std::ops::Range<usize>, FontId)> = Vec::new();
+    for (index, character) in text.char_indices() {
+        let end = index + character.len_utf8();
+        let current = runs.last().map(|(_, font_id)| *font_id);
+        let font_id = match current {
+            Some(font_id) if character.is_whitespace() || character.is_control() => font_id,
+            Some(font_id) if has_glyph(font_id, character) && font_id == fonts[0] => font_id,
+            _ => fonts
+                .iter()
+                .copied()
+                .find(|font_id| has_glyph(*font_id, character))
+                .unwrap_or(fonts[0]),
+        };
+        match runs.last_mut() {
+            Some((range, current)) if *current == font_id => range.end = end,
+            _ => runs.push((index..end, font_id)),
+        }
+    }
+    runs
+}
+
+impl Fonts {
+    /// Families that cover most scripts, for [Fonts::add_system_fallbacks].
+    pub const DEFAULT_FALLBACKS: &'static [&'static str] = &[
+        "Noto Sans",
+        "DejaVu Sans",
+        "Noto Sans CJK SC",
+        "Noto Sans Arabic",
+        "Noto Sans Hebrew",
+        "Noto Sans Devanagari",
+        "Noto Sans Thai",
+        "Noto Color Emoji",
+        "Noto Emoji",
+        "Symbola",
+    ];
+
+    /// The fonts shared with the backend.
+    pub fn global() -> &'static Fonts {
+        GLOBAL_FONTS.get_or_init(Fonts::default)
+    }
+
+    /// Registers a font, making it available to text sections with the returned id.
+    /// The description is what [Fonts::query] matches the font with.
+    ///
+    /// The shaper can't read the tables of fonts registered this way, so [shaped](TextOptions::shaping) text drawn with them has no ligatures, joining forms nor mark positioning, and its brackets are not mirrored in right to left text.
+    /// Prefer the `load_*` functions.
+    pub fn add_font(&self, font: FontArc, description: FontDescription) -> FontId {
+        let mut registry = self.registry.write().unwrap();
+        registry.register(font, description, None)
+    }
+
+    /// Loads a TTF or OTF font, reading its description from the font itself.
+    /// For font collections, only the first font is loaded.
+    ///
+    /// Fonts are never unloaded: their data is kept until the application exits.
+    /// Loading a font that is already loaded returns its id without keeping another copy.
+    pub fn load_font(&self, data: Vec<u8>) -> Result<FontId, FontError> {
+        self.load_font_with_index(data, 0)
+    }
+
+    /// Same as [Fonts::load_font], loading the font at an index of a font collection.
+    pub fn load_font_with_index(&self, data: Vec<u8>, index: u32) -> Result<FontId, FontError> {
+        let description = FontDescription::parse(&data, index)?;
+        let mut registry = self.registry.write().unwrap();
+        if let Some(id) = registry.find_data(&data, index) {
+            return Ok(id);
+        }
+        FontRef::try_from_slice_and_index(&data, index).map_err(|_| FontError::Invalid)?;
+        // Fonts are never removed, so their data lives as long as the application
+        let data: &'static [u8] = Box::leak(data.into_boxed_slice());
+        let font = FontRef::try_from_slice_and_index(data, index).unwrap();
+        Ok(registry.register(FontArc::new(font), description, Some((data, index))))
+    }
+
+    /// Makes a font the default one, [FontId(0)](FontId), unless a default font was already set.
+    /// Otherwise, the font is loaded like with [Fonts::load_static_font].
+    pub(crate) fn set_default_font(&self, data: &'static [u8]) -> Result<FontId, FontError> {
+        let description = FontDescription::parse(data, 0)?;
+        let font = FontRef::try_from_slice(data).map_err(|_| FontError::Invalid)?;
+        let mut registry = self.registry.write().unwrap();
+        if registry.has_default {
+            drop(registry);
+            return self.load_static_font(data);
+        }
+        registry.has_default = true;
+        let font = FontArc::new(font);
+        if registry.fonts.is_empty() {
+            registry.fonts.push(font);
+            registry.descriptions.push(description);
+            registry.data.push(Some((data, 0)));
+        } else {
+            registry.fonts[0] = font;
+            registry.descriptions[0] = description;
+            registry.data[0] = Some((data, 0));
+        }
+        Ok(FontId(0))
+    }
+
+    /// Same as [Fonts::load_font], without copying data that lives forever (like [include_bytes]).
+    pub fn load_static_font(&self, data: &'static [u8]) -> Result<FontId, FontError> {
+        self.load_static_font_with_index(data, 0)
+    }
+
+    fn load_static_font_with_index(
+        &self,
+        data: &'static [u8],
+        index: u32,
+    ) -> Result<FontId, FontError> {
+        let description = FontDescription::parse(data, index)?;
+        let font =
+            FontRef::try_from_slice_and_index(data, index).map_err(|_| FontError::Invalid)?;
+        let mut registry = self.registry.write().unwrap();
+        if let Some(id) = registry.find_data(data, index) {
+            return Ok(id);
+        }
+        Ok(registry.register(FontArc::new(font), description, Some((data, index))))
+    }
+
+    /// Loads a TTF or OTF font from a file, see [Fonts::load_font].
+    pub fn load_font_file<P: AsRef<std::path::Path>>(&self, path: P) -> Result<FontId, FontError> {
+        self.load_font(std::fs::read(path)?)
+    }
+
+    /// Finds the font of a family that is the closest to a weight and a style.
+    /// Families are compared without case, and [None] is returned if no font of the family is registered.
+    pub fn query(&self, family: &str, weight: u16, style: FontStyle) -> Option<FontId> {
+        let registry = self.registry.read().unwrap();
+        registry
+            .descriptions
+            .iter()
+            .enumerate()
+            .filter(|(_, description)| description.family.eq_ignore_ascii_case(family))
+            .min_by_key(|(_, description)| description.distance(weight, style))
+            .map(|(index, _)| FontId(index))
+    }
+
+    /// The fonts installed on the system.
+    /// Font directories are scanned the first time this is called, which can take a while.
+    /// The fonts found are cached in a file of the user's cache directory, so that the next runs only read the files that changed.
+    pub fn system_fonts(&self) -> &[SystemFont] {
+        self.system_fonts.get_or_init(scan_system_fonts)
+    }
+
+    /// Loads the installed font of a family that is the closest to a weight and a style (see [Fonts::query]).
+    /// Fonts that are already registered are not loaded again.
+    pub fn load_system_font(
+        &self,
+        family: &str,
+        weight: u16,
+        style: FontStyle,
+    ) -> Result<FontId, FontError> {
+        let system_font = self
+            .system_fonts()
+            .iter()
+            .filter(|font| font.description.family.eq_ignore_ascii_case(family))
+            .min_by_key(|font| font.description.distance(weight, style))
+            .ok_or(FontError::NotFound)?;
+        if let Some(id) = self.query(family, weight, style) {
+            if self.description(id).as_ref() == Some(&system_font.description) {
+                return Ok(id);
+            }
+        }
+        let data = std::fs::read(&system_font.path)?;
+        self.load_font_with_index(data, system_font.index)
+    }
+
+    /// Loads the regular fonts of installed families, and appends them to the fallbacks.
+    /// Families that are not installed are skipped, and so are the ones whose glyphs can't be drawn, like PNG emoji without the `png` feature.
+    /// [Fonts::DEFAULT_FALLBACKS] is a good choice to display text in any language.
+    pub fn add_system_fallbacks(&self, families: &[&str]) {
+        let mut fallbacks = self.fallbacks();
+        for family in families {
+            if let Ok(id) = self.load_system_font(family, 400, FontStyle::Normal) {
+                let drawable = match self.registry.read().unwrap().data.get(id.0) {
+                    Some(Some((data, index))) => can_draw_glyphs(data, *index),
+                    _ => true,
+                };
+                if drawable && !fallbacks.contains(&id) {
+                    fallbacks.push(id);
+                }
+            }
+        }
+        self.set_fallbacks(fallbacks);
+    }
+
+    /// Sets the fonts used to draw the characters that the font of a text doesn't have, in the order they are tried.
+    pub fn set_fallbacks(&self, fallbacks: Vec<FontId>) {
+        self.registry.write().unwrap().fallbacks = fallbacks;
+    }
+
+    pub fn fallbacks(&self) -> Vec<FontId> {
+        self.registry.read().unwrap().fallbacks.clone()
+    }
+
+    /// Splits the texts of a section so that each character is drawn with a font that has it.
+    pub(super) fn apply_fallbacks<X: Clone>(&self, section: &mut Section<X>) {
+        let registry = self.registry.read().unwrap();
+        section.text = registry.with_fallbacks(&section.text).0;
+    }
+
+    pub fn font(&self, id: FontId) -> Option<FontArc> {
+        self.registry.read().unwrap().fonts.get(id.0).cloned()
+    }
+
+    pub fn description(&self, id: FontId) -> Option<FontDescription> {
+        self.registry
+            .read()
+            .unwrap()
+            .descriptions
+            .get(id.0)
+            .cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.registry.read().unwrap().fonts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The registered fonts, in the order of their ids.
+    pub(crate) fn all(&self) -> Vec<FontArc> {
+        self.registry.read().unwrap().fonts.clone()
+    }
+
+    /// Measures a text drawn with a single font.
+    /// The text wraps at `max_width` pixels if specified, and at line breaks.
+    pub fn measure(
+        &self,
+        text: &str,
+        font: FontId,
+        scale: f32,
+        max_width: Option<f32>,
+    ) -> TextMetrics {
+        let section = Section::default()
+            .with_bounds((max_width.unwrap_or(f32::INFINITY), f32::INFINITY))
+            .add_text(Text::new(text).with_font_id(font).with_scale(scale));
+        self.measure_section(&section)
+    }
+
+    /// Measures a section exactly as [WgpuBackend::add_text](super::WgpuBackend::add_text) would lay it out, relatively to its `screen_position`.
+    /// Unknown font ids are measured with the default font.
+    pub fn measure_section<X: Clone>(&self, section: &Section<X>) -> TextMetrics {
+        let registry = self.registry.read().unwrap();
+        registry.measure(section, &section.layout)
+    }
+
+    /// Same as [Fonts::measure_section], for sections drawn with [WgpuBackend::add_shaped_text](super::WgpuBackend::add_shaped_text).
+    /// Glyphs are in the order of the text, which is not their visual order in right to left text.
+    pub fn measure_shaped_section<X: Clone>(&self, section: &Section<X>) -> TextMetrics {
+        let registry = self.registry.read().unwrap();
+        registry.measure(section, &registry.shaping_layout(section.layout))
+    }
+
+    /// The files of the registered fonts, in the order of their ids.
+    pub(super) fn font_data(&self) -> Vec<FontData> {
+        self.registry.read().unwrap().data.clone()
+    }
+
+    /// The layout placing glyphs of shaped text.
+    pub(super) fn shaping_layout(&self, layout: Layout<BuiltInLineBreaker>) -> ShapingLayout {
+        self.registry.read().unwrap().shaping_layout(layout)
+    }
+
+    /// Where underlines and strikethroughs of a font are drawn.
+    /// Fonts that don't tell it, or whose data is unknown, get usual values.
+    pub(super) fn decoration_metrics(&self, id: FontId, scale: f32) -> DecorationMetrics {
+        let mut metrics = DecorationMetrics {
+            underline: (scale * 0.1, scale / 16.0),
+            strikethrough: (-scale * 0.25, scale / 16.0),
+        };
+        let registry = self.registry.read().unwrap();
+        let (font, data) = match (registry.fonts.get(id.0), registry.data.get(id.0)) {
+            (Some(font), Some(Some(data))) => (font, *data),
+            _ => return metrics,
+        };
+        let face = match ttf_parser::Face::parse(data.0, data.1) {
+            Ok(face) => face,
+            Err(_) => return metrics,
+        };
+
+        // Font units have the Y axis pointing up
+        let factor = font.as_scaled(scale).v_scale_factor();
+        let to_pixels = |line: ttf_parser::LineMetrics| {
+            (
+                -line.position as f32 * factor,
+                (line.thickness as f32 * factor).max(1.0),
+            )
+        };
+        if let Some(underline) = face.underline_metrics() {
+            metrics.underline = to_pixels(underline);
+        }
+        if let Some(strikethrough) = face.strikeout_metrics() {
+            metrics.strikethrough = to_pixels(strikethrough);
+        }
+        metrics
+    }
+
+    /// The text and scale of an inline box, which reserves an advance and an ascent in pixels, see [Span::inline_box](super::Span::inline_box).
+    /// It is a no-break space, or a space if the font has none, scaled differently horizontally and vertically.
+    pub(super) fn inline_box(
+        &self,
+        id: FontId,
+        (advance, ascent): (f32, f32),
+    ) -> (&'static str, PxScale) {
+        let registry = self.registry.read().unwrap();
+        let font = match registry.fonts.get(id.0).or_else(|| registry.fonts.first()) {
+            Some(font) => font,
+            None => return (" ", PxScale::from(ascent)),
+        };
+        let (text, glyph) = match font.glyph_id('\u{a0}') {
+            glyph if glyph.0 != 0 => ("\u{a0}", glyph),
+            _ => (" ", font.glyph_id(' ')),
+        };
+        // A scale gives the height of the font, from its descent to its ascent
+        let height = font.height_unscaled();
+        let x = advance.max(0.0) * height / font.h_advance_unscaled(glyph).max(1.0);
+        let y = ascent.max(0.0) * height / font.ascent_unscaled().max(1.0);
+        (text, PxScale { x, y })
+    }
+
+    /// Measures a text drawn with [TextOptions].
+    fn measure_with_options(
+        &self,
+        text: &str,
+        options: &TextOptions,
+        max_width: Option<f32>,
+    ) -> TextMetrics {
+        let section = Section::default()
+            .with_bounds((max_width.unwrap_or(f32::INFINITY), f32::INFINITY))
+            .add_text(
+                Text::new(text)
+                    .with_font_id(options.font)
+                    .with_scale(options.scale),
+            );
+        match options.shaping {
+            true => self.measure_shaped_section(&section),
+            false => self.measure_section(&section),
+        }
+    }
+
+    /// Splits a text in the lines drawn by [WgpuBackend::add_text_in_rect](super::WgpuBackend::add_text_in_rect), and places them in the rectangle.
+    pub(super) fn fit_in_rect(
+        &self,
+        text: &str,
+        rect: Rect,
+        options: &TextOptions,
+    ) -> Vec<FittedLine> {
+        let max_width = rect.width();
+        let measure = |text: &str| self.measure_with_options(text, options, None);
+        let metrics = self.measure_with_options(
+            text,
+            options,
+            if options.wrap { Some(max_width) } else { None },
+        );
+
+        // A line goes from its first character to the first character of the next line
+        let mut starts: Vec<usize> = Vec::with_capacity(metrics.lines.len());
+        for line in &metrics.lines {
+            let glyphs = &metrics.glyphs[line.glyphs.clone()];
+            let start = match glyphs.iter().map(|glyph| glyph.byte_index).min() {
+                Some(start) => start,
+                // Blank lines start after the line break of the previous line
+                None => match starts.last() {
+                    Some(previous) => text[*previous..]
+                        .find('\n')
+                        .map_or(text.len(), |index| previous + index + 1),
+                    None => 0,
+                },
+            };
+            starts.push(start);
+        }
+        let lines: Vec<(&str, &LineMetrics)> = metrics
+            .lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| {
+                let start = starts[i];
+                let end = starts.get(i + 1).copied().unwrap_or(text.len());
+                (text[start..end].trim_end(), line)
+            })
+            .collect();
+
+        let mut visible = lines.len();
+        if let Some(max_lines) = options.max_lines {
+            visible = visible.min(max_lines);
+        }
+        if options.ellipsis {
+            while visible > 1 && lines[visible - 1].1.bounds.max.1 > rect.height() {
+                visible -= 1;
+            }
+        }
+        let truncated = visible < lines.len();
+
+        let height = match lines[..visible].last() {
+            Some((_, line)) => line.bounds.max.1,
+            None => 0.0,
+        };
+        let top = rect.min.1
+            + (rect.height() - height)
+                * match options.v_align {
+                    VerticalAlign::Top => 0.0,
+                    VerticalAlign::Center => 0.5,
+                    VerticalAlign::Bottom => 1.0,
+                };
+
+        let mut fitted = Vec::with_capacity(visible);
+        for (i, (line_text, line)) in lines[..visible].iter().enumerate() {
+            let mut line_text = line_text.to_string();
+            let mut width = measure(&line_text).width();
+            if options.ellipsis && (width > max_width || (truncated && i + 1 == visible)) {
+                line_text = self.ellipsize(&line_text, options, max_width);
+                width = measure(&line_text).width();
+            }
+            let left = rect.min.0
+                + (max_width - width)
+                    * match options.h_align {
+                        HorizontalAlign::Left => 0.0,
+                        HorizontalAlign::Center => 0.5,
+                        HorizontalAlign::Right => 1.0,
+                    };
+            fitted.push(FittedLine {
+                text: line_text,
+                position: (left, top + line.bounds.min.1),
+            });
+        }
+        fitted
+    }
+
+    /// Cuts the end of a line so that it fits in `max_width` with an ellipsis, which is added.
+    fn ellipsize(&self, line: &str, options: &TextOptions, max_width: f32) -> String {
+        let has_ellipsis = self
+            .font(options.font)
+            .map(|font| font.glyph_id('…').0 != 0)
+            .unwrap_or(false);
+        let ellipsis = if has_ellipsis { "…" } else { "..." };
+        let ellipsis_width = self.measure_with_options(ellipsis, options, None).width();
+
+        // Keep the characters that fit, in the order of the text which may not be the visual order
+        let mut glyphs = self.measure_with_options(line, options, None).glyphs;
+        glyphs.sort_by_key(|glyph| glyph.byte_index);
+        let mut width = 0.0;
+        let end = glyphs
+            .iter()
+            .take_while(|glyph| {
+                width += glyph.bounds.width();
+                width + ellipsis_width <= max_width
+            })
+            .last()
+            .map(|glyph| {
+                let character = line[glyph.byte_index..].chars().next();
+                glyph.byte_index + character.map(char::len_utf8).unwrap_or(0)
+            })
+            .unwrap_or(0);
+        format!("{}{}", line[..end].trim_end(), ellipsis)
+    }
+}
+
+/// Options of [WgpuBackend::add_text_in_rect](super::WgpuBackend::add_text_in_rect).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextOptions {
+    pub font: FontId,
+    /// The height of the text, in pixels.
+    pub scale: f32,
+    /// In sRGB, see [Color](super::Color).
+    pub color: [f32; 4],
+    pub h_align: HorizontalAlign,
+    pub v_align: VerticalAlign,
+    /// Breaks the lines that are wider than the rectangle.
+    pub wrap: bool,
+    /// The maximum number of lines to draw, the next ones are dropped.
+    pub max_lines: Option<usize>,
+    /// Ends cut lines with an ellipsis.
+    /// Lines are cut when they are wider than the rectangle (if they don't wrap), or before dropped lines.
+    /// With this option, lines that don't fit in the height of the rectangle are dropped, except the first one.
+    pub ellipsis: bool,
+    /// Hides the text that goes beyond the rectangle.
+    /// Off by default, since most text fits in its rectangle once wrapped or cut with an ellipsis.
+    pub clip: bool,
+    /// Shapes the text with the tables of its font, and orders bidirectional text.
+    /// This is needed for scripts like Arabic or Devanagari, and draws ligatures, but is slower.
+    pub shaping: bool,
+    /// Draws the text with distance fields and these effects, see [WgpuBackend::add_sdf_text](super::WgpuBackend::add_sdf_text).
+    /// This keeps the text sharp when it is scaled by transforms.
+    pub sdf: Option<TextEffects>,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        TextOptions {
+            font: FontId(0),
+            scale: 16.0,
+            color: [0.0, 0.0, 0.0, 1.0],
+            h_align: HorizontalAlign::Left,
+            v_align: VerticalAlign::Top,
+            wrap: true,
+            max_lines: None,
+            ellipsis: false,
+            clip: false,
+            shaping: false,
+            sdf: None,
+        }
+    }
+}
+
+/// The offset from the baseline (positive being down) and the thickness of text decorations, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct DecorationMetrics {
+    pub(super) underline: (f32, f32),
+    pub(super) strikethrough: (f32, f32),
+}
+
+/// A line of text placed by [Fonts::fit_in_rect].
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct FittedLine {
+    pub(super) text: String,
+    /// The top left corner of the line.
+    pub(super) position: (f32, f32),
+}
+
+/// The layout of a text, as measured by [Fonts::measure].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextMetrics {
+    /// The smallest rectangle containing all lines.
+    pub bounds: Rect,
+    /// The glyphs, in the order of the text.
+    /// Line breaks have no glyph.
+    pub glyphs: Vec<GlyphMetrics>,
+    pub lines: Vec<LineMetrics>,
+}
+
+impl Default for TextMetrics {
+    fn default() -> Self {
+        TextMetrics {
+            bounds: Rect::sized(0.0, 0.0, 0.0, 0.0),
+            glyphs: Vec::new(),
+            lines: Vec::new(),
+        }
+    }
+}
+
+impl TextMetrics {
+    pub fn width(&self) -> f32 {
+        self.bounds.width()
+    }
+
+    pub fn height(&self) -> f32 {
+        self.bounds.height()
+    }
+}
+
+/// A glyph measured by [Fonts::measure].
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlyphMetrics {
+    /// The index of the [Text] of the section this glyph comes from.
+    pub section_index: usize,
+    /// The index of the character of this glyph in its [Text].
+    pub byte_index: usize,
+    pub font_id: FontId,
+    /// The space taken by the glyph: its advance horizontally, and the height of its font vertically.
+    pub bounds: Rect,
+}
+
+/// A line measured by [Fonts::measure].
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineMetrics {
+    /// The vertical position of the baseline.
+    pub baseline: f32,
+    /// The highest ascent of the fonts of the line, above the baseline.
+    pub ascent: f32,
+    /// The lowest descent of the fonts of the line, usually negative since it is below the baseline.
+    pub descent: f32,
+    pub line_gap: f32,
+    pub bounds: Rect,
+    /// The range of the glyphs of the line in [TextMetrics::glyphs].
+    pub glyphs: std::ops::Range<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fonts() -> Fonts {
+        let fonts = Fonts::default();
+        fonts
+            .set_default_font(include_bytes!("ressources/Inconsolata-Regular.ttf"))
+            .unwrap();
+        fonts
+    }
+
+    #[test]
+    fn test_measure_single_line() {
+        let fonts = fonts();
+        let metrics = fonts.measure("Hello", FontId(0), 20.0, None);
+        assert_eq!(metrics.lines.len(), 1);
+        assert_eq!(metrics.glyphs.len(), 5);
+        assert_eq!(metrics.glyphs[4].byte_index, 4);
+
+        // Inconsolata is monospaced
+        let advance = metrics.glyphs[0].bounds.width();
+        assert!(advance > 0.0);
+        assert!((metrics.width() - 5.0 * advance).abs() < 0.01);
+        let line = &metrics.lines[0];
+        assert!((metrics.height() - (line.ascent - line.descent)).abs() < 0.01);
+        assert_eq!(metrics.bounds.min, (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_measure_wrapped() {
+        let fonts = fonts();
+        let single_line = fonts.measure("one two three", FontId(0), 20.0, None);
+        let wrapped = fonts.measure(
+            "one two three",
+            FontId(0),
+            20.0,
+            Some(single_line.width() / 2.0),
+        );
+        assert!(wrapped.lines.len() > 1);
+        assert!(wrapped.width() <= single_line.width() / 2.0);
+        assert!(wrapped.lines[1].baseline > wrapped.lines[0].baseline);
+        assert_eq!(
+            wrapped.lines.last().unwrap().glyphs.end,
+            wrapped.glyphs.len()
+        );
+
+        let broken = fonts.measure("a\nb", FontId(0), 20.0, None);
+        assert_eq!(broken.lines.len(), 2);
+        assert_eq!(broken.glyphs[1].byte_index, 2);
+
+        // Blank lines are measured too
+        let blank = fonts.measure("a\n\nb\n", FontId(0), 20.0, None);
+        assert_eq!(blank.lines.len(), 4);
+        assert_eq!(blank.lines[1].glyphs, 1..1);
+        let line_height = broken.lines[1].baseline - broken.lines[0].baseline;
+        for (i, line) in blank.lines.iter().enumerate() {
+            let baseline = broken.lines[0].baseline + i as f32 * line_height;
+            assert!((line.baseline - baseline).abs() < 0.01);
+        }
+        assert_eq!(fonts.measure("\n", FontId(0), 20.0, None).lines.len(), 2);
+
+        assert_eq!(
+            Fonts::default().measure("a", FontId(0), 20.0, None),
+            TextMetrics::default()
+        );
+    }
+
+    #[test]
+    fn test_fit_in_rect() {
+        let fonts = fonts();
+        let advance = fonts.measure("a", FontId(0), 20.0, None).width();
+        let line_height = fonts.measure("a", FontId(0), 20.0, None).height();
+        let rect = Rect::sized(10.0, 10.0, 10.5 * advance, 100.0);
+
+        let lines = fonts.fit_in_rect(
+            "one two three",
+            rect,
+            &TextOptions {
+                scale: 20.0,
+                ..TextOptions::default()
+            },
+        );
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].text, "one two");
+        assert_eq!(lines[1].text, "three");
+        assert_eq!(lines[0].position, (10.0, 10.0));
+        assert!(lines[1].position.1 >= 10.0 + line_height);
+
+        // Blank lines are kept
+        let options = TextOptions {
+            scale: 20.0,
+            ..TextOptions::default()
+        };
+        let lines = fonts.fit_in_rect("one\n\ntwo", rect, &options);
+        let texts: Vec<&str> = lines.iter().map(|line| line.text.as_str()).collect();
+        assert_eq!(texts, vec!["one", "", "two"]);
+        assert!((lines[2].position.1 - (10.0 + 2.0 * line_height)).abs() < 0.01);
+
+        // Alignment
+        let options = TextOptions {
+            scale: 20.0,
+            h_align: HorizontalAlign::Right,
+            v_align: VerticalAlign::Bottom,
+            ..TextOptions::default()
+        };
+        let lines = fonts.fit_in_rect("abc", rect, &options);
+        assert!((lines[0].position.0 - (rect.max.0 - 3.0 * advance)).abs() < 0.01);
+        assert!((lines[0].position.1 - (rect.max.1 - line_height)).abs() < 0.01);
+
+        // Single line truncation
+        let options = TextOptions {
+            scale: 20.0,
+            wrap: false,
+            ellipsis: true,
+            ..TextOptions::default()
+        };
+        let lines = fonts.fit_in_rect("one two three four", rect, &options);
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].text, "one two t…");
+
+        // Multiline truncation
+        let options = TextOptions {
+            scale: 20.0,
+            max_lines: Some(2),
+            ellipsis: true,
+            ..TextOptions::default()
+        };
+        let lines = fonts.fit_in_rect("one two three four five six", rect, &options);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1].text, "three fou…");
+        let short_rect = Rect::sized(10.0, 10.0, rect.width(), line_height * 1.5);
+        let lines = fonts.fit_in_rect("one two three four", short_rect, &options);
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].text, "one two…");
+    }
+
+    #[test]
+    fn test_query() {
+        let fonts = fonts();
+        let data = include_bytes!("ressources/Inconsolata-Regular.ttf");
+        let mut description = FontDescription::parse(data, 0).unwrap();
+        description.weight = 700;
+        let bold = fonts.add_font(FontArc::try_from_slice(data).unwrap(), description);
+
+        assert_eq!(
+            fonts.query("inconsolata", 400, FontStyle::Normal),
+            Some(FontId(0))
+        );
+        assert_eq!(
+            fonts.query("Inconsolata", 800, FontStyle::Italic),
+            Some(bold)
+        );
+        assert_eq!(fonts.query("Unknown", 400, FontStyle::Normal), None);
+        assert_eq!(fonts.description(bold).unwrap().weight, 700);
+        assert!(fonts.load_font(b"not a font".to_vec()).is_err());
+
+        // Loading the same font again doesn't register it twice
+        assert_eq!(fonts.load_font(data.to_vec()).unwrap(), FontId(0));
+        assert_eq!(fonts.load_static_font(data).unwrap(), FontId(0));
+    }
+
+    #[test]
+    fn test_default_font() {
+        let data = include_bytes!("ressources/Inconsolata-Regular.ttf");
+        let fonts = Fonts::default();
+        let mut description = FontDescription::parse(data, 0).unwrap();
+        description.family = "Other".to_string();
+        let other = fonts.add_font(FontArc::try_from_slice(data).unwrap(), description);
+
+        // The first id is kept for the default font
+        assert_eq!(other, FontId(1));
+        assert_eq!(fonts.query("Other", 400, FontStyle::Normal), Some(other));
+        assert_eq!(fonts.query("Inconsolata", 400, FontStyle::Normal), None);
+        assert_eq!(fonts.set_default_font(data).unwrap(), FontId(0));
+        assert_eq!(
+            fonts.query("Inconsolata", 400, FontStyle::Normal),
+            Some(FontId(0))
+        );
+        assert_eq!(fonts.len(), 2);
+    }
+
+    #[test]
+    fn test_font_runs() {
+        let (latin, cjk, emoji) = (FontId(0), FontId(1), FontId(2));
+        let has_glyph = |id: FontId, character: char| match id.0 {
+            0 => character.is_ascii(),
+            1 => character == '漢' || character == 'a',
+            _ => character == '😀',
+        };
+        let runs = |text| font_runs(text, &[latin, cjk, emoji], has_glyph);
+
+        assert_eq!(runs("ab"), vec![(0..2, latin)]);
+        assert_eq!(
+            runs("a漢 漢a😀"),
+            vec![(0..1, latin), (1..8, cjk), (8..9, latin), (9..13, emoji)]
+        );
+        // Missing characters are drawn with the first font
+        assert_eq!(runs("é"), vec![(0..2, latin)]);
+        assert_eq!(runs(""), vec![]);
+    }
+
+    #[test]
+    fn test_measure_with_fallbacks() {
+        let fonts = fonts();
+        let data = include_bytes!("ressources/Inconsolata-Regular.ttf");
+        let fallback = fonts.add_font(
+            FontArc::try_from_slice(data).unwrap(),
+            FontDescription::parse(data, 0).unwrap(),
+        );
+        fonts.set_fallbacks(vec![fallback]);
+
+        // Glyphs keep their position in the original text
+        let metrics = fonts.measure("a\u{10FFFF}b", FontId(0), 20.0, None);
+        assert_eq!(metrics.glyphs.len(), 3);
+        assert_eq!(metrics.glyphs[2].byte_index, 5);
+        assert_eq!(metrics.glyphs[2].section_index, 0);
+    }
+
+    #[test]
+    fn test_measure_shaped() {
+        let fonts = fonts();
+        let section = Section::default()
+            .with_bounds((120.0, f32::INFINITY))
+            .add_text(Text::new("one two three").with_scale(20.0));
+        let shaped = fonts.measure_shaped_section(&section);
+        let unshaped = fonts.measure_section(&section);
+        assert_eq!(shaped.lines.len(), unshaped.lines.len());
+        assert!((shaped.width() - unshaped.width()).abs() < 0.01);
+
+        // Lines of right to left text are cut in the order of the text
+        let options = TextOptions {
+            scale: 20.0,
+            shaping: true,
+            ellipsis: true,
+            wrap: false,
+            ..TextOptions::default()
+        };
+        let advance = fonts.measure("a", FontId(0), 20.0, None).width();
+        let lines = fonts.fit_in_rect(
+            "אבגדה",
+            Rect::sized(0.0, 0.0, 3.5 * advance, 100.0),
+            &options,
+        );
+        assert_eq!(lines[0].text, "אב…");
+    }
+}
@@ -1,12 +1,701 @@
+use super::color;
+use crate::rect::Rect;
 pub use std::sync::Arc;
 
+/// The format of the pixels of a texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextureFormat {
+    /// Red, green, blue and alpha bytes, in the sRGB color space. (default)
+    /// This is the format of most images.
+    #[default]
+    Rgba8Srgb,
+    /// Red, green, blue and alpha bytes, in a linear color space.
+    /// Useful for data that is not a picture, like normal maps.
+    Rgba8,
+    /// Blue, green, red and alpha bytes, in the sRGB color space.
+    /// This is the format of many video decoders and screen capture APIs.
+    Bgra8Srgb,
+    /// Blue, green, red and alpha bytes, in a linear color space.
+    Bgra8,
+    /// A single byte per pixel, drawn as white with this value as opacity.
+    /// The tint of [ImageOptions](super::ImageOptions) can be used to give it a color.
+    R8,
+}
+
+impl TextureFormat {
+    pub fn bytes_per_pixel(&self) -> u32 {
+        match self {
+            TextureFormat::R8 => 1,
+            _ => 4,
+        }
+    }
+
+    fn is_srgb(&self) -> bool {
+        matches!(self, TextureFormat::Rgba8Srgb | TextureFormat::Bgra8Srgb)
+    }
+
+    /// Falls back to [TextureFormat::Rgba8Srgb] for formats that have no equivalent.
+    pub(super) fn from_wgpu(format: wgpu::TextureFormat) -> TextureFormat {
+        match format {
+            wgpu::TextureFormat::Rgba8Unorm => TextureFormat::Rgba8,
+            wgpu::TextureFormat::Bgra8UnormSrgb => TextureFormat::Bgra8Srgb,
+            wgpu::TextureFormat::Bgra8Unorm => TextureFormat::Bgra8,
+            wgpu::TextureFormat::R8Unorm => TextureFormat::R8,
+            _ => TextureFormat::Rgba8Srgb,
+        }
+    }
+
+    pub(super) fn to_wgpu(self) -> wgpu::TextureFormat {
+        match self {
+            TextureFormat::Rgba8Srgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+            TextureFormat::Rgba8 => wgpu::TextureFormat::Rgba8Unorm,
+            TextureFormat::Bgra8Srgb => wgpu::TextureFormat::Bgra8UnormSrgb,
+            TextureFormat::Bgra8 => wgpu::TextureFormat::Bgra8Unorm,
+            TextureFormat::R8 => wgpu::TextureFormat::R8Unorm,
+        }
+    }
+}
+
+/// How a texture is sampled when it is not drawn at its exact size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FilterMode {
+    /// Pixels are blended smoothly. (default)
+    #[default]
+    Linear,
+    /// The closest pixel is used, which keeps pixel art sharp.
+    Nearest,
+}
+
+/// How a texture is sampled outside of its bounds.
+///
+/// Drawing a source rectangle bigger than the image (see [ImageOptions](super::ImageOptions)) samples outside of its bounds.
+/// Images packed in an atlas are always clamped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WrapMode {
+    /// The edge pixels are extended. (default)
+    #[default]
+    Clamp,
+    /// The texture is repeated, which is useful for tiled backgrounds.
+    Repeat,
+    /// The texture is repeated, mirroring it every other time.
+    MirrorRepeat,
+}
+
+/// Options of [WgpuBackend::create_texture_with_options](super::WgpuBackend::create_texture_with_options).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TextureOptions {
+    pub format: TextureFormat,
+    pub filter: FilterMode,
+    pub wrap: WrapMode,
+    /// Generates smaller versions of the texture, making it look better when it is drawn smaller than its size.
+    /// They are generated on the CPU when the texture is created, and when it is updated as a whole.
+    pub mipmaps: bool,
+}
+
+impl TextureOptions {
+    /// The number of mip levels of a texture created with these options.
+    pub(super) fn mip_level_count(&self, image_dimensions: (u32, u32)) -> u32 {
+        if self.mipmaps {
+            32 - image_dimensions
+                .0
+                .max(image_dimensions.1)
+                .max(1)
+                .leading_zeros()
+        } else {
+            1
+        }
+    }
+
+    pub(super) fn sampler_descriptor(&self) -> wgpu::SamplerDescriptor<'static> {
+        let filter = match self.filter {
+            FilterMode::Linear => wgpu::FilterMode::Linear,
+            FilterMode::Nearest => wgpu::FilterMode::Nearest,
+        };
+        let address_mode = match self.wrap {
+            WrapMode::Clamp => wgpu::AddressMode::ClampToEdge,
+            WrapMode::Repeat => wgpu::AddressMode::Repeat,
+            WrapMode::MirrorRepeat => wgpu::AddressMode::MirrorRepeat,
+        };
+        wgpu::SamplerDescriptor {
+            label: Some("Texture Sampler"),
+            address_mode_u: address_mode,
+            address_mode_v: address_mode,
+            address_mode_w: address_mode,
+            mag_filter: filter,
+            min_filter: filter,
+            mipmap_filter: filter,
+            ..Default::default()
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct TextureId {
     pub(super) id: Arc<usize>,
+    /// The GPU texture containing the image. It is shared by all images of an atlas.
+    pub(super) texture: usize,
+    /// The region of the GPU texture containing the image, in texture coordinates.
+    pub(super) tex_coords: Rect,
+    /// The position of the image in the GPU texture, in pixels.
+    pub(super) origin: (u32, u32),
+    size: (u32, u32),
+    format: TextureFormat,
+    pub(super) mip_level_count: u32,
+    /// Whether the colors are premultiplied by the alpha, which is the case of offscreen layers.
+    pub(super) premultiplied: bool,
 }
 
 impl TextureId {
-    pub(super) fn new(id: usize) -> TextureId {
-        TextureId { id: Arc::new(id) }
+    pub(super) fn new(id: usize, size: (u32, u32), format: TextureFormat) -> TextureId {
+        TextureId {
+            id: Arc::new(id),
+            texture: id,
+            tex_coords: Rect::sized(0.0, 0.0, 1.0, 1.0),
+            origin: (0, 0),
+            size,
+            format,
+            mip_level_count: 1,
+            premultiplied: false,
+        }
+    }
+
+    /// The dimensions of the image, in pixels.
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    /// Whether the image is packed in an atlas, sharing its GPU texture with other images.
+    pub(super) fn is_in_atlas(&self) -> bool {
+        *self.id != self.texture
+    }
+}
+
+/// The size of the textures atlases are made of.
+pub(super) const ATLAS_SIZE: u32 = 1024;
+
+/// Images larger than this (in any dimension) are never packed in an atlas.
+pub(super) const ATLAS_MAX_IMAGE_SIZE: u32 = 256;
+
+/// Space left around each image of an atlas so that neighbors don't bleed into each other when sampled.
+/// It is filled by repeating the edges of the image.
+pub(super) const ATLAS_PADDING: u32 = 1;
+
+/// A horizontal band of an atlas, containing images of similar heights.
+#[derive(Debug)]
+struct Shelf {
+    y: u32,
+    height: u32,
+    /// Free horizontal ranges (start, end), sorted and never adjacent.
+    free: Vec<(u32, u32)>,
+}
+
+/// Decides where images are placed in an atlas.
+/// Images are stored on shelves, which is efficient when many images have similar heights, like icons.
+#[derive(Debug)]
+pub(super) struct AtlasAllocator {
+    size: (u32, u32),
+    shelves: Vec<Shelf>,
+}
+
+impl AtlasAllocator {
+    pub(super) fn new(size: (u32, u32)) -> AtlasAllocator {
+        AtlasAllocator {
+            size,
+            shelves: Vec::new(),
+        }
+    }
+
+    /// Returns the position of a free rectangle of this size, or [None] if the atlas is full.
+    pub(super) fn allocate(&mut self, (width, height): (u32, u32)) -> Option<(u32, u32)> {
+        // Find the tightest existing shelf that is not too high for this image
+        let mut best: Option<(usize, usize)> = None;
+        for (shelf_index, shelf) in self.shelves.iter().enumerate() {
+            if shelf.height < height || shelf.height > height + height / 2 + 4 {
+                continue;
+            }
+            if let Some(range_index) = shelf.free.iter().position(|(s, e)| e - s >= width) {
+                match best {
+                    Some((best_shelf, _)) if self.shelves[best_shelf].height <= shelf.height => {}
+                    _ => best = Some((shelf_index, range_index)),
+                }
+            }
+        }
+
+        if let Some((shelf_index, range_index)) = best {
+            let shelf = &mut self.shelves[shelf_index];
+            let (start, end) = shelf.free[range_index];
+            if end - start == width {
+                shelf.free.remove(range_index);
+            } else {
+                shelf.free[range_index].0 += width;
+            }
+            return Some((start, shelf.y));
+        }
+
+        // Open a new shelf below the others
+        let y = self.shelves.last().map(|s| s.y + s.height).unwrap_or(0);
+        if width > self.size.0 || y + height > self.size.1 {
+            return None;
+        }
+        let mut free = Vec::new();
+        if width < self.size.0 {
+            free.push((width, self.size.0));
+        }
+        self.shelves.push(Shelf { y, height, free });
+        Some((0, y))
+    }
+
+    /// Makes room for more shelves below the existing ones.
+    pub(super) fn grow(&mut self, height: u32) {
+        self.size.1 = self.size.1.max(height);
+    }
+
+    /// Frees a rectangle previously returned by [AtlasAllocator::allocate].
+    pub(super) fn deallocate(&mut self, (x, y): (u32, u32), width: u32) {
+        let shelf_index = match self.shelves.iter().position(|shelf| shelf.y == y) {
+            Some(shelf_index) => shelf_index,
+            None => return,
+        };
+        let shelf = &mut self.shelves[shelf_index];
+
+        // Insert the range, merging it with its neighbors
+        let index = shelf
+            .free
+            .iter()
+            .position(|(s, _)| *s > x)
+            .unwrap_or(shelf.free.len());
+        shelf.free.insert(index, (x, x + width));
+        if index + 1 < shelf.free.len() && shelf.free[index].1 == shelf.free[index + 1].0 {
+            shelf.free[index].1 = shelf.free[index + 1].1;
+            shelf.free.remove(index + 1);
+        }
+        if index > 0 && shelf.free[index - 1].1 == shelf.free[index].0 {
+            shelf.free[index - 1].1 = shelf.free[index].1;
+            shelf.free.remove(index);
+        }
+
+        // Remove empty shelves at the bottom so that their space can be used by images of any height
+        while let Some(shelf) = self.shelves.last() {
+            if shelf.free.len() == 1 && shelf.free[0] == (0, self.size.0) {
+                self.shelves.pop();
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+/// An image stored in an [Atlas].
+#[derive(Debug)]
+struct AtlasAllocation {
+    texture_id: TextureId,
+    position: (u32, u32),
+    /// Size of the image, padding included.
+    size: (u32, u32),
+}
+
+/// A GPU texture shared by many small images.
+#[derive(Debug)]
+pub(super) struct Atlas {
+    /// The GPU texture of the atlas.
+    pub(super) texture: TextureId,
+    allocator: AtlasAllocator,
+    allocations: Vec<AtlasAllocation>,
+}
+
+impl Atlas {
+    pub(super) fn new(texture: TextureId) -> Atlas {
+        Atlas {
+            texture,
+            allocator: AtlasAllocator::new((ATLAS_SIZE, ATLAS_SIZE)),
+            allocations: Vec::new(),
+        }
+    }
+
+    /// Reserves space for an image and returns the position where its padded data must be written.
+    pub(super) fn allocate(
+        &mut self,
+        id: usize,
+        image_dimensions: (u32, u32),
+    ) -> Option<(TextureId, (u32, u32))> {
+        let padded_size = (
+            image_dimensions.0 + 2 * ATLAS_PADDING,
+            image_dimensions.1 + 2 * ATLAS_PADDING,
+        );
+        let position = self.allocator.allocate(padded_size)?;
+
+        let min = (
+            (position.0 + ATLAS_PADDING) as f32 / ATLAS_SIZE as f32,
+            (position.1 + ATLAS_PADDING) as f32 / ATLAS_SIZE as f32,
+        );
+        let texture_id = TextureId {
+            id: Arc::new(id),
+            texture: *self.texture.id,
+            tex_coords: Rect {
+                min,
+                max: (
+                    min.0 + image_dimensions.0 as f32 / ATLAS_SIZE as f32,
+                    min.1 + image_dimensions.1 as f32 / ATLAS_SIZE as f32,
+                ),
+            },
+            origin: (position.0 + ATLAS_PADDING, position.1 + ATLAS_PADDING),
+            size: image_dimensions,
+            format: TextureFormat::Rgba8Srgb,
+            mip_level_count: 1,
+            premultiplied: false,
+        };
+        self.allocations.push(AtlasAllocation {
+            texture_id: texture_id.clone(),
+            position,
+            size: padded_size,
+        });
+
+        Some((texture_id, position))
+    }
+
+    /// Frees the space of images that are not used anymore.
+    pub(super) fn sweep(&mut self) {
+        let allocator = &mut self.allocator;
+        self.allocations.retain(|allocation| {
+            if Arc::strong_count(&allocation.texture_id.id) > 1 {
+                true
+            } else {
+                allocator.deallocate(allocation.position, allocation.size.0);
+                false
+            }
+        });
+    }
+}
+
+const GLYPH_ATLAS_WIDTH: u32 = 1024;
+const GLYPH_ATLAS_MAX_HEIGHT: u32 = 8192;
+
+/// The pixels of glyphs rasterized by the backend, like distance fields or color glyphs, uploaded to a texture.
+/// Unlike an [Atlas], images are never removed, and the atlas grows when it is full.
+#[derive(Debug)]
+pub(super) struct GlyphAtlas {
+    size: (u32, u32),
+    bytes_per_pixel: u32,
+    pixels: Vec<u8>,
+    allocator: AtlasAllocator,
+    /// The rows changed since the last upload.
+    dirty_rows: Option<(u32, u32)>,
+}
+
+impl GlyphAtlas {
+    pub(super) fn new(bytes_per_pixel: u32) -> GlyphAtlas {
+        let size = (GLYPH_ATLAS_WIDTH, 512);
+        GlyphAtlas {
+            size,
+            bytes_per_pixel,
+            pixels: vec![0; (size.0 * size.1 * bytes_per_pixel) as usize],
+            allocator: AtlasAllocator::new(size),
+            dirty_rows: None,
+        }
+    }
+
+    pub(super) fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    pub(super) fn bytes_per_row(&self) -> u32 {
+        self.size.0 * self.bytes_per_pixel
+    }
+
+    /// Copies an image to the atlas and returns its position, or [None] if the atlas can't grow anymore.
+    pub(super) fn insert(
+        &mut self,
+        (width, height): (u32, u32),
+        pixels: &[u8],
+    ) -> Option<(u32, u32)> {
+        // Images too large for the atlas don't make it grow for nothing
+        if !self.could_fit((width, height)) {
+            return None;
+        }
+        // A pixel is left between images so that they don't bleed into each other when sampled
+        let (x, y) = loop {
+            match self.allocator.allocate((width + 1, height + 1)) {
+                Some(position) => break position,
+                None if self.size.1 < GLYPH_ATLAS_MAX_HEIGHT => self.grow(),
+                None => return None,
+            }
+        };
+        let row_length = (width * self.bytes_per_pixel) as usize;
+        for row in 0..height {
+            let start = (((y + row) * self.size.0 + x) * self.bytes_per_pixel) as usize;
+            let source = row as usize * row_length;
+            self.pixels[start..start + row_length]
+                .copy_from_slice(&pixels[source..source + row_length]);
+        }
+        self.dirty_rows = Some(match self.dirty_rows {
+            Some((start, end)) => (start.min(y), end.max(y + height)),
+            None => (y, y + height),
+        });
+        Some((x, y))
+    }
+
+    /// Tells whether an image fits in the atlas once it is empty and has grown as much as it can.
+    pub(super) fn could_fit(&self, (width, height): (u32, u32)) -> bool {
+        width < self.size.0 && height < GLYPH_ATLAS_MAX_HEIGHT
+    }
+
+    /// Removes all the images, keeping the size of the atlas.
+    pub(super) fn clear(&mut self) {
+        self.pixels.iter_mut().for_each(|byte| *byte = 0);
+        self.allocator = AtlasAllocator::new(self.size);
+        self.dirty_rows = Some((0, self.size.1));
+    }
+
+    /// Doubles the height of the atlas, keeping its images in place.
+    fn grow(&mut self) {
+        self.size.1 *= 2;
+        self.pixels.resize(
+            (self.size.0 * self.size.1 * self.bytes_per_pixel) as usize,
+            0,
+        );
+        self.allocator.grow(self.size.1);
+        self.dirty_rows = Some((0, self.size.1));
+    }
+
+    /// Returns the first row changed since the last call and the pixels of the changed rows.
+    pub(super) fn take_changes(&mut self) -> Option<(u32, &[u8])> {
+        let (start, end) = self.dirty_rows.take()?;
+        let row_length = (self.size.0 * self.bytes_per_pixel) as usize;
+        Some((
+            start,
+            &self.pixels[start as usize * row_length..end as usize * row_length],
+        ))
+    }
+}
+
+pub(super) fn srgb_to_linear(value: u8) -> f32 {
+    color::srgb_to_linear(value as f32 / 255.0)
+}
+
+pub(super) fn linear_to_srgb(value: f32) -> u8 {
+    (color::linear_to_srgb(value) * 255.0)
+        .round()
+        .clamp(0.0, 255.0) as u8
+}
+
+/// Halves the size of an image, averaging each square of four pixels.
+/// Color channels of sRGB images are averaged in linear space.
+pub(super) fn downsample(
+    image_dimensions: (u32, u32),
+    image_data: &[u8],
+    format: TextureFormat,
+) -> ((u32, u32), Vec<u8>) {
+    let bytes_per_pixel = format.bytes_per_pixel() as usize;
+    let (width, height) = (image_dimensions.0 as usize, image_dimensions.1 as usize);
+    let dimensions = ((width / 2).max(1), (height / 2).max(1));
+
+    let mut data = Vec::with_capacity(dimensions.0 * dimensions.1 * bytes_per_pixel);
+    for y in 0..dimensions.1 {
+        for x in 0..dimensions.0 {
+            for channel in 0..bytes_per_pixel {
+                let linear = format.is_srgb() && channel < 3;
+                let mut sum = 0.0;
+                for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)].iter() {
+                    let source_x = (x * 2 + dx).min(width - 1);
+                    let source_y = (y * 2 + dy).min(height - 1);
+                    let value =
+                        image_data[(source_y * width + source_x) * bytes_per_pixel + channel];
+                    sum += if linear {
+                        srgb_to_linear(value)
+                    } else {
+                        value as f32 / 255.0
+                    };
+                }
+                data.push(if linear {
+                    linear_to_srgb(sum / 4.0)
+                } else {
+                    (sum / 4.0 * 255.0).round() as u8
+                });
+            }
+        }
+    }
+
+    ((dimensions.0 as u32, dimensions.1 as u32), data)
+}
+
+/// Copies a rectangle `(x, y, width, height)` out of an image.
+pub(super) fn crop(
+    image_dimensions: (u32, u32),
+    image_data: &[u8],
+    bytes_per_pixel: u32,
+    (x, y, width, height): (u32, u32, u32, u32),
+) -> Vec<u8> {
+    let row_length = (image_dimensions.0 * bytes_per_pixel) as usize;
+    let mut cropped = Vec::with_capacity((width * height * bytes_per_pixel) as usize);
+    for row in y..y + height {
+        let start = row as usize * row_length + (x * bytes_per_pixel) as usize;
+        cropped.extend_from_slice(&image_data[start..start + (width * bytes_per_pixel) as usize]);
+    }
+    cropped
+}
+
+/// Surrounds an RGBA image with a border made of copies of its edge pixels.
+pub(super) fn pad_image(image_dimensions: (u32, u32), image_rgba: &[u8], padding: u32) -> Vec<u8> {
+    let (width, height) = (image_dimensions.0 as usize, image_dimensions.1 as usize);
+    let padding = padding as usize;
+    let padded_width = width + 2 * padding;
+    let padded_height = height + 2 * padding;
+
+    let mut padded = Vec::with_capacity(padded_width * padded_height * 4);
+    for y in 0..padded_height {
+        let source_y = y.saturating_sub(padding).min(height - 1);
+        for x in 0..padded_width {
+            let source_x = x.saturating_sub(padding).min(width - 1);
+            let i = (source_y * width + source_x) * 4;
+            padded.extend_from_slice(&image_rgba[i..i + 4]);
+        }
+    }
+    padded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allocator() {
+        let mut allocator = AtlasAllocator::new((100, 100));
+        assert_eq!(allocator.allocate((40, 20)), Some((0, 0)));
+        assert_eq!(allocator.allocate((40, 20)), Some((40, 0)));
+        // Too wide for the first shelf
+        assert_eq!(allocator.allocate((40, 20)), Some((0, 20)));
+        // Too small for the existing shelves
+        assert_eq!(allocator.allocate((10, 5)), Some((0, 40)));
+        // Too high for the remaining space
+        assert_eq!(allocator.allocate((10, 60)), None);
+
+        allocator.deallocate((40, 0), 40);
+        assert_eq!(allocator.allocate((50, 18)), Some((40, 0)));
+        assert_eq!(allocator.allocate((101, 1)), None);
+    }
+
+    #[test]
+    fn test_deallocate_merges() {
+        let mut allocator = AtlasAllocator::new((100, 100));
+        assert_eq!(allocator.allocate((30, 10)), Some((0, 0)));
+        assert_eq!(allocator.allocate((30, 10)), Some((30, 0)));
+        assert_eq!(allocator.allocate((30, 10)), Some((60, 0)));
+        allocator.deallocate((0, 0), 30);
+        allocator.deallocate((60, 0), 30);
+        allocator.deallocate((30, 0), 30);
+        assert!(allocator.shelves.is_empty());
+
+        // The space is available for any height again
+        assert_eq!(allocator.allocate((100, 100)), Some((0, 0)));
+    }
+
+    #[test]
+    fn test_atlas_sweep() {
+        let mut atlas = Atlas::new(TextureId::new(
+            0,
+            (ATLAS_SIZE, ATLAS_SIZE),
+            TextureFormat::Rgba8Srgb,
+        ));
+        let (image, position) = atlas.allocate(1, (16, 16)).unwrap();
+        assert_eq!(position, (0, 0));
+        assert_eq!(image.texture, 0);
+        assert_eq!(image.size(), (16, 16));
+        assert_eq!(image.origin, (1, 1));
+        assert!(image.is_in_atlas());
+        assert_eq!(image.tex_coords.min, (1.0 / 1024.0, 1.0 / 1024.0));
+        assert_eq!(image.tex_coords.max, (17.0 / 1024.0, 17.0 / 1024.0));
+
+        atlas.sweep();
+        assert_eq!(atlas.allocations.len(), 1);
+        std::mem::drop(image);
+        atlas.sweep();
+        assert!(atlas.allocations.is_empty());
+    }
+
+    #[test]
+    fn test_mipmaps() {
+        let options = TextureOptions {
+            mipmaps: true,
+            ..TextureOptions::default()
+        };
+        assert_eq!(options.mip_level_count((256, 100)), 9);
+        assert_eq!(options.mip_level_count((1, 1)), 1);
+        assert_eq!(TextureOptions::default().mip_level_count((256, 100)), 1);
+
+        let image = [0, 255, 255, 255, 0, 0, 0, 255];
+        let (dimensions, data) = downsample((2, 1), &image, TextureFormat::Rgba8);
+        assert_eq!(dimensions, (1, 1));
+        assert_eq!(data, vec![0, 128, 128, 255]);
+        // Averaging is done in linear space for sRGB images
+        let (_, data) = downsample((2, 1), &image, TextureFormat::Rgba8Srgb);
+        assert_eq!(data, vec![0, 188, 188, 255]);
+    }
+
+    #[test]
+    fn test_crop() {
+        let image: Vec<u8> = (0..12).collect();
+        assert_eq!(
+            crop((3, 2), &image, 2, (1, 0, 2, 2)),
+            vec![2, 3, 4, 5, 8, 9, 10, 11]
+        );
+        assert_eq!(crop((3, 2), &image, 2, (0, 1, 1, 1)), vec![6, 7]);
+    }
+
+    #[test]
+    fn test_pad_image() {
+        let image = [1, 1, 1, 1, 2, 2, 2, 2];
+        let padded = pad_image((2, 1), &image, 1);
+        assert_eq!(padded.len(), 4 * 3 * 4);
+        let pixels: Vec<u8> = padded.chunks(4).map(|pixel| pixel[0]).collect();
+        assert_eq!(pixels, vec![1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2]);
+    }
+
+    #[test]
+    fn test_glyph_atlas() {
+        let mut atlas = GlyphAtlas::new(2);
+        assert_eq!(
+            atlas.insert((2, 2), &[1, 2, 3, 4, 5, 6, 7, 8]),
+            Some((0, 0))
+        );
+        let (first_row, rows) = atlas.take_changes().unwrap();
+        assert_eq!(first_row, 0);
+        let row_length = 2 * GLYPH_ATLAS_WIDTH as usize;
+        assert_eq!(rows.len(), 2 * row_length);
+        assert_eq!(rows[..4], [1, 2, 3, 4]);
+        assert_eq!(rows[row_length..row_length + 4], [5, 6, 7, 8]);
+        assert_eq!(atlas.take_changes(), None);
+
+        // Images wider than the atlas are rejected without growing it
+        let size = atlas.size();
+        let wide = vec![0; 2 * GLYPH_ATLAS_WIDTH as usize];
+        assert_eq!(atlas.insert((GLYPH_ATLAS_WIDTH, 1), &wide), None);
+        assert_eq!(atlas.size(), size);
+
+        // The atlas grows when it is full, keeping its images
+        let image = vec![0; 2 * 300 * 300];
+        for _ in 0..6 {
+            atlas.insert((300, 300), &image).unwrap();
+        }
+        assert_eq!(atlas.size(), (GLYPH_ATLAS_WIDTH, 1024));
+        let (first_row, rows) = atlas.take_changes().unwrap();
+        assert_eq!(first_row, 0);
+        assert_eq!(rows[..4], [1, 2, 3, 4]);
+
+        // Until it reaches its maximum size
+        let image = vec![0; 2 * 1000 * 1000];
+        while atlas.insert((1000, 1000), &image).is_some() {}
+        assert_eq!(atlas.size(), (GLYPH_ATLAS_WIDTH, GLYPH_ATLAS_MAX_HEIGHT));
+        assert!(atlas.could_fit((1000, 1000)));
+        assert!(!atlas.could_fit((GLYPH_ATLAS_WIDTH, 1)));
+
+        // Emptying it makes room again
+        atlas.take_changes();
+        atlas.clear();
+        assert_eq!(atlas.insert((1000, 1000), &image), Some((0, 0)));
+        assert_eq!(atlas.take_changes().unwrap().0, 0);
     }
 }
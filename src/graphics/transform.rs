@@ -0,0 +1,348 @@
+use crate::rect::Rect;
+
+/// A 2D affine transform, mapping `(x, y)` to `(a*x + c*y + e, b*x + d*y + f)`.
+///
+/// The matrix is stored by rows: `[[a, c, e], [b, d, f]]`.
+/// Angles are in radians, and positive angles rotate clockwise since the Y axis points down.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub matrix: [[f32; 3]; 2],
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::IDENTITY
+    }
+}
+
+impl Transform {
+    /// The transform that does nothing.
+    pub const IDENTITY: Transform = Transform {
+        matrix: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+    };
+
+    pub fn translation(x: f32, y: f32) -> Transform {
+        Transform {
+            matrix: [[1.0, 0.0, x], [0.0, 1.0, y]],
+        }
+    }
+
+    /// Scales relative to the origin.
+    pub fn scale(x: f32, y: f32) -> Transform {
+        Transform {
+            matrix: [[x, 0.0, 0.0], [0.0, y, 0.0]],
+        }
+    }
+
+    /// Rotates around the origin.
+    pub fn rotation(angle: f32) -> Transform {
+        let (sin, cos) = angle.sin_cos();
+        Transform {
+            matrix: [[cos, -sin, 0.0], [sin, cos, 0.0]],
+        }
+    }
+
+    /// Skews along the X axis by `x` and along the Y axis by `y`, relative to the origin.
+    pub fn skew(x: f32, y: f32) -> Transform {
+        Transform {
+            matrix: [[1.0, x.tan(), 0.0], [y.tan(), 1.0, 0.0]],
+        }
+    }
+
+    /// Applies `self` around a point instead of the origin.
+    /// For example, `Transform::rotation(angle).around((50.0, 50.0))` rotates around `(50.0, 50.0)`.
+    pub fn around(self, (x, y): (f32, f32)) -> Transform {
+        Transform::translation(-x, -y)
+            .then(self)
+            .then(Transform::translation(x, y))
+    }
+
+    /// Combines two transforms: the result applies `self` first, and then `next`.
+    pub fn then(self, next: Transform) -> Transform {
+        let [[a1, c1, e1], [b1, d1, f1]] = self.matrix;
+        let [[a2, c2, e2], [b2, d2, f2]] = next.matrix;
+        Transform {
+            matrix: [
+                [a2 * a1 + c2 * b1, a2 * c1 + c2 * d1, a2 * e1 + c2 * f1 + e2],
+                [b2 * a1 + d2 * b1, b2 * c1 + d2 * d1, b2 * e1 + d2 * f1 + f2],
+            ],
+        }
+    }
+
+    /// Returns the transform undoing `self`, or [None] if `self` flattens everything (like a scale of zero).
+    pub fn inverse(&self) -> Option<Transform> {
+        let [[a, c, e], [b, d, f]] = self.matrix;
+        let determinant = a * d - b * c;
+        if determinant.abs() <= f32::EPSILON {
+            return None;
+        }
+        let (a, b, c, d) = (
+            d / determinant,
+            -b / determinant,
+            -c / determinant,
+            a / determinant,
+        );
+        Some(Transform {
+            matrix: [[a, c, -(a * e + c * f)], [b, d, -(b * e + d * f)]],
+        })
+    }
+
+    pub fn apply(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        let [[a, c, e], [b, d, f]] = self.matrix;
+        (a * x + c * y + e, b * x + d * y + f)
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Transform::IDENTITY
+    }
+
+    /// The rows of the matrix, padded for uniform buffers.
+    pub(super) fn rows(&self) -> [[f32; 4]; 2] {
+        let [[a, c, e], [b, d, f]] = self.matrix;
+        [[a, c, e, 0.0], [b, d, f, 0.0]]
+    }
+
+    /// Returns the four corners of a transformed rectangle, ordered as top left, top right, bottom left, bottom right.
+    pub(super) fn apply_rect(&self, rect: Rect) -> [(f32, f32); 4] {
+        [
+            self.apply(rect.min),
+            self.apply((rect.max.0, rect.min.1)),
+            self.apply((rect.min.0, rect.max.1)),
+            self.apply(rect.max),
+        ]
+    }
+
+    /// Returns the smallest axis-aligned rectangle containing a transformed rectangle.
+    pub(super) fn bounding_box(&self, rect: Rect) -> Rect {
+        let corners = self.apply_rect(rect);
+        let mut bounding_box = Rect {
+            min: corners[0],
+            max: corners[0],
+        };
+        for (x, y) in &corners[1..] {
+            bounding_box.min = (bounding_box.min.0.min(*x), bounding_box.min.1.min(*y));
+            bounding_box.max = (bounding_box.max.0.max(*x), bounding_box.max.1.max(*y));
+        }
+        bounding_box
+    }
+}
+
+/// The maximum number of half-planes a [ClipRegion] is made of.
+/// Nested clips sharing the same orientation don't add planes, so this allows three nested clips with different rotations.
+pub(crate) const MAX_CLIP_PLANES: usize = 12;
+
+/// A convex region outside of which nothing is drawn.
+/// It is the intersection of half-planes `a*x + b*y + c >= 0`, in pixels of the render target.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ClipRegion {
+    planes: [[f32; 4]; MAX_CLIP_PLANES],
+    plane_count: usize,
+}
+
+impl ClipRegion {
+    /// A region that contains everything.
+    pub(crate) const NONE: ClipRegion = ClipRegion {
+        planes: [[0.0; 4]; MAX_CLIP_PLANES],
+        plane_count: 0,
+    };
+
+    /// Restricts the region to a rectangle, once transformed.
+    /// When the planes of the intersection don't fit, the region becomes the axis-aligned rectangle containing the intersection.
+    pub(crate) fn intersect(&self, rect: Rect, transform: &Transform) -> ClipRegion {
+        let mut region = *self;
+        let inverse = match transform.inverse() {
+            Some(inverse) => inverse,
+            None => {
+                // Nothing is visible through a flattened rectangle
+                region.add_plane([0.0, 0.0, -1.0]);
+                return region;
+            }
+        };
+
+        // A point is inside if its local coordinates are inside the rectangle
+        let [[a, c, e], [b, d, f]] = inverse.matrix;
+        let planes = [
+            [a, c, e - rect.min.0],
+            [-a, -c, rect.max.0 - e],
+            [b, d, f - rect.min.1],
+            [-b, -d, rect.max.1 - f],
+        ];
+        for plane in planes.iter() {
+            if !region.add_plane(*plane) {
+                return self.bounding_region(rect, transform);
+            }
+        }
+        region
+    }
+
+    /// The axis-aligned rectangle containing the intersection of the region and of a transformed rectangle.
+    /// It draws more than the intersection rather than less, but always fits.
+    fn bounding_region(&self, rect: Rect, transform: &Transform) -> ClipRegion {
+        let corners = [
+            rect.min,
+            (rect.max.0, rect.min.1),
+            rect.max,
+            (rect.min.0, rect.max.1),
+        ];
+        let mut polygon: Vec<(f32, f32)> = corners.iter().map(|p| transform.apply(*p)).collect();
+        // Cut the polygon by each plane
+        for [a, b, c, _] in &self.planes[..self.plane_count] {
+            let distance = |(x, y): (f32, f32)| a * x + b * y + c;
+            let mut clipped = Vec::with_capacity(polygon.len() + 1);
+            for (i, point) in polygon.iter().enumerate() {
+                let next = polygon[(i + 1) % polygon.len()];
+                let (d0, d1) = (distance(*point), distance(next));
+                if d0 >= 0.0 {
+                    clipped.push(*point);
+                }
+                if (d0 >= 0.0) != (d1 >= 0.0) {
+                    let t = d0 / (d0 - d1);
+                    clipped.push((
+                        point.0 + (next.0 - point.0) * t,
+                        point.1 + (next.1 - point.1) * t,
+                    ));
+                }
+            }
+            polygon = clipped;
+        }
+
+        let mut region = ClipRegion::NONE;
+        if polygon.is_empty() {
+            region.add_plane([0.0, 0.0, -1.0]);
+            return region;
+        }
+        let (mut min, mut max) = (polygon[0], polygon[0]);
+        for (x, y) in &polygon[1..] {
+            min = (min.0.min(*x), min.1.min(*y));
+            max = (max.0.max(*x), max.1.max(*y));
+        }
+        region.add_plane([1.0, 0.0, -min.0]);
+        region.add_plane([-1.0, 0.0, max.0]);
+        region.add_plane([0.0, 1.0, -min.1]);
+        region.add_plane([0.0, -1.0, max.1]);
+        region
+    }
+
+    /// Adds a plane to the region, returning `false` if there is no room left for it.
+    fn add_plane(&mut self, [a, b, c]: [f32; 3]) -> bool {
+        // Normalize the plane so that its equation gives distances in pixels
+        let length = (a * a + b * b).sqrt();
+        let plane = if length > f32::EPSILON {
+            [a / length, b / length, c / length, 0.0]
+        } else {
+            [0.0, 0.0, c.signum(), 0.0]
+        };
+
+        // Only keep the tightest of parallel planes
+        for existing in &mut self.planes[..self.plane_count] {
+            if (existing[0] - plane[0]).abs() < 0.0001 && (existing[1] - plane[1]).abs() < 0.0001 {
+                existing[2] = existing[2].min(plane[2]);
+                return true;
+            }
+        }
+
+        if self.plane_count == MAX_CLIP_PLANES {
+            return false;
+        }
+        self.planes[self.plane_count] = plane;
+        self.plane_count += 1;
+        true
+    }
+
+    pub(crate) fn contains(&self, (x, y): (f32, f32)) -> bool {
+        self.planes[..self.plane_count]
+            .iter()
+            .all(|[a, b, c, _]| a * x + b * y + c >= 0.0)
+    }
+
+    /// The planes, padded for the uniform buffer.
+    pub(super) fn planes(&self) -> ([[f32; 4]; MAX_CLIP_PLANES], u32) {
+        (self.planes, self.plane_count as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near((x1, y1): (f32, f32), (x2, y2): (f32, f32)) {
+        assert!(
+            (x1 - x2).abs() < 0.0001 && (y1 - y2).abs() < 0.0001,
+            "{:?} != {:?}",
+            (x1, y1),
+            (x2, y2)
+        );
+    }
+
+    #[test]
+    fn test_transforms() {
+        let transform = Transform::scale(2.0, 3.0).then(Transform::translation(10.0, 20.0));
+        assert_eq!(transform.apply((1.0, 1.0)), (12.0, 23.0));
+
+        let rotation = Transform::rotation(std::f32::consts::FRAC_PI_2);
+        assert_near(rotation.apply((1.0, 0.0)), (0.0, 1.0));
+        let rotation = rotation.around((10.0, 10.0));
+        assert_near(rotation.apply((10.0, 10.0)), (10.0, 10.0));
+        assert_near(rotation.apply((11.0, 10.0)), (10.0, 11.0));
+
+        let skew = Transform::skew(std::f32::consts::FRAC_PI_4, 0.0);
+        assert_near(skew.apply((0.0, 2.0)), (2.0, 2.0));
+    }
+
+    #[test]
+    fn test_inverse() {
+        let transform = Transform::rotation(0.5)
+            .then(Transform::scale(2.0, 0.5))
+            .then(Transform::translation(-4.0, 7.0));
+        let inverse = transform.inverse().unwrap();
+        assert_near(inverse.apply(transform.apply((3.0, -5.0))), (3.0, -5.0));
+        assert!(Transform::scale(0.0, 1.0).inverse().is_none());
+    }
+
+    #[test]
+    fn test_bounding_box() {
+        let transform = Transform::rotation(std::f32::consts::FRAC_PI_2);
+        let bounding_box = transform.bounding_box(Rect::sized(0.0, 0.0, 10.0, 20.0));
+        assert_near(bounding_box.min, (-20.0, 0.0));
+        assert_near(bounding_box.max, (0.0, 10.0));
+    }
+
+    #[test]
+    fn test_clip_region() {
+        let rect = Rect::sized(10.0, 10.0, 100.0, 50.0);
+        let region = ClipRegion::NONE.intersect(rect, &Transform::IDENTITY);
+        assert!(region.contains((10.0, 10.0)));
+        assert!(region.contains((60.0, 30.0)));
+        assert!(!region.contains((9.0, 30.0)));
+        assert!(!region.contains((60.0, 61.0)));
+
+        // Nested clips with the same orientation don't add planes
+        let nested = region.intersect(Rect::sized(0.0, 0.0, 50.0, 200.0), &Transform::IDENTITY);
+        assert_eq!(nested.planes().1, 4);
+        assert!(nested.contains((45.0, 30.0)));
+        assert!(!nested.contains((55.0, 30.0)));
+
+        // Clips follow transforms
+        let rotated = ClipRegion::NONE.intersect(
+            Rect::sized(0.0, 0.0, 10.0, 10.0),
+            &Transform::rotation(std::f32::consts::FRAC_PI_4),
+        );
+        assert!(rotated.contains((0.0, 10.0)));
+        assert!(!rotated.contains((5.0, 0.5)));
+
+        let flattened = ClipRegion::NONE.intersect(rect, &Transform::scale(0.0, 0.0));
+        assert!(!flattened.contains((0.0, 0.0)));
+
+        // Too many planes give the rectangle containing the intersection
+        let square = Rect::sized(-10.0, -10.0, 20.0, 20.0);
+        let mut region = ClipRegion::NONE;
+        for angle in [0.1, 0.2, 0.3, 0.4].iter() {
+            region = region.intersect(square, &Transform::rotation(*angle));
+        }
+        assert_eq!(region.planes().1, 4);
+        assert!(region.contains((0.0, 0.0)));
+        assert!(region.contains((9.0, 0.0)));
+        assert!(!region.contains((11.0, 0.0)));
+        assert!(!region.contains((0.0, -11.0)));
+    }
+}
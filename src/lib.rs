@@ -1,6 +1,7 @@
 pub mod app;
 pub mod area;
 pub mod containers;
+pub mod editing;
 pub mod graphics;
 pub mod prelude;
 pub mod rect;
@@ -11,3 +12,4 @@ pub use prelude::*;
 
 pub use glyph_brush;
 pub use glyph_brush::ab_glyph;
+pub use winit;
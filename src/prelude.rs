@@ -1,9 +1,16 @@
-pub use crate::app::{run, App};
+pub use crate::app::{run, run_with_settings, App, Runtime};
 pub use crate::area::Area;
 pub use crate::containers;
-pub use crate::graphics::{TextureId, WgpuBackend};
+pub use crate::editing::{Clipboard, TextEditor};
+pub use crate::graphics::{
+    AntiAliasing, BlendMode, Color, CustomPipelineDescriptor, CustomPipelineId, CustomShader,
+    EdgeMode, FilterMode, FontDescription, FontStyle, Fonts, ImageOptions, NineSlice, Paint,
+    RichText, RichTextLayout, Screenshot, Settings, Span, TextEffects, TextGamma, TextMetrics,
+    TextOptions, TextureFormat, TextureId, TextureOptions, Transform, WgpuBackend, WrapMode,
+};
 pub use crate::rect::Rect;
 pub use crate::widget::{Widget, WidgetSize};
 
 pub use glyph_brush;
 pub use glyph_brush::ab_glyph;
+pub use winit;
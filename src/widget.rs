@@ -68,6 +68,8 @@ pub trait Widget {
     ///   
     /// The container of the widget will then choose the final size and placement of the widget.  
     /// [Widget::render] will always be called after this.
+    ///
+    /// Text can be measured with [Fonts::global](crate::graphics::Fonts::global).
     fn allocate_area(
         &mut self,
         screen_size: (usize, usize),
@@ -77,4 +79,13 @@ pub trait Widget {
     /// This function will always be called after [Widget::allocate_area].  
     /// The widget should consider that it owns the passed [Area] of the screen.
     fn render(&self, area: Area);
+
+    /// Handles an event of the window, like a key press or a click.
+    /// Returns `true` if the event was consumed.
+    ///
+    /// Containers give every event to all of their widgets, so that a click on a widget can unfocus the others.
+    /// Mouse positions are in pixels of the window.
+    fn handle_event(&mut self, _event: &winit::event::WindowEvent) -> bool {
+        false
+    }
 }